@@ -0,0 +1,365 @@
+use crate::storage::Storage;
+use crate::storage::bundle::{ConflictResolution, ConsoleBundle, ImportOptions, ItemStatus};
+use crate::plugin::loader::PLUGIN_DIR;
+use crate::plugin::manifest::PluginManifest;
+use crate::plugin::registry::{InstallOutcome, PluginArchive, PluginRegistry};
+use std::path::Path;
+
+const USAGE: &str = "Usage:
+  console verify <data_file> [session_id]   Verify session integrity (all sessions if no id)
+  console migrate <data_file>               Upgrade all stored files to the current schema version
+  console snapshot <data_file>              Back up the data directory
+  console restore <data_file> <time_ms>     Restore the latest snapshot taken at or before a time
+  console bundle-export <data_file> <bundle_file> [session_id...]
+                                            Export configuration and chosen sessions for another car
+  console bundle-import <data_file> <bundle_file> [--dry-run] [--use-bundle] [--with-sessions]
+                                            Preview and merge a bundle, keeping local settings on conflict
+                                            unless --use-bundle is given
+  console plugin-pack <manifest_file> <plugin_file> <archive_file>
+                                            Package a plugin and its manifest for installation
+  console plugin-install <archive_file>     Install or upgrade a plugin from an archive
+  console plugin-list                       List installed plugins and check their files
+  console plugin-pin <id>                   Keep a plugin at its installed version
+  console plugin-unpin <id>                 Allow upgrading a pinned plugin again
+  console plugin-remove <id>                Uninstall a plugin";
+
+/// Run a command-line command, returns the process exit code
+pub fn run(args: &[String]) -> i32 {
+    match args.first().map(|s| s.as_str()) {
+        Some("verify") => verify(&args[1..]),
+        Some("migrate") => migrate(&args[1..]),
+        Some("snapshot") => snapshot(&args[1..]),
+        Some("restore") => restore(&args[1..]),
+        Some("bundle-export") => bundle_export(&args[1..]),
+        Some("bundle-import") => bundle_import(&args[1..]),
+        Some("plugin-pack") => plugin_pack(&args[1..]),
+        Some("plugin-install") => plugin_install(&args[1..]),
+        Some("plugin-list") => plugin_list(),
+        Some("plugin-pin") => plugin_pin(&args[1..], true),
+        Some("plugin-unpin") => plugin_pin(&args[1..], false),
+        Some("plugin-remove") => plugin_remove(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            2
+        }
+    }
+}
+
+fn verify(args: &[String]) -> i32 {
+    let Some(data_file) = args.first() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let storage = Storage::new(data_file);
+
+    let session_ids = match args.get(1) {
+        Some(id) => vec![id.clone()],
+        None => match storage.list_session_ids() {
+            Ok(ids) => ids,
+            Err(e) => {
+                eprintln!("Failed to list sessions: {}", e);
+                return 1;
+            }
+        },
+    };
+
+    let mut failures = 0;
+    for id in session_ids {
+        let result = storage.verify_session(&id).and_then(|report| {
+            let manifest = storage.load_integrity_manifest(&id)?;
+            Ok((report, manifest))
+        });
+        match result {
+            Ok((report, manifest)) => {
+                println!("Session {}: {}", id, report.describe());
+                println!("  chain head: {}", manifest.chain_head());
+                if !report.is_valid() {
+                    failures += 1;
+                }
+            }
+            Err(e) => {
+                println!("Session {}: {}", id, e);
+                failures += 1;
+            }
+        }
+    }
+
+    if failures > 0 { 1 } else { 0 }
+}
+
+fn migrate(args: &[String]) -> i32 {
+    let Some(data_file) = args.first() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let storage = Storage::new(data_file);
+
+    match storage.migrate_all() {
+        Ok(report) => {
+            for path in &report.migrated {
+                println!("Migrated   {}", path);
+            }
+            for path in &report.up_to_date {
+                println!("Up to date {}", path);
+            }
+            for (path, error) in &report.failed {
+                println!("Failed     {}: {}", path, error);
+            }
+            if report.failed.is_empty() { 0 } else { 1 }
+        }
+        Err(e) => {
+            eprintln!("Migration failed: {}", e);
+            1
+        }
+    }
+}
+
+fn snapshot(args: &[String]) -> i32 {
+    let Some(data_file) = args.first() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    match Storage::new(data_file).create_snapshot() {
+        Ok(manifest) => {
+            println!(
+                "Snapshot {}: {} files, {} new objects ({} bytes), verified",
+                manifest.timestamp_ms,
+                manifest.files.len(),
+                manifest.new_objects,
+                manifest.new_bytes
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("Snapshot failed: {}", e);
+            1
+        }
+    }
+}
+
+fn restore(args: &[String]) -> i32 {
+    let (Some(data_file), Some(Ok(time_ms))) = (args.first(), args.get(1).map(|t| t.parse::<u64>())) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    match Storage::new(data_file).restore_to_point_in_time(time_ms) {
+        Ok(manifest) => {
+            println!("Restored snapshot {} ({} files)", manifest.timestamp_ms, manifest.files.len());
+            0
+        }
+        Err(e) => {
+            eprintln!("Restore failed: {}", e);
+            1
+        }
+    }
+}
+
+fn bundle_export(args: &[String]) -> i32 {
+    let (Some(data_file), Some(bundle_file)) = (args.first(), args.get(1)) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let result = Storage::new(data_file)
+        .export_bundle(&args[2..])
+        .and_then(|bundle| bundle.save(bundle_file).map(|_| bundle));
+    match result {
+        Ok(bundle) => {
+            println!(
+                "Exported {} files and {} sessions to {}",
+                bundle.files.len(),
+                bundle.sessions.len(),
+                bundle_file
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("Export failed: {}", e);
+            1
+        }
+    }
+}
+
+fn bundle_import(args: &[String]) -> i32 {
+    let (Some(data_file), Some(bundle_file)) = (args.first(), args.get(1)) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let flags = &args[2..];
+    let options = ImportOptions {
+        resolution: if flags.iter().any(|f| f == "--use-bundle") {
+            ConflictResolution::UseBundle
+        } else {
+            ConflictResolution::KeepExisting
+        },
+        include_sessions: flags.iter().any(|f| f == "--with-sessions"),
+    };
+    let storage = Storage::new(data_file);
+
+    let bundle = match ConsoleBundle::load(bundle_file) {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            eprintln!("Failed to read bundle: {}", e);
+            return 1;
+        }
+    };
+    let preview = match storage.preview_bundle_import(&bundle) {
+        Ok(preview) => preview,
+        Err(e) => {
+            eprintln!("Import failed: {}", e);
+            return 1;
+        }
+    };
+    for item in &preview.items {
+        match item.status {
+            ItemStatus::New => println!("New       {} = {}", item.name, item.incoming),
+            ItemStatus::Identical => println!("Same      {}", item.name),
+            ItemStatus::Conflict => println!(
+                "Conflict  {}: {} -> {}",
+                item.name,
+                item.existing.as_deref().unwrap_or(""),
+                item.incoming
+            ),
+        }
+    }
+    if preview.has_conflicts() && options.resolution == ConflictResolution::KeepExisting {
+        println!("{} conflicting settings will keep their local value", preview.conflicts().count());
+    }
+    if flags.iter().any(|f| f == "--dry-run") {
+        return 0;
+    }
+
+    match storage.import_bundle(&bundle, &options) {
+        Ok(report) => {
+            println!(
+                "Updated {} files, kept {} local settings, imported {} sessions",
+                report.updated_files.len(),
+                report.kept_existing.len(),
+                report.imported_sessions.len()
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("Import failed: {}", e);
+            1
+        }
+    }
+}
+
+fn open_registry() -> Option<PluginRegistry> {
+    match PluginRegistry::open(Path::new(PLUGIN_DIR)) {
+        Ok(registry) => Some(registry),
+        Err(e) => {
+            eprintln!("Failed to read the plugin registry: {}", e);
+            None
+        }
+    }
+}
+
+fn plugin_pack(args: &[String]) -> i32 {
+    let (Some(manifest_file), Some(plugin_file), Some(archive_file)) = (args.first(), args.get(1), args.get(2)) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let result = (|| -> Result<PluginArchive, Box<dyn std::error::Error>> {
+        let mut manifest: PluginManifest = serde_json::from_slice(&std::fs::read(manifest_file)?)?;
+        if manifest.entry.is_empty() {
+            manifest.entry = Path::new(plugin_file).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        }
+        let archive = PluginArchive::pack(manifest, std::fs::read(plugin_file)?);
+        archive.verify()?;
+        archive.save(Path::new(archive_file))?;
+        Ok(archive)
+    })();
+    match result {
+        Ok(archive) => {
+            println!("Packed {} {} into {}", archive.manifest.id, archive.manifest.version, archive_file);
+            0
+        }
+        Err(e) => {
+            eprintln!("Packing failed: {}", e);
+            1
+        }
+    }
+}
+
+fn plugin_install(args: &[String]) -> i32 {
+    let Some(archive_file) = args.first() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let Some(mut registry) = open_registry() else {
+        return 1;
+    };
+    match registry.install(Path::new(archive_file)) {
+        Ok(InstallOutcome::Installed { id, version }) => {
+            println!("Installed {} {}", id, version);
+            0
+        }
+        Ok(InstallOutcome::Upgraded { id, from, to }) => {
+            println!("Upgraded {} from {} to {}", id, from, to);
+            0
+        }
+        Err(e) => {
+            eprintln!("Install failed: {}", e);
+            1
+        }
+    }
+}
+
+fn plugin_list() -> i32 {
+    let Some(registry) = open_registry() else {
+        return 1;
+    };
+    let mut failures = 0;
+    for plugin in registry.installed() {
+        let pinned = if plugin.pinned { " (pinned)" } else { "" };
+        match registry.verify(&plugin.id) {
+            Ok(()) => println!("{} {} {}{}", plugin.id, plugin.version, plugin.entry, pinned),
+            Err(e) => {
+                println!("{} {} {}{}: {}", plugin.id, plugin.version, plugin.entry, pinned, e);
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 { 1 } else { 0 }
+}
+
+fn plugin_pin(args: &[String], pinned: bool) -> i32 {
+    let Some(id) = args.first() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let Some(mut registry) = open_registry() else {
+        return 1;
+    };
+    match registry.set_pinned(id, pinned) {
+        Ok(()) => {
+            println!("{} {}", if pinned { "Pinned" } else { "Unpinned" }, id);
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed: {}", e);
+            1
+        }
+    }
+}
+
+fn plugin_remove(args: &[String]) -> i32 {
+    let Some(id) = args.first() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let Some(mut registry) = open_registry() else {
+        return 1;
+    };
+    match registry.remove(id) {
+        Ok(plugin) => {
+            println!("Removed {} {}", plugin.id, plugin.version);
+            0
+        }
+        Err(e) => {
+            eprintln!("Remove failed: {}", e);
+            1
+        }
+    }
+}
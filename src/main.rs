@@ -1,8 +1,22 @@
 use eframe::egui;
 use std::sync::{Arc, Mutex};
 use crate::telemetry::TelemetryData;
+use crate::plugin::{utils, PluginManager, PluginStatus};
+use crate::plugin::events::Event;
+use crate::plugin::input::{KeyBindings, TOGGLE_PROFILER_ACTION};
+use crate::plugin::health::HungPlugins;
+use crate::plugin::permissions::Permission;
+use crate::plugin::schedule::{lock_within, UpdateThread, TICK_INTERVAL, UI_LOCK_WAIT};
+use crate::plugin::watcher::PluginWatcher;
+use crate::storage::Storage;
+use crate::storage::recorder::SessionRecorder;
+use crate::storage::retention::{RetentionPolicy, RetentionReport};
 use crate::ui::{Dashboard, Section, SectionContent, PlayerContent};
+use crate::ui::plugin_profiler::ProfileRow;
+use crate::ui::plugin_settings::PluginSettingsPanel;
+use crate::ui::sessions::SessionCatalogPanel;
 
+mod commands;
 mod plugin;
 mod telemetry;
 mod storage;
@@ -13,17 +27,96 @@ pub struct ConsoleApp {
     dashboard: Option<Dashboard>,
     ui_error: Option<String>,
     font_loaded: bool,
+    storage: Storage,
+    session_catalog: SessionCatalogPanel,
+    /// Shared with the update thread, which updates plugins between frames
+    plugin_manager: Arc<Mutex<PluginManager>>,
+    /// Acquires telemetry and updates plugins; stopped when the app is dropped
+    update_thread: Option<UpdateThread>,
+    plugin_settings: PluginSettingsPanel,
+    /// Plugin files that were refused at startup or on reload
+    plugin_errors: Vec<String>,
+    plugin_watcher: PluginWatcher,
+    last_plugin_poll: std::time::Instant,
+    /// Telemetry alerts seen on the last frame, so each is published once
+    active_alerts: Vec<String>,
+    /// Keys, such as steering wheel buttons, mapped to plugin actions
+    key_bindings: KeyBindings,
+    /// Whether the plugin profiling overlay is shown
+    show_profiler: bool,
+    /// Plugins the watchdog found stuck in a call
+    hung_plugins: HungPlugins,
+    /// What the plugin panels, permission prompt and profiler showed when
+    /// the manager was last free, drawn while the update thread holds it
+    plugin_panels: Vec<PluginPanel>,
+    pending_permission: Option<(String, Permission)>,
+    profile_rows: Vec<ProfileRow>,
+    /// Session being recorded on the update thread, if any
+    recorder: Arc<Mutex<Option<SessionRecorder>>>,
+    /// Outcome of the last recording or retention run
+    recording_status: Option<String>,
+    /// Retention running in the background, and whether to run it again
+    /// once it finishes because another session was saved meanwhile
+    retention: Option<std::thread::JoinHandle<Result<RetentionReport, String>>>,
+    retention_again: bool,
+}
+
+/// A plugin panel as last seen in the manager
+struct PluginPanel {
+    name: String,
+    disabled_reason: Option<String>,
+    status: Option<PluginStatus>,
+}
+
+/// Limits kept on recorded sessions, backups and logs
+fn retention_policy() -> RetentionPolicy {
+    const DAY_SECS: u64 = 24 * 3600;
+    RetentionPolicy {
+        max_total_bytes: Some(2 * 1024 * 1024 * 1024),
+        keep_backups: Some(20),
+        keep_snapshots: Some(10),
+        downsample_after_secs: Some(30 * DAY_SECS),
+        archive_after_secs: Some(90 * DAY_SECS),
+        ..Default::default()
+    }
 }
 
 impl ConsoleApp {
     fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let storage = Storage::new("telemetry.json");
+        let key_bindings_path = storage.key_bindings_path();
         let mut app = ConsoleApp {
-            telemetry_data: Arc::new(Mutex::new(TelemetryData::default())),
+            telemetry_data: Arc::new(Mutex::new(TelemetryData::new())),
             dashboard: None,
             ui_error: None,
             font_loaded: false,
+            storage,
+            session_catalog: SessionCatalogPanel::new(),
+            plugin_manager: Arc::new(Mutex::new(PluginManager::new())),
+            update_thread: None,
+            plugin_settings: PluginSettingsPanel::new(),
+            plugin_errors: Vec::new(),
+            plugin_watcher: PluginWatcher::new(plugin::loader::PLUGIN_DIR),
+            last_plugin_poll: std::time::Instant::now(),
+            active_alerts: Vec::new(),
+            key_bindings: KeyBindings::load(&key_bindings_path).unwrap_or_else(|e| {
+                eprintln!("[INPUT] Failed to read {}: {}", key_bindings_path.display(), e);
+                KeyBindings::default()
+            }),
+            show_profiler: false,
+            hung_plugins: HungPlugins::default(),
+            plugin_panels: Vec::new(),
+            pending_permission: None,
+            profile_rows: Vec::new(),
+            recorder: Arc::new(Mutex::new(None)),
+            recording_status: None,
+            retention: None,
+            retention_again: false,
         };
+        app.start_retention();
         app.load_dashboard();
+        app.load_plugins();
+        app.start_update_thread(cc.egui_ctx.clone());
         // Custom font setup will be done in update()
         app
     }
@@ -49,6 +142,177 @@ impl ConsoleApp {
         }
     }
 
+    fn load_plugins(&mut self) {
+        let mut plugin_manager = self.plugin_manager.lock().unwrap();
+        let _ = plugin_manager.load_configs(&self.storage.plugin_configs_path());
+        if let Err(e) = plugin_manager.open_state(std::path::Path::new(&self.storage.plugin_state_path())) {
+            eprintln!("[PLUGIN STATE] Failed to read plugin state: {}", e);
+        }
+        if let Err(e) = plugin_manager.load_permissions(&self.storage.plugin_permissions_path()) {
+            eprintln!("[PLUGIN PERMISSIONS] Failed to read permission decisions: {}", e);
+        }
+        let report = plugin_manager.load_plugins_from_dir(plugin::loader::PLUGIN_DIR);
+        for (path, error) in &report.rejected {
+            eprintln!("[PLUGIN ERROR] Refused {}: {}", path.display(), error);
+            self.plugin_errors.push(format!("{}: {}", path.display(), error));
+        }
+        for (name, reason) in plugin_manager.init_plugins() {
+            self.plugin_errors.push(format!("{}: {}", name, reason));
+        }
+        plugin_manager.publish(Event::ConsoleStarted);
+        self.hung_plugins = plugin_manager.hung_plugins();
+    }
+
+    /// Acquire telemetry and update plugins off the UI thread, repainting as new data comes in
+    fn start_update_thread(&mut self, ctx: egui::Context) {
+        let recorder = self.recorder.clone();
+        let storage = Storage::new(&self.storage.file_path);
+        let acquire = move |data: &mut TelemetryData| {
+            // The shared frame still holds what the last tick published, plugin channels included
+            if let Some(recorder) = recorder.lock().unwrap().as_mut() {
+                recorder.record(&storage, data);
+            }
+            // Simulated telemetry until a real source is connected
+            data.update_timestamp();
+            data.speed += 0.1;
+            data.rpm += 10.0;
+            if data.rpm > 8000.0 {
+                data.rpm = 1000.0;
+            }
+            ctx.request_repaint();
+        };
+        self.update_thread = Some(UpdateThread::start(self.plugin_manager.clone(), self.telemetry_data.clone(), TICK_INTERVAL, acquire));
+    }
+
+    /// Enforce the retention policy at startup and after each recorded
+    /// session, on its own thread so a slow card doesn't stall the dashboard
+    fn start_retention(&mut self) {
+        if self.retention.is_some() {
+            self.retention_again = true;
+            return;
+        }
+        let storage = Storage::new(&self.storage.file_path);
+        self.retention = Some(std::thread::spawn(move || {
+            storage.apply_retention(&retention_policy()).map_err(|e| e.to_string())
+        }));
+    }
+
+    /// Report a retention run once it finished
+    fn poll_retention(&mut self) {
+        if !self.retention.as_ref().is_some_and(|handle| handle.is_finished()) {
+            return;
+        }
+        let Some(handle) = self.retention.take() else {
+            return;
+        };
+        match handle.join().unwrap_or_else(|_| Err("retention panicked".to_string())) {
+            Ok(report) => {
+                if report.bytes_freed > 0 {
+                    eprintln!("[STORAGE] Retention freed {} bytes", report.bytes_freed);
+                }
+                for (id, error) in &report.skipped_sessions {
+                    eprintln!("[STORAGE] Retention skipped session {}: {}", id, error);
+                }
+            }
+            Err(e) => {
+                eprintln!("[STORAGE] Retention failed: {}", e);
+                self.recording_status = Some(format!("Retention failed: {}", e));
+            }
+        }
+        self.session_catalog.refresh(&self.storage);
+        if std::mem::take(&mut self.retention_again) {
+            self.start_retention();
+        }
+    }
+
+    fn start_recording(&mut self) {
+        *self.recorder.lock().unwrap() = Some(SessionRecorder::new(&retention_policy()));
+        self.recording_status = None;
+    }
+
+    /// Save the recorded session
+    fn stop_recording(&mut self) {
+        let Some(recorder) = self.recorder.lock().unwrap().take() else {
+            return;
+        };
+        self.recording_status = Some(match recorder.finish(&self.storage) {
+            Ok(Some(id)) => format!("Saved session {}", id),
+            Ok(None) => "Nothing was recorded".to_string(),
+            Err(e) => format!("Failed to save session: {}", e),
+        });
+        self.session_catalog.refresh(&self.storage);
+    }
+
+    fn show_recording_controls(&mut self, ui: &mut egui::Ui) {
+        let recording = self.recorder.lock().unwrap().as_ref().map(|r| (r.len(), r.stop_reason().map(str::to_string)));
+        ui.horizontal(|ui| {
+            match recording {
+                None => {
+                    if ui.button("Record").clicked() {
+                        self.start_recording();
+                    }
+                }
+                Some((samples, stop_reason)) => {
+                    if ui.button("Stop and save").clicked() {
+                        self.stop_recording();
+                        // Make room for the next one
+                        self.start_retention();
+                    }
+                    match stop_reason {
+                        Some(reason) => ui.colored_label(egui::Color32::RED, reason),
+                        None => ui.label(format!("Recording: {} samples", samples)),
+                    };
+                }
+            }
+            if let Some(status) = &self.recording_status {
+                ui.label(status);
+            }
+        });
+    }
+
+    /// Tell plugins about alerts that weren't active on the previous frame
+    fn publish_new_alerts(&mut self, wait: std::time::Duration) {
+        let data = self.telemetry_data.lock().unwrap().clone();
+        let Some(plugin_manager) = lock_within(&self.plugin_manager, wait) else {
+            return;
+        };
+        let mut alerts = data.get_alerts();
+        alerts.extend(plugin_manager.channel_alerts(&data));
+        for alert in alerts.iter().filter(|a| !self.active_alerts.contains(a)) {
+            plugin_manager.publish(Event::AlertRaised { message: alert.clone() });
+        }
+        self.active_alerts = alerts;
+    }
+
+    /// Reload plugins whose files changed, at most once a second
+    fn reload_changed_plugins(&mut self, wait: std::time::Duration) {
+        if self.last_plugin_poll.elapsed() < std::time::Duration::from_secs(1) {
+            return;
+        }
+        // Leave the changes to the watcher until they can be applied
+        let Some(mut plugin_manager) = lock_within(&self.plugin_manager, wait) else {
+            return;
+        };
+        self.last_plugin_poll = std::time::Instant::now();
+
+        let changes = self.plugin_watcher.poll();
+        if changes.is_empty() {
+            return;
+        }
+        let report = plugin_manager.apply_plugin_changes(&changes);
+        drop(plugin_manager);
+        for name in report.loaded.iter().chain(&report.reloaded) {
+            println!("[PLUGIN] Loaded {}", name);
+        }
+        for name in &report.unloaded {
+            println!("[PLUGIN] Unloaded {}", name);
+        }
+        for (path, error) in &report.rejected {
+            eprintln!("[PLUGIN ERROR] Refused {}: {}", path.display(), error);
+            self.plugin_errors.push(format!("{}: {}", path.display(), error));
+        }
+    }
+
     fn custom_color_for_section(id: &str) -> egui::Color32 {
         match id {
             "messages" => egui::Color32::from_rgb(40, 40, 80),
@@ -176,21 +440,130 @@ impl ConsoleApp {
 }
 
 impl eframe::App for ConsoleApp {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.update_thread = None;
+        self.stop_recording();
+        // Retention runs again at startup; let a run in progress finish its files
+        if let Some(handle) = self.retention.take() {
+            let _ = handle.join();
+        }
+        // A thread left stuck in a plugin call still holds the manager
+        match lock_within(&self.plugin_manager, std::time::Duration::from_secs(1)) {
+            Some(mut plugin_manager) => {
+                plugin_manager.publish(Event::ConsoleStopping);
+                plugin_manager.dispatch_events();
+            }
+            None => eprintln!("[PLUGIN] Plugins are busy, exiting without telling them"),
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Set up custom font once
         if !self.font_loaded {
             self.setup_custom_fonts(ctx);
         }
-        // Simulate telemetry update
-        {
-            let mut data = self.telemetry_data.lock().unwrap();
-            data.speed += 0.1;
-            data.rpm += 10.0;
-            if data.rpm > 8000.0 {
-                data.rpm = 1000.0;
+        self.poll_retention();
+        // Plugins are updated on the update thread. The UI thread locks the
+        // manager only around its own plugin calls, so the two run in parallel.
+        // If the manager stays locked, e.g. by a hung plugin, this frame is
+        // drawn from what was last seen without waiting for it again.
+        let wait = match lock_within(&self.plugin_manager, UI_LOCK_WAIT) {
+            Some(_) => UI_LOCK_WAIT,
+            None => std::time::Duration::ZERO,
+        };
+        self.reload_changed_plugins(wait);
+        self.publish_new_alerts(wait);
+        // Typing into a text field shouldn't skip tracks
+        if !ctx.wants_keyboard_input() {
+            for action in self.key_bindings.pressed_actions(ctx) {
+                if action == TOGGLE_PROFILER_ACTION {
+                    self.show_profiler = !self.show_profiler;
+                } else if let Some(mut plugin_manager) = lock_within(&self.plugin_manager, wait) {
+                    plugin_manager.dispatch_action(&action);
+                }
             }
         }
 
+        if let Some(plugin_manager) = lock_within(&self.plugin_manager, wait) {
+            self.plugin_panels = plugin_manager
+                .get_all_metadata()
+                .into_iter()
+                .filter_map(|metadata| {
+                    let enabled = plugin_manager.get_plugin(&metadata.name).is_some_and(|p| p.is_enabled());
+                    let disabled_reason = plugin_manager.plugin_health(&metadata.name).and_then(|h| h.disabled_reason.clone());
+                    let status = plugin_manager.plugin_status(&metadata.name);
+                    (enabled || disabled_reason.is_some()).then_some(PluginPanel { name: metadata.name, disabled_reason, status })
+                })
+                .collect();
+            self.pending_permission = plugin_manager.pending_permissions().into_iter().next();
+        }
+        for panel in &self.plugin_panels {
+            // Panels are keyed by plugin name, so a reloaded plugin keeps its position
+            let (plugin_manager, hung_plugins) = (&self.plugin_manager, &self.hung_plugins);
+            egui::Window::new(&panel.name)
+                .id(egui::Id::new(("plugin", &panel.name)))
+                .default_width(320.0)
+                .show(ctx, |ui| match lock_within(plugin_manager, wait) {
+                    Some(mut plugin_manager) => {
+                        plugin_manager.render_plugin(&panel.name, ui);
+                        if panel.disabled_reason.is_some() && ui.button("Re-enable").clicked() {
+                            plugin_manager.enable_plugin(&panel.name);
+                        }
+                    }
+                    None if hung_plugins.contains(&panel.name) => {
+                        ui.colored_label(egui::Color32::RED, "Not responding: a call to this plugin hasn't returned");
+                    }
+                    None => {
+                        ui.colored_label(egui::Color32::YELLOW, "Waiting for plugins");
+                        if let Some(status) = &panel.status {
+                            utils::display_status(ui, status);
+                        }
+                    }
+                });
+        }
+        // Ask about one new permission at a time; the plugin runs without it meanwhile
+        if let Some((name, permission)) = self.pending_permission.clone() {
+            let plugin_manager = &self.plugin_manager;
+            let mut decided = false;
+            egui::Window::new("permission request").collapsible(false).show(ctx, |ui| {
+                ui.label(format!("{} wants to {}.", name, permission.describe()));
+                ui.horizontal(|ui| {
+                    let allow = ui.button("Allow").clicked();
+                    if allow || ui.button("Deny").clicked() {
+                        // Without the manager the prompt stays up to be answered again
+                        if let Some(mut plugin_manager) = lock_within(plugin_manager, UI_LOCK_WAIT) {
+                            plugin_manager.decide_permission(&name, permission.clone(), allow);
+                            if let Err(e) = plugin_manager.save_permissions() {
+                                eprintln!("[PLUGIN PERMISSIONS] Failed to save permission decisions: {}", e);
+                            }
+                            decided = true;
+                        }
+                    }
+                });
+            });
+            if decided {
+                self.pending_permission = None;
+            }
+        }
+        if self.show_profiler {
+            if let Some(plugin_manager) = lock_within(&self.plugin_manager, wait) {
+                self.profile_rows = crate::ui::plugin_profiler::snapshot(&plugin_manager);
+            }
+            let rows = &self.profile_rows;
+            egui::Window::new("plugin profiler")
+                .default_width(480.0)
+                .show(ctx, |ui| {
+                    crate::ui::plugin_profiler::show(ui, rows);
+                });
+        }
+        if !self.plugin_errors.is_empty() {
+            egui::Window::new("plugin errors").show(ctx, |ui| {
+                for error in &self.plugin_errors {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+            });
+        }
+
         if let Some(ref dashboard) = self.dashboard {
             // Each section in its own draggable window
             for section in &dashboard.sections {
@@ -201,6 +574,21 @@ impl eframe::App for ConsoleApp {
                         self.render_section(ui, section);
                     });
             }
+
+            egui::Window::new("sessions")
+                .default_width(480.0)
+                .default_height(320.0)
+                .show(ctx, |ui| {
+                    self.show_recording_controls(ui);
+                    ui.separator();
+                    self.session_catalog.show(ui, &self.storage);
+                });
+
+            egui::Window::new("plugin settings")
+                .default_width(360.0)
+                .show(ctx, |ui| {
+                    self.plugin_settings.show(ui, &self.plugin_manager, wait);
+                });
         } else if let Some(ref err) = self.ui_error {
             egui::CentralPanel::default().show(ctx, |ui| {
                 ui.colored_label(egui::Color32::RED, err);
@@ -210,10 +598,19 @@ impl eframe::App for ConsoleApp {
                 ui.heading("No dashboard layout loaded.");
             });
         }
+        // Logs plugins that took more than their share of this frame
+        if let Some(mut plugin_manager) = lock_within(&self.plugin_manager, wait) {
+            plugin_manager.end_frame();
+        }
     }
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(commands::run(&args));
+    }
+
     let options = eframe::NativeOptions::default();
     let _ = eframe::run_native(
         "Console",
@@ -1,9 +1,31 @@
 use egui::Ui;
 use crate::telemetry::TelemetryData;
 use std::collections::HashMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::Instant;
 use serde::{Serialize, Deserialize};
 
-pub trait Plugin {
+pub mod abi;
+pub mod deps;
+pub mod draw;
+pub mod events;
+pub mod health;
+pub mod input;
+pub mod loader;
+pub mod manifest;
+pub mod permissions;
+pub mod profiler;
+pub mod registry;
+pub mod schedule;
+pub mod settings;
+pub mod sources;
+pub mod state;
+pub mod wasm;
+pub mod watcher;
+
+/// A dashboard plugin. Plugins are updated on the update thread and
+/// rendered on the UI thread, so they must be `Send`.
+pub trait Plugin: Send {
     fn init(&mut self);
     fn update(&mut self, data: &TelemetryData);
     fn render(&self, ui: &mut Ui);
@@ -34,19 +56,91 @@ pub trait Plugin {
         PluginStatus::Ready
     }
     
+    /// Crash or budget overrun the host caught during the last call, as
+    /// opposed to an error the plugin reports in its status
+    fn take_fault(&mut self) -> Option<String> {
+        None
+    }
+    
     /// Cleanup resources when plugin is unloaded
     fn cleanup(&mut self) {}
+    
+    /// Receive services exposed by dependencies, called before `init`
+    fn connect_services(&mut self, _services: &deps::Services) {}
+    
+    /// Expose services to plugins that depend on this one, called after `init`
+    fn provide_services(&mut self, _services: &mut deps::ServiceProvider) {}
+    
+    /// Receive the handle to publish events with, called before `init`
+    fn connect_events(&mut self, _events: events::EventSender) {}
+    
+    /// Receive the plugin's persistent key-value store, called before `init`
+    fn connect_state(&mut self, _state: state::PluginState) {}
+    
+    /// Kinds of events delivered to `on_event`
+    fn subscriptions(&self) -> Vec<events::EventKind> {
+        Vec::new()
+    }
+    
+    /// Handle an event published by another plugin or the host
+    fn on_event(&mut self, _event: &events::Envelope) {}
+    
+    /// Telemetry channels this plugin publishes as a data source
+    fn channels(&self) -> Vec<sources::PluginChannel> {
+        Vec::new()
+    }
+    
+    /// Write this plugin's channels into `data.extra`, called before `update`.
+    /// `data` already holds the built-in channels and those of the plugins
+    /// this one depends on, so derived channels can be computed from them.
+    fn produce(&mut self, _data: &mut TelemetryData) {}
+    
+    /// React to a tap, swipe, control or hardware button. Returns whether
+    /// the plugin used it; unused actions are offered to other plugins.
+    fn handle_input(&mut self, _input: &input::Interaction) -> bool {
+        false
+    }
+    
+    /// Limit what the plugin's host functions may do to the permissions the
+    /// user granted. Only called for plugins the console restricts.
+    fn set_permissions(&mut self, _granted: &permissions::PermissionSet) {}
+    
+    /// Bytes of memory the plugin holds, if it can tell
+    fn memory_usage(&self) -> Option<usize> {
+        None
+    }
+    
+    /// How often `update` should be called. A manifest that declares a
+    /// schedule takes precedence.
+    fn update_schedule(&self) -> schedule::UpdateSchedule {
+        schedule::UpdateSchedule::EveryTick
+    }
 }
 
+/// Missing fields take their defaults, so fields added later don't break
+/// existing plugins; build it with `PluginMetadata::new` or
+/// `..Default::default()`
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PluginMetadata {
     pub name: String,
     pub version: String,
     pub author: String,
     pub description: String,
     pub category: PluginCategory,
+    /// Plugin names with an optional version requirement, e.g. `"Fuel Monitor ^1.2"`
     pub dependencies: Vec<String>,
     pub settings: Vec<PluginSetting>,
+    /// Permissions the plugin asks for, e.g. `"read_channel:rpm"`; see `permissions::Permission`.
+    /// A built-in plugin that declares none is unrestricted.
+    pub permissions: Vec<String>,
+}
+
+impl PluginMetadata {
+    /// Metadata with the given name and defaults for everything else
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), ..Self::default() }
+    }
 }
 
 impl Default for PluginMetadata {
@@ -59,6 +153,7 @@ impl Default for PluginMetadata {
             category: PluginCategory::Other,
             dependencies: Vec::new(),
             settings: Vec::new(),
+            permissions: Vec::new(),
         }
     }
 }
@@ -76,17 +171,27 @@ pub enum PluginCategory {
     Other,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PluginSetting {
     pub name: String,
     pub value_type: SettingType,
     pub default_value: String,
     pub description: String,
     pub required: bool,
+    /// Lowest accepted value of an `Integer` or `Float` setting
+    #[serde(default)]
+    pub min: Option<f64>,
+    /// Highest accepted value of an `Integer` or `Float` setting
+    #[serde(default)]
+    pub max: Option<f64>,
+    /// Accepted values, any value when empty
+    #[serde(default)]
+    pub choices: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub enum SettingType {
+    #[default]
     String,
     Integer,
     Float,
@@ -108,6 +213,32 @@ pub struct PluginManager {
     plugins: Vec<Box<dyn Plugin>>,
     plugin_configs: HashMap<String, HashMap<String, String>>,
     enabled_plugins: Vec<String>,
+    /// File each dynamically loaded plugin came from, by plugin name
+    sources: HashMap<String, std::path::PathBuf>,
+    /// Manifest of each dynamically loaded plugin, by plugin name
+    manifests: HashMap<String, manifest::PluginManifest>,
+    health: HashMap<String, health::PluginHealth>,
+    /// Started on the first supervised call
+    watchdog: Option<health::Watchdog>,
+    services: deps::ServiceRegistry,
+    /// Problems found in the saved config of each plugin
+    config_errors: HashMap<String, Vec<settings::SettingError>>,
+    events: events::EventBus,
+    channels: sources::ChannelRegistry,
+    gestures: HashMap<String, input::GestureDetector>,
+    /// Plugin last interacted with, offered hardware buttons first
+    focused: Option<String>,
+    permission_decisions: permissions::PermissionDecisions,
+    /// What each restricted plugin may do; plugins missing here are trusted
+    grants: HashMap<String, permissions::PermissionSet>,
+    profiler: profiler::Profiler,
+    scheduler: schedule::Scheduler,
+    /// Persistent state of all plugins, shared with their handles
+    state: std::sync::Arc<std::sync::Mutex<state::StateStore>>,
+    /// File plugin configs are saved to, set by `load_configs`
+    configs_path: std::path::PathBuf,
+    /// File permission decisions are saved to, set by `load_permissions`
+    permissions_path: std::path::PathBuf,
 }
 
 impl PluginManager {
@@ -116,21 +247,225 @@ impl PluginManager {
             plugins: Vec::new(),
             plugin_configs: HashMap::new(),
             enabled_plugins: Vec::new(),
+            sources: HashMap::new(),
+            manifests: HashMap::new(),
+            health: HashMap::new(),
+            watchdog: None,
+            services: deps::ServiceRegistry::new(),
+            config_errors: HashMap::new(),
+            events: events::EventBus::new(),
+            channels: sources::ChannelRegistry::default(),
+            gestures: HashMap::new(),
+            focused: None,
+            permission_decisions: permissions::PermissionDecisions::default(),
+            grants: HashMap::new(),
+            profiler: profiler::Profiler::default(),
+            scheduler: schedule::Scheduler::default(),
+            state: Default::default(),
+            configs_path: std::path::PathBuf::from(crate::storage::bundle::PLUGIN_CONFIGS_FILE),
+            permissions_path: std::path::PathBuf::from(permissions::PERMISSIONS_FILE),
+        }
+    }
+    
+    /// Add a plugin to the manager, applying its saved configuration.
+    /// A plugin whose name is already registered is refused and `false` returned.
+    pub fn add_plugin(&mut self, plugin: Box<dyn Plugin>) -> bool {
+        let name = plugin.get_metadata().name;
+        if self.get_plugin(&name).is_some() {
+            eprintln!("[PLUGIN ERROR] Refused {}: a plugin with this name is already registered", name);
+            return false;
+        }
+        self.plugins.push(plugin);
+        self.apply_saved_config(self.plugins.len() - 1);
+        self.refresh_permissions();
+        true
+    }
+    
+    /// Apply the saved config of a plugin, if any, after checking it against
+    /// the plugin's settings. Invalid values fall back to their default.
+    fn apply_saved_config(&mut self, index: usize) {
+        let metadata = self.plugins[index].get_metadata();
+        let Some(config) = self.plugin_configs.get(&metadata.name) else {
+            return;
+        };
+        let (config, errors) = settings::sanitize(&metadata.settings, config);
+        for error in &errors {
+            eprintln!("[PLUGIN CONFIG] {}: {}", metadata.name, error);
+        }
+        self.plugins[index].set_config(config);
+        if errors.is_empty() {
+            self.config_errors.remove(&metadata.name);
+        } else {
+            self.config_errors.insert(metadata.name, errors);
+        }
+    }
+    
+    /// Validate and apply a new config. Nothing is applied if any setting is invalid.
+    pub fn set_plugin_config(&mut self, name: &str, config: HashMap<String, String>) -> Result<(), Vec<settings::SettingError>> {
+        let Some(plugin) = self.get_plugin_mut(name) else {
+            return Err(vec![settings::SettingError { setting: String::new(), message: format!("no plugin named '{}'", name) }]);
+        };
+        settings::Settings::parse(&plugin.get_metadata().settings, &config)?;
+        plugin.set_config(config.clone());
+        self.plugin_configs.insert(name.to_string(), config);
+        self.config_errors.remove(name);
+        Ok(())
+    }
+    
+    /// Problems found in the saved config of a plugin when it was applied
+    pub fn config_errors(&self, name: &str) -> &[settings::SettingError] {
+        self.config_errors.get(name).map(|e| e.as_slice()).unwrap_or(&[])
+    }
+    
+    /// Load every plugin library and WASM module in a folder, refusing
+    /// incompatible or tampered ones and names or ids that are already registered
+    pub fn load_plugins_from_dir(&mut self, dir: &str) -> loader::LoadReport {
+        let mut report = loader::LoadReport::default();
+        let files = match loader::plugin_files(std::path::Path::new(dir)) {
+            Ok(files) => files,
+            Err(e) => {
+                report.rejected.push((dir.into(), e.to_string()));
+                return report;
+            }
+        };
+
+        for path in files {
+            match loader::load_plugin(&path) {
+                Ok((plugin, manifest)) => {
+                    let name = plugin.get_metadata().name;
+                    if self.get_plugin(&name).is_some() {
+                        report.rejected.push((path, format!("a plugin named '{}' is already loaded", name)));
+                        continue;
+                    }
+                    if let Some(owner) = self.plugin_by_id(manifest.id()) {
+                        report.rejected.push((path, format!("id '{}' is already used by '{}'", manifest.id(), owner.get_metadata().name)));
+                        continue;
+                    }
+                    self.add_plugin(plugin);
+                    self.sources.insert(name.clone(), path);
+                    self.manifests.insert(name.clone(), manifest);
+                    report.loaded.push(name);
+                }
+                Err(e) => report.rejected.push((path, e)),
+            }
         }
+        self.refresh_permissions();
+        report
+    }
+    
+    /// Name of the plugin loaded from a file, if any
+    pub fn plugin_from_source(&self, path: &std::path::Path) -> Option<String> {
+        self.sources.iter().find(|(_, p)| p.as_path() == path).map(|(name, _)| name.clone())
     }
     
-    /// Add a plugin to the manager
-    pub fn add_plugin(&mut self, plugin: Box<dyn Plugin>) {
-        let metadata = plugin.get_metadata();
-        let plugin_name = metadata.name.clone();
+    /// Load a new version of a plugin file in place of the running one.
+    ///
+    /// The new version is loaded before the old one is unloaded, so a broken
+    /// build leaves the running plugin untouched. The old plugin's current
+    /// config is re-applied, it keeps its place and enabled state, and a new
+    /// file is simply loaded and initialized.
+    pub fn reload_plugin(&mut self, path: &std::path::Path) -> Result<String, String> {
+        let (mut plugin, manifest) = loader::load_plugin(path)?;
+        let name = plugin.get_metadata().name;
+        let old_name = self.plugin_from_source(path);
+        if old_name.as_deref() != Some(name.as_str()) && self.get_plugin(&name).is_some() {
+            return Err(format!("a plugin named '{}' is already loaded", name));
+        }
+        if let Some(owner) = self.plugin_by_id(manifest.id()).map(|p| p.get_metadata().name).filter(|o| Some(o) != old_name.as_ref()) {
+            return Err(format!("id '{}' is already used by '{}'", manifest.id(), owner));
+        }
         
-        // Load saved configuration if available
-        if let Some(_config) = self.plugin_configs.get(&plugin_name) {
-            // We can't modify the plugin here due to trait object limitations
-            // In a real implementation, you'd need to handle this differently
+        // The new version must fit the dependency graph, for itself and its dependents
+        let mut metadata: Vec<PluginMetadata> = self
+            .get_all_metadata()
+            .into_iter()
+            .filter(|m| Some(&m.name) != old_name.as_ref())
+            .collect();
+        metadata.push(plugin.get_metadata());
+        if let Some((refused, reason)) = deps::resolve(&metadata).rejected.into_iter().next() {
+            return Err(if refused == name { reason } else { format!("would break '{}': {}", refused, reason) });
         }
         
-        self.plugins.push(plugin);
+        let mut index = self.plugins.len();
+        if let Some(old_name) = &old_name {
+            if let Some(position) = self.plugins.iter().position(|p| &p.get_metadata().name == old_name) {
+                let mut old = self.plugins.remove(position);
+                self.plugin_configs.insert(old_name.clone(), old.get_config());
+                plugin.set_enabled(old.is_enabled());
+                old.cleanup();
+                index = position;
+            }
+            self.sources.remove(old_name);
+            self.manifests.remove(old_name);
+            self.services.remove_provider(old_name);
+        }
+        
+        if let Some(old_name) = old_name.as_ref().filter(|n| !self.plugin_configs.contains_key(&name) && **n != name) {
+            if let Some(config) = self.plugin_configs.get(old_name).cloned() {
+                self.plugin_configs.insert(name.clone(), config);
+            }
+        }
+        // A new build starts with a clean record
+        self.health.remove(&name);
+        self.profiler.remove(&name);
+        self.scheduler.remove(&name);
+        self.plugins.insert(index, plugin);
+        self.apply_saved_config(index);
+        self.sources.insert(name.clone(), path.to_path_buf());
+        self.manifests.insert(name.clone(), manifest);
+        self.refresh_permissions();
+        self.sort_by_dependencies();
+        if let Some(index) = self.plugins.iter().position(|p| p.get_metadata().name == name) {
+            self.start_plugin(index);
+        }
+        for (plugin, reason) in self.register_channels() {
+            eprintln!("[PLUGIN ERROR] {}: {}", plugin, reason);
+        }
+        
+        // Dependents drop what they held from the old version
+        for index in 0..self.plugins.len() {
+            let metadata = self.plugins[index].get_metadata();
+            let depends = deps::dependencies(&metadata).unwrap_or_default().iter().any(|d| d.name == name);
+            if depends {
+                let services = deps::Services::new(&self.services, &metadata);
+                self.plugins[index].connect_services(&services);
+            }
+        }
+        Ok(name)
+    }
+    
+    /// Unload the plugin that came from a file that was deleted
+    pub fn unload_plugin_file(&mut self, path: &std::path::Path) -> Option<String> {
+        let name = self.plugin_from_source(path)?;
+        if let Some(plugin) = self.get_plugin(&name) {
+            self.plugin_configs.insert(name.clone(), plugin.get_config());
+        }
+        self.remove_plugin(&name);
+        self.sources.remove(&name);
+        Some(name)
+    }
+    
+    /// Apply plugin file changes reported by a `PluginWatcher`
+    pub fn apply_plugin_changes(&mut self, changes: &[watcher::PluginChange]) -> loader::LoadReport {
+        let mut report = loader::LoadReport::default();
+        for change in changes {
+            match change {
+                watcher::PluginChange::Added(path) | watcher::PluginChange::Modified(path) => {
+                    let existed = self.plugin_from_source(path).is_some();
+                    match self.reload_plugin(path) {
+                        Ok(name) if existed => report.reloaded.push(name),
+                        Ok(name) => report.loaded.push(name),
+                        Err(e) => report.rejected.push((path.clone(), e)),
+                    }
+                }
+                watcher::PluginChange::Removed(path) => {
+                    if let Some(name) = self.unload_plugin_file(path) {
+                        report.unloaded.push(name);
+                    }
+                }
+            }
+        }
+        report
     }
     
     /// Remove a plugin by name
@@ -138,6 +473,13 @@ impl PluginManager {
         if let Some(index) = self.plugins.iter().position(|p| p.get_metadata().name == name) {
             let mut plugin = self.plugins.remove(index);
             plugin.cleanup();
+            self.services.remove_provider(name);
+            self.manifests.remove(name);
+            self.grants.remove(name);
+            self.profiler.remove(name);
+            self.scheduler.remove(name);
+            self.register_channels();
+            self.publish(events::Event::PluginUnloaded { name: name.to_string() });
             Some(plugin)
         } else {
             None
@@ -149,29 +491,457 @@ impl PluginManager {
         self.plugins.iter().find(|p| p.get_metadata().name == name).map(|p| p.as_ref())
     }
     
+    /// Get a plugin by the id in its manifest. Plugins without a manifest
+    /// are identified by their name.
+    pub fn plugin_by_id(&self, id: &str) -> Option<&dyn Plugin> {
+        match self.manifests.iter().find(|(_, m)| m.id() == id) {
+            Some((name, _)) => self.get_plugin(name),
+            None => self.get_plugin(id).filter(|_| !self.manifests.contains_key(id)),
+        }
+    }
+    
+    /// Manifest of a plugin loaded from a file
+    pub fn plugin_manifest(&self, name: &str) -> Option<&manifest::PluginManifest> {
+        self.manifests.get(name)
+    }
+    
     /// Get a mutable plugin by name
     pub fn get_plugin_mut(&mut self, name: &str) -> Option<&mut Box<dyn Plugin>> {
         self.plugins.iter_mut().find(|p| p.get_metadata().name == name)
     }
     
-    /// Update all plugins with telemetry data
-    pub fn update_plugins(&mut self, data: &TelemetryData) {
-        for plugin in &mut self.plugins {
-            if plugin.is_enabled() {
-                plugin.update(data);
+    /// Run one plugin call with panic isolation, timing and health tracking.
+    /// Plugins that keep crashing or overrunning their budget are disabled,
+    /// hung ones at once. Plugins the watchdog marked hung aren't called.
+    fn supervise(&mut self, index: usize, call: &'static str, f: impl FnOnce(&mut dyn Plugin)) {
+        let plugin = &mut self.plugins[index];
+        let name = plugin.get_metadata().name;
+        let watchdog = self.watchdog.get_or_insert_with(health::Watchdog::start);
+        if watchdog.is_hung(&name) {
+            return;
+        }
+        
+        watchdog.begin(&name, call);
+        let started = Instant::now();
+        let result = catch_unwind(AssertUnwindSafe(|| f(plugin.as_mut())));
+        let elapsed = started.elapsed();
+        watchdog.end();
+        self.profiler.record(&name, call, elapsed);
+        
+        let health = self.health.entry(name.clone()).or_default();
+        let verdict = match result {
+            _ if watchdog.is_hung(&name) => Some(format!("{} hung for {:.1}s", call, elapsed.as_secs_f64())),
+            Err(payload) => health.record_failure(call, &health::panic_message(&*payload)),
+            Ok(()) => match plugin.take_fault() {
+                Some(message) => health.record_failure(call, &message),
+                None => health.record_success(call, elapsed),
+            },
+        };
+        if let Some(reason) = verdict {
+            eprintln!("[PLUGIN] Disabled {}: {}", name, reason);
+            plugin.set_enabled(false);
+            health.disabled_reason = Some(reason);
+            self.enabled_plugins.retain(|n| n != &name);
+        }
+    }
+    
+    /// Put plugins after the ones they depend on, dropping those whose
+    /// dependencies can't be satisfied. Returns the dropped plugins with the reason.
+    fn sort_by_dependencies(&mut self) -> Vec<(String, String)> {
+        let resolution = deps::resolve(&self.get_all_metadata());
+        let mut plugins: Vec<Option<Box<dyn Plugin>>> = self.plugins.drain(..).map(Some).collect();
+        self.plugins = resolution.order.iter().filter_map(|&i| plugins[i].take()).collect();
+        for (name, _) in &resolution.rejected {
+            self.sources.remove(name);
+            self.manifests.remove(name);
+            self.enabled_plugins.retain(|n| n != name);
+        }
+        resolution.rejected
+    }
+    
+    /// Connect a plugin to its dependencies' services, initialize it and
+    /// register the services it exposes
+    fn start_plugin(&mut self, index: usize) {
+        let metadata = self.plugins[index].get_metadata();
+        let mut registry = std::mem::take(&mut self.services);
+        let sender = self.events.sender(&metadata.name);
+        let state = state::PluginState::new(&self.state_namespace(&metadata.name), self.state.clone());
+        self.supervise(index, "init", |p| {
+            p.connect_events(sender);
+            p.connect_state(state);
+            p.connect_services(&deps::Services::new(&registry, &metadata));
+            p.init();
+            p.provide_services(&mut deps::ServiceProvider::new(&mut registry, &metadata.name));
+        });
+        self.services = registry;
+        self.publish(events::Event::PluginLoaded { name: metadata.name });
+    }
+    
+    /// Resolve dependencies and initialize plugins, dependencies first.
+    /// Plugins with missing, incompatible or cyclic dependencies are
+    /// refused and returned with the reason, as are channels that clash.
+    pub fn init_plugins(&mut self) -> Vec<(String, String)> {
+        let mut refused = self.sort_by_dependencies();
+        for (name, reason) in &refused {
+            eprintln!("[PLUGIN ERROR] Refused {}: {}", name, reason);
+        }
+        for index in 0..self.plugins.len() {
+            self.start_plugin(index);
+        }
+        refused.extend(self.register_channels());
+        refused
+    }
+    
+    /// Collect the channels plugins publish, returning the refused ones
+    fn register_channels(&mut self) -> Vec<(String, String)> {
+        let mut registry = sources::ChannelRegistry::default();
+        for plugin in &self.plugins {
+            let name = plugin.get_metadata().name;
+            let mut channels = plugin.channels();
+            if let Some(grants) = self.grants.get(&name) {
+                for channel in channels.iter().filter(|c| !grants.can_write_channel(&c.name)) {
+                    registry.rejected.push((name.clone(), format!("not permitted to publish channel '{}'", channel.name)));
+                }
+                channels.retain(|c| grants.can_write_channel(&c.name));
+            }
+            registry.register(&name, channels);
+        }
+        let rejected = registry.rejected.clone();
+        self.channels = registry;
+        rejected
+    }
+    
+    /// Channels published by plugins, with the plugin owning each
+    pub fn plugin_channels(&self) -> &[(String, sources::PluginChannel)] {
+        &self.channels.channels
+    }
+    
+    /// Let data source plugins add their channels to a telemetry frame.
+    ///
+    /// Plugins run in dependency order and may only write the channels they
+    /// registered. A channel that isn't produced, or whose plugin is
+    /// disabled, has no value in the frame.
+    pub fn produce_telemetry(&mut self, data: &mut TelemetryData) {
+        for index in 0..self.plugins.len() {
+            let name = self.plugins[index].get_metadata().name;
+            let owned: Vec<String> = self.channels.owned_by(&name).map(|c| c.name.clone()).collect();
+            if owned.is_empty() {
+                continue;
+            }
+            // Only values written this frame count
+            let mut frame = match self.grants.get(&name) {
+                Some(grants) => grants.filter_telemetry(data),
+                None => data.clone(),
+            };
+            frame.extra.retain(|name, _| !owned.contains(name));
+            if self.plugins[index].is_enabled() {
+                self.supervise(index, "produce", |p| p.produce(&mut frame));
+            }
+            let enabled = self.plugins[index].is_enabled();
+            for channel in owned {
+                match frame.extra.get(&channel) {
+                    Some(value) if enabled && value.is_finite() => {
+                        data.extra.insert(channel, *value);
+                    }
+                    _ => {
+                        data.extra.remove(&channel);
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Alerts raised by plugin channels outside their limits
+    pub fn channel_alerts(&self, data: &TelemetryData) -> Vec<String> {
+        self.channels.channels.iter().filter_map(|(_, c)| c.alert(data)).collect()
+    }
+    
+    /// When a plugin wants to be updated: from its manifest, or else the plugin itself
+    pub fn update_schedule(&self, name: &str) -> Option<schedule::UpdateSchedule> {
+        let plugin = self.get_plugin(name)?;
+        Some(self.manifests.get(name).and_then(|m| m.update_schedule()).unwrap_or_else(|| plugin.update_schedule()))
+    }
+    
+    /// Update the plugins that are due according to their schedule, then
+    /// deliver the events published meanwhile. Returns the number updated.
+    pub fn update_plugins(&mut self, data: &TelemetryData) -> usize {
+        self.update_plugins_at(data, Instant::now())
+    }
+    
+    /// `update_plugins` as if called at `now`
+    pub fn update_plugins_at(&mut self, data: &TelemetryData, now: Instant) -> usize {
+        let mut updated = 0;
+        for index in 0..self.plugins.len() {
+            if !self.plugins[index].is_enabled() {
+                continue;
+            }
+            let name = self.plugins[index].get_metadata().name;
+            let schedule = self.update_schedule(&name).unwrap_or_default();
+            // Watched channels are compared as the plugin may see them
+            let filtered = self.grants.get(&name).map(|g| g.filter_telemetry(data));
+            let frame = filtered.as_ref().unwrap_or(data);
+            if !self.scheduler.due(&name, &schedule, frame, now) {
+                continue;
+            }
+            self.scheduler.record(&name, &schedule, frame, now);
+            self.supervise(index, "update", |p| p.update(frame));
+            updated += 1;
+        }
+        self.dispatch_events();
+        updated
+    }
+    
+    /// Publish an event as the host
+    pub fn publish(&self, event: events::Event) {
+        self.events.sender(events::HOST_SOURCE).publish(event);
+    }
+    
+    /// Handle for publishing events from elsewhere in the console
+    pub fn event_sender(&self, source: &str) -> events::EventSender {
+        self.events.sender(source)
+    }
+    
+    /// Deliver queued events to the enabled plugins subscribed to them,
+    /// never back to their publisher. Events published while handling
+    /// these wait for the next call. Returns the number of deliveries.
+    /// Restricted plugins only publish and receive the topics they may.
+    pub fn dispatch_events(&mut self) -> usize {
+        let mut delivered = 0;
+        for envelope in self.events.drain() {
+            let kind = envelope.event.kind();
+            if self.grants.get(&envelope.source).is_some_and(|g| !g.can_publish(kind.topic())) {
+                eprintln!("[PLUGIN PERMISSIONS] Dropped '{}' event from {}: not permitted", kind.topic(), envelope.source);
+                continue;
+            }
+            for index in 0..self.plugins.len() {
+                let plugin = &self.plugins[index];
+                let name = plugin.get_metadata().name;
+                if !plugin.is_enabled() || name == envelope.source || !plugin.subscriptions().contains(&kind) {
+                    continue;
+                }
+                if self.grants.get(&name).is_some_and(|g| !g.can_subscribe(kind.topic())) {
+                    continue;
+                }
+                self.supervise(index, "on_event", |p| p.on_event(&envelope));
+                delivered += 1;
+            }
+        }
+        delivered
+    }
+    
+    /// Render one plugin, showing its error instead if it failed
+    pub fn render_plugin(&mut self, name: &str, ui: &mut Ui) {
+        let Some(index) = self.plugins.iter().position(|p| p.get_metadata().name == name) else {
+            return;
+        };
+        if self.plugins[index].is_enabled() {
+            input::take_queued(ui);
+            self.supervise(index, "render", |p| p.render(ui));
+            
+            let mut interactions = input::take_queued(ui);
+            let gesture = self.gestures.entry(name.to_string()).or_default().detect(ui, ui.min_rect());
+            // A tap on one of the plugin's own controls is reported as that control only
+            if let Some(gesture) = gesture.filter(|g| interactions.is_empty() || !matches!(g, input::Interaction::Tap { .. })) {
+                interactions.push(gesture);
+            }
+            if !interactions.is_empty() {
+                self.focused = Some(name.to_string());
+            }
+            for interaction in &interactions {
+                self.deliver_input(index, interaction);
             }
         }
+        if let Some(PluginStatus::Error(message)) = self.plugin_status(name) {
+            utils::display_status(ui, &PluginStatus::Error(message));
+        }
+    }
+    
+    /// Pass an interaction to one plugin, returns whether it was used
+    fn deliver_input(&mut self, index: usize, interaction: &input::Interaction) -> bool {
+        let mut handled = false;
+        self.supervise(index, "input", |p| handled = p.handle_input(interaction));
+        handled
+    }
+    
+    /// Send an interaction to a plugin by name, as if it came from its panel
+    pub fn send_input(&mut self, name: &str, interaction: &input::Interaction) -> bool {
+        match self.plugins.iter().position(|p| p.get_metadata().name == name) {
+            Some(index) if self.plugins[index].is_enabled() => {
+                self.focused = Some(name.to_string());
+                self.deliver_input(index, interaction)
+            }
+            _ => false,
+        }
+    }
+    
+    /// Offer a hardware button action to the focused plugin, then to the
+    /// others in order, until one uses it. Returns the plugin that did.
+    /// The press is also published on the event bus.
+    pub fn dispatch_action(&mut self, action: &str) -> Option<String> {
+        self.publish(events::Event::ButtonPressed { button: action.to_string() });
+        let interaction = input::Interaction::Action { name: action.to_string() };
+        let focused = self.focused.as_ref().and_then(|f| self.plugins.iter().position(|p| &p.get_metadata().name == f));
+        let order: Vec<usize> = focused.into_iter().chain((0..self.plugins.len()).filter(|i| Some(*i) != focused)).collect();
+        for index in order {
+            if self.plugins[index].is_enabled() && self.deliver_input(index, &interaction) {
+                return Some(self.plugins[index].get_metadata().name);
+            }
+        }
+        None
+    }
+    
+    /// Plugin last interacted with
+    pub fn focused_plugin(&self) -> Option<&str> {
+        self.focused.as_deref()
     }
     
     /// Render all plugins
-    pub fn render_plugins(&self, ui: &mut Ui) {
-        for plugin in &self.plugins {
-            if plugin.is_enabled() {
-                plugin.render(ui);
+    pub fn render_plugins(&mut self, ui: &mut Ui) {
+        for metadata in self.get_all_metadata() {
+            self.render_plugin(&metadata.name, ui);
+        }
+    }
+    
+    /// Status of a plugin as seen by the manager: failures it caught take
+    /// precedence over what the plugin reports itself
+    pub fn plugin_status(&self, name: &str) -> Option<PluginStatus> {
+        let plugin = self.get_plugin(name)?;
+        let health = self.health.get(name);
+        if let Some(reason) = health.and_then(|h| h.disabled_reason.as_ref()) {
+            return Some(PluginStatus::Error(format!("Disabled: {}", reason)));
+        }
+        if let Some(error) = health.and_then(|h| h.last_error.as_ref()) {
+            return Some(PluginStatus::Error(error.clone()));
+        }
+        Some(plugin.get_status())
+    }
+    
+    /// Permissions a plugin asks for in its metadata and manifest, with the
+    /// declarations that aren't valid permissions
+    fn declared_permissions(&self, name: &str) -> (Vec<permissions::Permission>, Vec<String>) {
+        let mut declared = self.get_plugin(name).map(|p| p.get_metadata().permissions).unwrap_or_default();
+        if let Some(manifest) = self.manifests.get(name) {
+            declared.extend(manifest.requested_permissions());
+        }
+        permissions::parse_all(&declared)
+    }
+    
+    /// Permissions a plugin asks for
+    pub fn requested_permissions(&self, name: &str) -> Vec<permissions::Permission> {
+        self.declared_permissions(name).0
+    }
+    
+    /// Work out what each plugin may do and tell the plugin. Plugins loaded
+    /// from files, and plugins that declare permissions, only get what the
+    /// user allowed; other built-in plugins are trusted. Only what the host
+    /// filters is enforced: native code can bypass these checks.
+    fn refresh_permissions(&mut self) {
+        self.grants.clear();
+        for index in 0..self.plugins.len() {
+            let name = self.plugins[index].get_metadata().name;
+            let (requested, errors) = self.declared_permissions(&name);
+            for error in errors {
+                eprintln!("[PLUGIN PERMISSIONS] {}: {}", name, error);
             }
+            if requested.is_empty() && !self.sources.contains_key(&name) {
+                continue;
+            }
+            let granted = self.permission_decisions.granted(&self.state_namespace(&name), &requested);
+            self.plugins[index].set_permissions(&granted);
+            self.grants.insert(name, granted);
+        }
+        let mut store = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for plugin in &self.plugins {
+            let name = plugin.get_metadata().name;
+            store.set_allowed(&self.state_namespace(&name), self.grants.get(&name).is_none_or(|g| g.can_store()));
         }
     }
     
+    /// What a plugin may do, `None` for trusted plugins
+    pub fn granted_permissions(&self, name: &str) -> Option<&permissions::PermissionSet> {
+        self.grants.get(name)
+    }
+    
+    /// Permissions restricted plugins asked for that the user hasn't
+    /// allowed or denied yet, in plugin order
+    pub fn pending_permissions(&self) -> Vec<(String, permissions::Permission)> {
+        self.plugins
+            .iter()
+            .map(|p| p.get_metadata().name)
+            .filter(|name| self.grants.contains_key(name))
+            .flat_map(|name| {
+                let undecided = self.permission_decisions.undecided(&self.state_namespace(&name), &self.requested_permissions(&name));
+                undecided.into_iter().map(move |p| (name.clone(), p))
+            })
+            .collect()
+    }
+    
+    /// Allow or deny a permission, taking effect immediately
+    pub fn decide_permission(&mut self, name: &str, permission: permissions::Permission, allowed: bool) {
+        let id = self.state_namespace(name);
+        self.permission_decisions.decide(&id, permission, allowed);
+        self.refresh_permissions();
+        for (plugin, reason) in self.register_channels() {
+            eprintln!("[PLUGIN ERROR] {}: {}", plugin, reason);
+        }
+    }
+    
+    /// Save the user's permission decisions
+    pub fn save_permissions(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.permission_decisions.save(&self.permissions_path)
+    }
+    
+    /// Load the user's permission decisions saved at `path`, apply them and
+    /// save them there from now on
+    pub fn load_permissions(&mut self, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.permissions_path = path.to_path_buf();
+        self.permission_decisions = permissions::PermissionDecisions::load(path)?;
+        self.refresh_permissions();
+        Ok(())
+    }
+    
+    /// Namespace of a plugin's state: its manifest id, or its name
+    fn state_namespace(&self, name: &str) -> String {
+        self.manifests.get(name).map(|m| m.id().to_string()).unwrap_or_else(|| name.to_string())
+    }
+    
+    /// Load plugin state saved at `path` and keep it there from now on
+    pub fn open_state(&mut self, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).open(path)
+    }
+    
+    /// Handle on a plugin's state, as the plugin itself gets it
+    pub fn plugin_state(&self, name: &str) -> Option<state::PluginState> {
+        self.get_plugin(name)?;
+        Some(state::PluginState::new(&self.state_namespace(name), self.state.clone()))
+    }
+    
+    /// Timings of a plugin's recent calls
+    pub fn plugin_profile(&self, name: &str) -> Option<&profiler::PluginProfile> {
+        self.profiler.profile(name)
+    }
+    
+    /// Memory a plugin reports using
+    pub fn plugin_memory(&self, name: &str) -> Option<usize> {
+        self.get_plugin(name)?.memory_usage()
+    }
+    
+    /// Mark the end of a UI frame for profiling. Returns the plugins that
+    /// spent longer than the frame budget in their calls during the frame.
+    pub fn end_frame(&mut self) -> Vec<(String, std::time::Duration)> {
+        self.profiler.end_frame()
+    }
+    
+    /// Failure record of a plugin
+    pub fn plugin_health(&self, name: &str) -> Option<&health::PluginHealth> {
+        self.health.get(name)
+    }
+    
+    /// Plugins stuck in a call, readable without locking the manager
+    pub fn hung_plugins(&mut self) -> health::HungPlugins {
+        self.watchdog.get_or_insert_with(health::Watchdog::start).hung_plugins()
+    }
+    
     /// Get all plugin metadata
     pub fn get_all_metadata(&self) -> Vec<PluginMetadata> {
         self.plugins.iter().map(|p| p.get_metadata()).collect()
@@ -179,6 +949,10 @@ impl PluginManager {
     
     /// Enable a plugin
     pub fn enable_plugin(&mut self, name: &str) -> bool {
+        self.health.remove(name);
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.clear_hung(name);
+        }
         if let Some(plugin) = self.get_plugin_mut(name) {
             plugin.set_enabled(true);
             if !self.enabled_plugins.contains(&name.to_string()) {
@@ -206,36 +980,34 @@ impl PluginManager {
         &self.enabled_plugins
     }
     
-    /// Save plugin configurations
+    /// Save plugin configurations to the file they were loaded from
     pub fn save_configs(&self) -> Result<(), Box<dyn std::error::Error>> {
         use std::fs;
-        use serde_json;
+        use crate::storage::schema::{self, FileKind};
         
         let configs: HashMap<String, HashMap<String, String>> = self.plugins
             .iter()
             .map(|p| (p.get_metadata().name.clone(), p.get_config()))
             .collect();
         
-        let json = serde_json::to_string_pretty(&configs)?;
-        fs::write("plugin_configs.json", json)?;
+        let json = schema::encode(FileKind::PluginConfigs, &configs)?;
+        fs::write(&self.configs_path, json)?;
         Ok(())
     }
     
-    /// Load plugin configurations
-    pub fn load_configs(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Load plugin configurations saved at `path` and save them there from now on
+    pub fn load_configs(&mut self, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
         use std::fs;
-        use serde_json;
+        use crate::storage::schema::{self, FileKind};
         
-        if let Ok(json) = fs::read_to_string("plugin_configs.json") {
-            let configs: HashMap<String, HashMap<String, String>> = serde_json::from_str(&json)?;
+        self.configs_path = path.to_path_buf();
+        if let Ok(json) = fs::read(path) {
+            let configs: HashMap<String, HashMap<String, String>> = schema::decode(FileKind::PluginConfigs, &json)?;
             self.plugin_configs = configs;
             
             // Apply configurations to plugins
-            for plugin in &mut self.plugins {
-                let name = plugin.get_metadata().name.clone();
-                if let Some(config) = self.plugin_configs.get(&name) {
-                    plugin.set_config(config.clone());
-                }
+            for index in 0..self.plugins.len() {
+                self.apply_saved_config(index);
             }
         }
         Ok(())
@@ -295,17 +1067,13 @@ pub mod utils {
     
     /// Validate plugin configuration
     pub fn validate_config(metadata: &PluginMetadata, config: &HashMap<String, String>) -> Result<(), String> {
-        for setting in &metadata.settings {
-            if setting.required {
-                if !config.contains_key(&setting.name) {
-                    return Err(format!("Required setting '{}' is missing", setting.name));
-                }
-            }
-        }
-        Ok(())
+        super::settings::Settings::parse(&metadata.settings, config)
+            .map(|_| ())
+            .map_err(|errors| errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "))
     }
     
-    /// Parse setting value based on type
+    /// Parse setting value based on type. `settings::Settings` gives typed
+    /// access to a whole config instead.
     pub fn parse_setting_value(setting_type: &SettingType, value: &str) -> Result<Box<dyn std::any::Any>, String> {
         match setting_type {
             SettingType::String => Ok(Box::new(value.to_string())),
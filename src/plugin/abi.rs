@@ -0,0 +1,230 @@
+//! Stable C ABI between the console and natively compiled plugins.
+//!
+//! Only `#[repr(C)]` data, `extern "C"` functions and NUL-terminated UTF-8
+//! JSON strings cross the boundary, so a plugin built with a different
+//! compiler version keeps working as long as the ABI version matches.
+//!
+//! A plugin library exports two symbols:
+//! - `console_plugin_abi_version() -> u32`, checked before anything else
+//! - `console_plugin_entry() -> *const PluginVTable`
+//!
+//! Both are generated by [`export_plugin!`](crate::export_plugin) for any
+//! type implementing [`ExportedPlugin`]. `src/plugins/speedometer.rs` is
+//! built as the `speedometer` example to show a complete library.
+//!
+//! This version of the ABI carries telemetry, config and a draw list only.
+//! Native plugins don't get the event bus, input, persistent state or
+//! services, and permissions only limit the channels they are sent: native
+//! code can do anything the console process can. Plugins that need those
+//! APIs or a sandbox should be WebAssembly modules.
+
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use crate::telemetry::TelemetryData;
+use super::PluginMetadata;
+use super::draw::DrawList;
+use super::health::panic_message;
+
+/// Version of the plugin ABI. Bump whenever `PluginVTable` or the meaning
+/// of any of its functions changes.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+pub const ABI_VERSION_SYMBOL: &[u8] = b"console_plugin_abi_version\0";
+pub const ENTRY_SYMBOL: &[u8] = b"console_plugin_entry\0";
+
+/// Function table exported by a plugin library.
+///
+/// Strings returned by the plugin are owned by the instance and stay valid
+/// until the next call on that instance. Every function catches panics; a
+/// caught panic is reported through `last_error`.
+#[repr(C)]
+pub struct PluginVTable {
+    pub abi_version: u32,
+    /// `size_of::<PluginVTable>()` on the plugin side
+    pub vtable_size: u32,
+    pub create: extern "C" fn() -> *mut c_void,
+    pub destroy: unsafe extern "C" fn(*mut c_void),
+    /// `PluginMetadata` as JSON
+    pub metadata: unsafe extern "C" fn(*mut c_void) -> *const c_char,
+    pub init: unsafe extern "C" fn(*mut c_void),
+    /// Takes `TelemetryData` as JSON
+    pub update: unsafe extern "C" fn(*mut c_void, *const c_char),
+    /// Returns a `DrawList` as JSON
+    pub render: unsafe extern "C" fn(*mut c_void) -> *const c_char,
+    /// Returns the configuration as a JSON object of strings
+    pub get_config: unsafe extern "C" fn(*mut c_void) -> *const c_char,
+    pub set_config: unsafe extern "C" fn(*mut c_void, *const c_char),
+    pub cleanup: unsafe extern "C" fn(*mut c_void),
+    /// Message of the last caught panic, or null
+    pub last_error: unsafe extern "C" fn(*mut c_void) -> *const c_char,
+}
+
+/// Check the ABI version a plugin reports against ours
+pub fn check_compatibility(plugin_version: u32) -> Result<(), String> {
+    if plugin_version == PLUGIN_ABI_VERSION {
+        Ok(())
+    } else {
+        Err(format!(
+            "plugin was built for ABI version {}, this console supports version {}",
+            plugin_version, PLUGIN_ABI_VERSION
+        ))
+    }
+}
+
+/// Plugin side of the ABI. Unlike `Plugin`, nothing here touches egui:
+/// plugins describe their panel with a `DrawList`. There is no equivalent
+/// of `connect_events`, `handle_input` or `connect_state`, see the module docs.
+pub trait ExportedPlugin: Default {
+    fn metadata(&self) -> PluginMetadata;
+    fn init(&mut self) {}
+    fn update(&mut self, data: &TelemetryData);
+    fn draw(&self) -> DrawList;
+    fn get_config(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
+    fn set_config(&mut self, _config: HashMap<String, String>) {}
+    fn cleanup(&mut self) {}
+}
+
+/// Plugin state behind the opaque instance pointer
+struct Instance<T> {
+    plugin: T,
+    output: CString,
+    error: Option<CString>,
+}
+
+fn to_cstring(text: String) -> CString {
+    CString::new(text).unwrap_or_default()
+}
+
+/// Run `f` on an instance, recording a panic instead of unwinding into the host
+unsafe fn with_instance<T, R>(instance: *mut c_void, fallback: R, f: impl FnOnce(&mut Instance<T>) -> R) -> R {
+    let Some(instance) = (instance as *mut Instance<T>).as_mut() else {
+        return fallback;
+    };
+    match catch_unwind(AssertUnwindSafe(|| f(&mut *instance))) {
+        Ok(result) => {
+            instance.error = None;
+            result
+        }
+        Err(payload) => {
+            instance.error = Some(to_cstring(panic_message(&*payload)));
+            fallback
+        }
+    }
+}
+
+/// Return JSON through the instance's output buffer
+fn output<T>(instance: &mut Instance<T>, json: String) -> *const c_char {
+    instance.output = to_cstring(json);
+    instance.output.as_ptr()
+}
+
+unsafe fn read_json<V: serde::de::DeserializeOwned>(text: *const c_char) -> Option<V> {
+    if text.is_null() {
+        return None;
+    }
+    serde_json::from_slice(CStr::from_ptr(text).to_bytes()).ok()
+}
+
+extern "C" fn ffi_create<T: ExportedPlugin>() -> *mut c_void {
+    match catch_unwind(T::default) {
+        Ok(plugin) => Box::into_raw(Box::new(Instance { plugin, output: CString::default(), error: None })) as *mut c_void,
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn ffi_destroy<T: ExportedPlugin>(instance: *mut c_void) {
+    if !instance.is_null() {
+        let instance = Box::from_raw(instance as *mut Instance<T>);
+        let _ = catch_unwind(AssertUnwindSafe(move || drop(instance)));
+    }
+}
+
+unsafe extern "C" fn ffi_metadata<T: ExportedPlugin>(instance: *mut c_void) -> *const c_char {
+    with_instance::<T, _>(instance, std::ptr::null(), |i| {
+        let json = serde_json::to_string(&i.plugin.metadata()).unwrap_or_default();
+        output(i, json)
+    })
+}
+
+unsafe extern "C" fn ffi_init<T: ExportedPlugin>(instance: *mut c_void) {
+    with_instance::<T, _>(instance, (), |i| i.plugin.init())
+}
+
+unsafe extern "C" fn ffi_update<T: ExportedPlugin>(instance: *mut c_void, data: *const c_char) {
+    let Some(data) = read_json::<TelemetryData>(data) else {
+        return;
+    };
+    with_instance::<T, _>(instance, (), |i| i.plugin.update(&data))
+}
+
+unsafe extern "C" fn ffi_render<T: ExportedPlugin>(instance: *mut c_void) -> *const c_char {
+    with_instance::<T, _>(instance, std::ptr::null(), |i| {
+        let json = serde_json::to_string(&i.plugin.draw()).unwrap_or_default();
+        output(i, json)
+    })
+}
+
+unsafe extern "C" fn ffi_get_config<T: ExportedPlugin>(instance: *mut c_void) -> *const c_char {
+    with_instance::<T, _>(instance, std::ptr::null(), |i| {
+        let json = serde_json::to_string(&i.plugin.get_config()).unwrap_or_default();
+        output(i, json)
+    })
+}
+
+unsafe extern "C" fn ffi_set_config<T: ExportedPlugin>(instance: *mut c_void, config: *const c_char) {
+    let Some(config) = read_json::<HashMap<String, String>>(config) else {
+        return;
+    };
+    with_instance::<T, _>(instance, (), |i| i.plugin.set_config(config))
+}
+
+unsafe extern "C" fn ffi_cleanup<T: ExportedPlugin>(instance: *mut c_void) {
+    with_instance::<T, _>(instance, (), |i| i.plugin.cleanup())
+}
+
+unsafe extern "C" fn ffi_last_error<T: ExportedPlugin>(instance: *mut c_void) -> *const c_char {
+    match (instance as *mut Instance<T>).as_ref().and_then(|i| i.error.as_ref()) {
+        Some(error) => error.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+impl PluginVTable {
+    /// Function table for a plugin type, used by `export_plugin!`
+    pub const fn for_plugin<T: ExportedPlugin>() -> Self {
+        Self {
+            abi_version: PLUGIN_ABI_VERSION,
+            vtable_size: std::mem::size_of::<PluginVTable>() as u32,
+            create: ffi_create::<T>,
+            destroy: ffi_destroy::<T>,
+            metadata: ffi_metadata::<T>,
+            init: ffi_init::<T>,
+            update: ffi_update::<T>,
+            render: ffi_render::<T>,
+            get_config: ffi_get_config::<T>,
+            set_config: ffi_set_config::<T>,
+            cleanup: ffi_cleanup::<T>,
+            last_error: ffi_last_error::<T>,
+        }
+    }
+}
+
+/// Export a type implementing `ExportedPlugin` from a `cdylib` plugin crate
+#[macro_export]
+macro_rules! export_plugin {
+    ($plugin:ty) => {
+        #[no_mangle]
+        pub extern "C" fn console_plugin_abi_version() -> u32 {
+            $crate::plugin::abi::PLUGIN_ABI_VERSION
+        }
+
+        #[no_mangle]
+        pub extern "C" fn console_plugin_entry() -> *const $crate::plugin::abi::PluginVTable {
+            static VTABLE: $crate::plugin::abi::PluginVTable = $crate::plugin::abi::PluginVTable::for_plugin::<$plugin>();
+            &VTABLE
+        }
+    };
+}
@@ -0,0 +1,218 @@
+use semver::{Version, VersionReq};
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use super::PluginMetadata;
+
+/// An entry of `PluginMetadata::dependencies`: a plugin name, optionally
+/// followed by a version requirement, e.g. `"Fuel Monitor >=1.2, <2"`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub name: String,
+    pub requirement: VersionReq,
+}
+
+impl Dependency {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let split = text.find(['<', '>', '=', '^', '~', '*']).unwrap_or(text.len());
+        let name = text[..split].trim();
+        if name.is_empty() {
+            return Err(format!("invalid dependency '{}': missing plugin name", text));
+        }
+        let requirement = match text[split..].trim() {
+            "" => VersionReq::STAR,
+            req => VersionReq::parse(req).map_err(|e| format!("invalid version requirement in '{}': {}", text, e))?,
+        };
+        Ok(Self { name: name.to_string(), requirement })
+    }
+}
+
+/// Dependencies of a plugin, parsed
+pub fn dependencies(metadata: &PluginMetadata) -> Result<Vec<Dependency>, String> {
+    metadata.dependencies.iter().map(|d| Dependency::parse(d)).collect()
+}
+
+/// Load order worked out from plugin dependencies
+#[derive(Debug, Clone, Default)]
+pub struct Resolution {
+    /// Indices of the accepted plugins, each after everything it depends on
+    pub order: Vec<usize>,
+    /// Names of the refused plugins, with the reason
+    pub rejected: Vec<(String, String)>,
+}
+
+/// Check every plugin's dependencies against the others and order them.
+///
+/// A plugin is refused if a dependency is missing, has an incompatible
+/// version, is itself refused, or is part of a cycle. Plugins without
+/// dependencies between them keep their relative order.
+pub fn resolve(plugins: &[PluginMetadata]) -> Resolution {
+    let mut resolution = Resolution::default();
+    let mut rejected: HashMap<usize, String> = HashMap::new();
+    let index_of: HashMap<&str, usize> = plugins.iter().enumerate().map(|(i, m)| (m.name.as_str(), i)).collect();
+
+    // Parse dependencies into edges, checking presence and versions
+    let mut edges: Vec<Vec<usize>> = vec![Vec::new(); plugins.len()];
+    for (i, metadata) in plugins.iter().enumerate() {
+        let parsed = dependencies(metadata).and_then(|deps| {
+            deps.iter()
+                .map(|dep| {
+                    let &j = index_of
+                        .get(dep.name.as_str())
+                        .ok_or_else(|| format!("missing dependency '{}'", dep.name))?;
+                    let version = Version::parse(&plugins[j].version)
+                        .map_err(|_| format!("dependency '{}' has an invalid version '{}'", dep.name, plugins[j].version))?;
+                    if !dep.requirement.matches(&version) {
+                        return Err(format!(
+                            "requires '{}' {}, found version {}",
+                            dep.name, dep.requirement, plugins[j].version
+                        ));
+                    }
+                    Ok(j)
+                })
+                .collect::<Result<Vec<usize>, String>>()
+        });
+        match parsed {
+            Ok(deps) => edges[i] = deps,
+            Err(reason) => {
+                rejected.insert(i, reason);
+            }
+        }
+    }
+
+    // Refusal spreads to everything that depends on a refused plugin
+    loop {
+        let newly: Vec<(usize, String)> = (0..plugins.len())
+            .filter(|i| !rejected.contains_key(i))
+            .filter_map(|i| {
+                edges[i]
+                    .iter()
+                    .find(|j| rejected.contains_key(j))
+                    .map(|&j| (i, format!("dependency '{}' was refused", plugins[j].name)))
+            })
+            .collect();
+        if newly.is_empty() {
+            break;
+        }
+        rejected.extend(newly);
+    }
+
+    // Topological order, always taking the earliest ready plugin
+    let mut done: HashSet<usize> = HashSet::new();
+    loop {
+        let next = (0..plugins.len()).find(|i| {
+            !rejected.contains_key(i) && !done.contains(i) && edges[*i].iter().all(|j| done.contains(j))
+        });
+        let Some(next) = next else { break };
+        done.insert(next);
+        resolution.order.push(next);
+    }
+
+    // Whatever is left waits on a cycle
+    let stuck: Vec<usize> = (0..plugins.len()).filter(|i| !rejected.contains_key(i) && !done.contains(i)).collect();
+    for &i in &stuck {
+        let reason = match find_cycle(i, &edges, &done) {
+            Some(cycle) => format!(
+                "dependency cycle: {}",
+                cycle.iter().map(|&j| plugins[j].name.as_str()).collect::<Vec<_>>().join(" -> ")
+            ),
+            None => "depends on a plugin in a dependency cycle".to_string(),
+        };
+        rejected.insert(i, reason);
+    }
+
+    let mut rejected: Vec<(usize, String)> = rejected.into_iter().collect();
+    rejected.sort_by_key(|(i, _)| *i);
+    resolution.rejected = rejected.into_iter().map(|(i, reason)| (plugins[i].name.clone(), reason)).collect();
+    resolution
+}
+
+/// Path from `start` back to itself through unresolved plugins, if `start` is on a cycle
+fn find_cycle(start: usize, edges: &[Vec<usize>], done: &HashSet<usize>) -> Option<Vec<usize>> {
+    let mut path = vec![start];
+    let mut visited = HashSet::new();
+    fn walk(node: usize, start: usize, edges: &[Vec<usize>], done: &HashSet<usize>, path: &mut Vec<usize>, visited: &mut HashSet<usize>) -> bool {
+        for &next in &edges[node] {
+            if done.contains(&next) {
+                continue;
+            }
+            if next == start {
+                path.push(start);
+                return true;
+            }
+            if visited.insert(next) {
+                path.push(next);
+                if walk(next, start, edges, done, path, visited) {
+                    return true;
+                }
+                path.pop();
+            }
+        }
+        false
+    }
+    walk(start, start, edges, done, &mut path, &mut visited).then_some(path)
+}
+
+/// Services exposed by plugins, looked up by provider name and type
+#[derive(Default)]
+pub struct ServiceRegistry {
+    services: HashMap<(String, TypeId), Arc<dyn Any + Send + Sync>>,
+}
+
+impl ServiceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a service, replacing any of the same type from that provider
+    pub fn register<T: Any + Send + Sync>(&mut self, provider: &str, service: T) {
+        self.services.insert((provider.to_string(), TypeId::of::<T>()), Arc::new(service));
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self, provider: &str) -> Option<Arc<T>> {
+        let service = self.services.get(&(provider.to_string(), TypeId::of::<T>()))?;
+        service.clone().downcast::<T>().ok()
+    }
+
+    /// Drop every service a plugin registered
+    pub fn remove_provider(&mut self, provider: &str) {
+        self.services.retain(|(name, _), _| name != provider);
+    }
+}
+
+/// What a plugin sees of the registry: services of its declared dependencies only
+pub struct Services<'a> {
+    registry: &'a ServiceRegistry,
+    dependencies: Vec<String>,
+}
+
+impl<'a> Services<'a> {
+    pub fn new(registry: &'a ServiceRegistry, metadata: &PluginMetadata) -> Self {
+        let dependencies = dependencies(metadata).unwrap_or_default().into_iter().map(|d| d.name).collect();
+        Self { registry, dependencies }
+    }
+
+    /// Service of type `T` exposed by the dependency `provider`
+    pub fn get<T: Any + Send + Sync>(&self, provider: &str) -> Option<Arc<T>> {
+        if !self.dependencies.iter().any(|d| d == provider) {
+            return None;
+        }
+        self.registry.get(provider)
+    }
+}
+
+/// Handle a plugin registers its own services through
+pub struct ServiceProvider<'a> {
+    registry: &'a mut ServiceRegistry,
+    provider: String,
+}
+
+impl<'a> ServiceProvider<'a> {
+    pub fn new(registry: &'a mut ServiceRegistry, provider: &str) -> Self {
+        Self { registry, provider: provider.to_string() }
+    }
+
+    pub fn provide<T: Any + Send + Sync>(&mut self, service: T) {
+        self.registry.register(&self.provider, service);
+    }
+}
@@ -0,0 +1,154 @@
+use egui::{Color32, RichText, Ui};
+use serde::{Serialize, Deserialize};
+
+/// One drawing instruction produced by a plugin that can't touch egui directly
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DrawCommand {
+    Heading { text: String },
+    Text {
+        text: String,
+        /// RGBA colour, the theme's text colour when missing
+        #[serde(default)]
+        color: Option<[u8; 4]>,
+    },
+    Gauge {
+        label: String,
+        value: f64,
+        #[serde(default)]
+        min: f64,
+        max: f64,
+        #[serde(default)]
+        unit: String,
+    },
+    Rect { width: f32, height: f32, color: [u8; 4] },
+    Circle { radius: f32, color: [u8; 4] },
+    /// Polyline in points relative to the top-left of a `width` x `height` area
+    Line {
+        points: Vec<[f32; 2]>,
+        width: f32,
+        height: f32,
+        color: [u8; 4],
+        #[serde(default = "default_stroke")]
+        stroke: f32,
+    },
+    Separator,
+    /// Clicking it sends `Interaction::Control { id, value: 1.0 }` to the plugin
+    Button { id: String, label: String },
+    /// Moving it sends `Interaction::Control { id, value }` with the new value
+    Slider { id: String, label: String, value: f64, min: f64, max: f64 },
+}
+
+fn default_stroke() -> f32 {
+    1.0
+}
+
+/// Everything a plugin wants shown in its panel for one frame
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DrawList {
+    pub commands: Vec<DrawCommand>,
+}
+
+impl DrawList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn heading(&mut self, text: &str) -> &mut Self {
+        self.commands.push(DrawCommand::Heading { text: text.to_string() });
+        self
+    }
+
+    pub fn text(&mut self, text: &str) -> &mut Self {
+        self.commands.push(DrawCommand::Text { text: text.to_string(), color: None });
+        self
+    }
+
+    pub fn gauge(&mut self, label: &str, value: f64, min: f64, max: f64, unit: &str) -> &mut Self {
+        self.commands.push(DrawCommand::Gauge {
+            label: label.to_string(),
+            value,
+            min,
+            max,
+            unit: unit.to_string(),
+        });
+        self
+    }
+
+    pub fn separator(&mut self) -> &mut Self {
+        self.commands.push(DrawCommand::Separator);
+        self
+    }
+
+    pub fn button(&mut self, id: &str, label: &str) -> &mut Self {
+        self.commands.push(DrawCommand::Button { id: id.to_string(), label: label.to_string() });
+        self
+    }
+
+    pub fn slider(&mut self, id: &str, label: &str, value: f64, min: f64, max: f64) -> &mut Self {
+        self.commands.push(DrawCommand::Slider { id: id.to_string(), label: label.to_string(), value, min, max });
+        self
+    }
+}
+
+fn color(rgba: [u8; 4]) -> Color32 {
+    Color32::from_rgba_unmultiplied(rgba[0], rgba[1], rgba[2], rgba[3])
+}
+
+/// Largest size a plugin may request for a single shape, in points
+const MAX_SHAPE_SIZE: f32 = 2048.0;
+
+/// Render a draw list into a plugin's panel
+pub fn render_draw_list(ui: &mut Ui, list: &DrawList) {
+    for command in &list.commands {
+        match command {
+            DrawCommand::Heading { text } => {
+                ui.heading(text);
+            }
+            DrawCommand::Text { text, color: Some(rgba) } => {
+                ui.label(RichText::new(text).color(color(*rgba)));
+            }
+            DrawCommand::Text { text, color: None } => {
+                ui.label(text);
+            }
+            DrawCommand::Gauge { label, value, min, max, unit } => {
+                let range = (max - min).max(f64::EPSILON);
+                let progress = ((value - min) / range).clamp(0.0, 1.0) as f32;
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(format!("{}: ", label)).strong());
+                    ui.add(egui::ProgressBar::new(progress).text(format!("{:.1} {}", value, unit)));
+                });
+            }
+            DrawCommand::Rect { width, height, color: rgba } => {
+                let size = egui::vec2(width.clamp(0.0, MAX_SHAPE_SIZE), height.clamp(0.0, MAX_SHAPE_SIZE));
+                let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+                ui.painter().rect_filled(rect, 0.0, color(*rgba));
+            }
+            DrawCommand::Circle { radius, color: rgba } => {
+                let radius = radius.clamp(0.0, MAX_SHAPE_SIZE / 2.0);
+                let (rect, _) = ui.allocate_exact_size(egui::vec2(radius * 2.0, radius * 2.0), egui::Sense::hover());
+                ui.painter().circle_filled(rect.center(), radius, color(*rgba));
+            }
+            DrawCommand::Line { points, width, height, color: rgba, stroke } => {
+                let size = egui::vec2(width.clamp(0.0, MAX_SHAPE_SIZE), height.clamp(0.0, MAX_SHAPE_SIZE));
+                let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+                let points = points.iter().map(|[x, y]| rect.min + egui::vec2(*x, *y)).collect();
+                ui.painter_at(rect).add(egui::Shape::line(points, egui::Stroke::new(*stroke, color(*rgba))));
+            }
+            DrawCommand::Separator => {
+                ui.separator();
+            }
+            DrawCommand::Button { id, label } => {
+                if ui.button(label).clicked() {
+                    super::input::queue(ui, id, 1.0);
+                }
+            }
+            DrawCommand::Slider { id, label, value, min, max } => {
+                let mut value = *value;
+                if ui.add(egui::Slider::new(&mut value, *min..=*max).text(label)).changed() {
+                    super::input::queue(ui, id, value);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Source name of events published by the console itself
+pub const HOST_SOURCE: &str = "host";
+
+/// Something that happened, published by a plugin or the host
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    AlertRaised { message: String },
+    LapCompleted { lap: u32, time_secs: f64 },
+    TrackChanged { track: String },
+    ButtonPressed { button: String },
+    SessionStarted { session_id: String },
+    SessionStopped { session_id: String },
+    PluginLoaded { name: String },
+    PluginUnloaded { name: String },
+    ConsoleStarted,
+    ConsoleStopping,
+    /// Event defined by plugins, identified by its topic
+    Custom { topic: String, payload: serde_json::Value },
+}
+
+/// What a plugin subscribes to: a kind of event, or a custom topic
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    AlertRaised,
+    LapCompleted,
+    TrackChanged,
+    ButtonPressed,
+    SessionStarted,
+    SessionStopped,
+    PluginLoaded,
+    PluginUnloaded,
+    ConsoleStarted,
+    ConsoleStopping,
+    Custom(String),
+}
+
+impl EventKind {
+    /// Name used for the kind in `publish:` and `subscribe:` permissions:
+    /// `lap_completed`, or the topic of custom events
+    pub fn topic(&self) -> &str {
+        match self {
+            EventKind::AlertRaised => "alert_raised",
+            EventKind::LapCompleted => "lap_completed",
+            EventKind::TrackChanged => "track_changed",
+            EventKind::ButtonPressed => "button_pressed",
+            EventKind::SessionStarted => "session_started",
+            EventKind::SessionStopped => "session_stopped",
+            EventKind::PluginLoaded => "plugin_loaded",
+            EventKind::PluginUnloaded => "plugin_unloaded",
+            EventKind::ConsoleStarted => "console_started",
+            EventKind::ConsoleStopping => "console_stopping",
+            EventKind::Custom(topic) => topic,
+        }
+    }
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::AlertRaised { .. } => EventKind::AlertRaised,
+            Event::LapCompleted { .. } => EventKind::LapCompleted,
+            Event::TrackChanged { .. } => EventKind::TrackChanged,
+            Event::ButtonPressed { .. } => EventKind::ButtonPressed,
+            Event::SessionStarted { .. } => EventKind::SessionStarted,
+            Event::SessionStopped { .. } => EventKind::SessionStopped,
+            Event::PluginLoaded { .. } => EventKind::PluginLoaded,
+            Event::PluginUnloaded { .. } => EventKind::PluginUnloaded,
+            Event::ConsoleStarted => EventKind::ConsoleStarted,
+            Event::ConsoleStopping => EventKind::ConsoleStopping,
+            Event::Custom { topic, .. } => EventKind::Custom(topic.clone()),
+        }
+    }
+}
+
+/// An event with the name of whoever published it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Envelope {
+    pub source: String,
+    pub event: Event,
+}
+
+/// Handle for publishing events, stamped with the publisher's name.
+/// It can be cloned and sent to other threads.
+#[derive(Clone)]
+pub struct EventSender {
+    source: String,
+    sender: Sender<Envelope>,
+}
+
+impl EventSender {
+    pub fn publish(&self, event: Event) {
+        // The bus only goes away with the manager, when nobody is listening anyway
+        let _ = self.sender.send(Envelope { source: self.source.clone(), event });
+    }
+}
+
+/// Queue of published events waiting to be delivered
+pub struct EventBus {
+    sender: Sender<Envelope>,
+    receiver: Receiver<Envelope>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { sender, receiver }
+    }
+
+    pub fn sender(&self, source: &str) -> EventSender {
+        EventSender { source: source.to_string(), sender: self.sender.clone() }
+    }
+
+    /// Events published so far; later ones wait for the next call
+    pub fn drain(&self) -> Vec<Envelope> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
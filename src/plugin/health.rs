@@ -0,0 +1,162 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Failed calls in a row after which a plugin is disabled
+pub const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+/// Time an `update` may take before it counts as slow
+pub const UPDATE_BUDGET: Duration = Duration::from_millis(20);
+/// Slow updates in a row after which a plugin is disabled
+pub const MAX_CONSECUTIVE_SLOW_UPDATES: u32 = 50;
+/// Time after which a call is reported as hung and its plugin no longer called
+pub const HANG_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How a plugin has behaved under the manager's supervision
+#[derive(Debug, Clone, Default)]
+pub struct PluginHealth {
+    pub consecutive_failures: u32,
+    pub total_failures: u32,
+    pub consecutive_slow_updates: u32,
+    pub last_error: Option<String>,
+    /// Set when the manager disabled the plugin, with the reason
+    pub disabled_reason: Option<String>,
+}
+
+impl PluginHealth {
+    /// Record a call that returned normally, returns the reason to disable the plugin if any
+    pub fn record_success(&mut self, call: &str, elapsed: Duration) -> Option<String> {
+        self.consecutive_failures = 0;
+        self.last_error = None;
+        if call == "update" {
+            if elapsed > UPDATE_BUDGET {
+                self.consecutive_slow_updates += 1;
+            } else {
+                self.consecutive_slow_updates = 0;
+            }
+            if self.consecutive_slow_updates >= MAX_CONSECUTIVE_SLOW_UPDATES {
+                return Some(format!(
+                    "update exceeded its {}ms budget {} times in a row",
+                    UPDATE_BUDGET.as_millis(),
+                    self.consecutive_slow_updates
+                ));
+            }
+        }
+        None
+    }
+
+    /// Record a panic or budget overrun, returns the reason to disable the plugin if any
+    pub fn record_failure(&mut self, call: &str, message: &str) -> Option<String> {
+        self.consecutive_failures += 1;
+        self.total_failures += 1;
+        self.last_error = Some(format!("{} failed: {}", call, message));
+        (self.consecutive_failures >= MAX_CONSECUTIVE_FAILURES)
+            .then(|| format!("{} failed {} times in a row: {}", call, self.consecutive_failures, message))
+    }
+}
+
+/// Text of a panic payload
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "plugin panicked".to_string())
+}
+
+struct ActiveCall {
+    plugin: String,
+    call: &'static str,
+    started: Instant,
+    reported: bool,
+}
+
+/// Plugins the watchdog marked hung, readable while the manager is locked
+#[derive(Clone, Default)]
+pub struct HungPlugins(Arc<Mutex<HashSet<String>>>);
+
+impl HungPlugins {
+    pub fn contains(&self, plugin: &str) -> bool {
+        self.0.lock().map(|hung| hung.contains(plugin)).unwrap_or(false)
+    }
+}
+
+/// Background thread that marks plugins whose calls don't return as hung.
+///
+/// A hung call can't be interrupted: the thread that made it waits until it
+/// returns. It is reported while it is still running so the culprit shows
+/// up in the log and the UI, and the plugin is not called again until it is
+/// re-enabled.
+pub struct Watchdog {
+    active: Arc<Mutex<Option<ActiveCall>>>,
+    hung: HungPlugins,
+    stop: Arc<AtomicBool>,
+}
+
+impl Watchdog {
+    pub fn start() -> Self {
+        let active: Arc<Mutex<Option<ActiveCall>>> = Arc::new(Mutex::new(None));
+        let hung = HungPlugins::default();
+        let stop = Arc::new(AtomicBool::new(false));
+        let (thread_active, thread_hung, thread_stop) = (active.clone(), hung.0.clone(), stop.clone());
+        thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(100));
+                let Ok(mut active) = thread_active.lock() else {
+                    return;
+                };
+                if let Some(call) = active.as_mut() {
+                    if !call.reported && call.started.elapsed() >= HANG_TIMEOUT {
+                        call.reported = true;
+                        if let Ok(mut hung) = thread_hung.lock() {
+                            hung.insert(call.plugin.clone());
+                        }
+                        eprintln!(
+                            "[PLUGIN WATCHDOG] {} has been in {} for {:.1}s",
+                            call.plugin,
+                            call.call,
+                            call.started.elapsed().as_secs_f64()
+                        );
+                    }
+                }
+            }
+        });
+        Self { active, hung, stop }
+    }
+
+    /// Whether a call of the plugin overran `HANG_TIMEOUT`
+    pub fn is_hung(&self, plugin: &str) -> bool {
+        self.hung.contains(plugin)
+    }
+
+    /// Handle to the hung plugins that doesn't need the manager
+    pub fn hung_plugins(&self) -> HungPlugins {
+        self.hung.clone()
+    }
+
+    /// Let a hung plugin be called again
+    pub fn clear_hung(&self, plugin: &str) {
+        if let Ok(mut hung) = self.hung.0.lock() {
+            hung.remove(plugin);
+        }
+    }
+
+    pub fn begin(&self, plugin: &str, call: &'static str) {
+        if let Ok(mut active) = self.active.lock() {
+            *active = Some(ActiveCall { plugin: plugin.to_string(), call, started: Instant::now(), reported: false });
+        }
+    }
+
+    pub fn end(&self) {
+        if let Ok(mut active) = self.active.lock() {
+            *active = None;
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
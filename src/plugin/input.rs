@@ -0,0 +1,151 @@
+use egui::{Pos2, Rect, Ui, Vec2};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Key bindings file, in the data directory next to the plugin configs
+pub const KEY_BINDINGS_FILE: &str = "key_bindings.json";
+
+/// Action that toggles the plugin profiling overlay, handled by the console itself
+pub const TOGGLE_PROFILER_ACTION: &str = "toggle_profiler";
+
+/// Movement below which a press counts as a tap, in points
+pub const TAP_SLOP: f32 = 10.0;
+/// Movement from which a press counts as a swipe, in points
+pub const SWIPE_DISTANCE: f32 = 60.0;
+/// Press duration from which a tap becomes a long press, in seconds
+pub const LONG_PRESS_SECS: f64 = 0.6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwipeDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// User input delivered to a plugin's `handle_input`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Interaction {
+    /// Tap or click, relative to the panel's top-left corner
+    Tap { x: f32, y: f32 },
+    LongPress { x: f32, y: f32 },
+    Swipe { direction: SwipeDirection },
+    /// A control drawn by the plugin was used, see `queue`
+    Control { id: String, value: f64 },
+    /// A hardware button, mapped to an action name by the key bindings
+    Action { name: String },
+}
+
+fn queue_id() -> egui::Id {
+    egui::Id::new("plugin_input_queue")
+}
+
+/// Report a control used during `render`. The manager passes it to the
+/// plugin's `handle_input` once rendering is done, when the plugin can
+/// change its state.
+pub fn queue(ui: &Ui, id: &str, value: f64) {
+    let interaction = Interaction::Control { id: id.to_string(), value };
+    ui.ctx().data_mut(|d| d.get_temp_mut_or_default::<Vec<Interaction>>(queue_id()).push(interaction));
+}
+
+/// Take the controls queued since the last call
+pub(crate) fn take_queued(ui: &Ui) -> Vec<Interaction> {
+    ui.ctx().data_mut(|d| std::mem::take(d.get_temp_mut_or_default::<Vec<Interaction>>(queue_id())))
+}
+
+/// Classify a finished press by how far and how long the pointer moved.
+/// Returns `None` for drags that are neither taps nor swipes.
+pub fn classify_gesture(start: Pos2, end: Pos2, duration_secs: f64, panel: Rect) -> Option<Interaction> {
+    let delta: Vec2 = end - start;
+    if delta.length() < TAP_SLOP {
+        let (x, y) = (start.x - panel.min.x, start.y - panel.min.y);
+        return Some(if duration_secs >= LONG_PRESS_SECS { Interaction::LongPress { x, y } } else { Interaction::Tap { x, y } });
+    }
+    if delta.length() < SWIPE_DISTANCE {
+        return None;
+    }
+    let direction = if delta.x.abs() >= delta.y.abs() {
+        if delta.x < 0.0 { SwipeDirection::Left } else { SwipeDirection::Right }
+    } else if delta.y < 0.0 {
+        SwipeDirection::Up
+    } else {
+        SwipeDirection::Down
+    };
+    Some(Interaction::Swipe { direction })
+}
+
+/// Follows presses that start inside a plugin panel and turns them into gestures
+#[derive(Debug, Clone, Default)]
+pub struct GestureDetector {
+    press: Option<(Pos2, f64)>,
+}
+
+impl GestureDetector {
+    pub fn detect(&mut self, ui: &Ui, panel: Rect) -> Option<Interaction> {
+        let (pressed, released, pos, time) = ui.input(|i| {
+            (i.pointer.primary_pressed(), i.pointer.primary_released(), i.pointer.interact_pos(), i.time)
+        });
+        if pressed {
+            self.press = pos.filter(|p| panel.contains(*p)).map(|p| (p, time));
+        }
+        if !released {
+            return None;
+        }
+        let (start, started) = self.press.take()?;
+        classify_gesture(start, pos.unwrap_or(start), time - started, panel)
+    }
+}
+
+/// Mapping from key names (as egui names them, e.g. "Left", "Space", "F1")
+/// to action names. Steering wheel controls usually show up as such keys.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyBindings {
+    pub bindings: BTreeMap<String, String>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let bindings = [
+            ("Left", "previous"),
+            ("Right", "next"),
+            ("Up", "volume_up"),
+            ("Down", "volume_down"),
+            ("Space", "play_pause"),
+            ("Enter", "select"),
+            ("Escape", "back"),
+            ("F12", TOGGLE_PROFILER_ACTION),
+        ];
+        Self { bindings: bindings.iter().map(|(k, a)| (k.to_string(), a.to_string())).collect() }
+    }
+}
+
+impl KeyBindings {
+    /// Read bindings from a JSON file, the defaults if there is none
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        match std::fs::read(path) {
+            Ok(json) => Ok(serde_json::from_slice(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn action(&self, key: egui::Key) -> Option<&str> {
+        self.bindings.get(key.name()).map(|a| a.as_str())
+    }
+
+    /// Actions of the bound keys pressed this frame
+    pub fn pressed_actions(&self, ctx: &egui::Context) -> Vec<String> {
+        ctx.input(|i| {
+            i.events
+                .iter()
+                .filter_map(|event| match event {
+                    egui::Event::Key { key, pressed: true, repeat: false, .. } => self.action(*key).map(|a| a.to_string()),
+                    _ => None,
+                })
+                .collect()
+        })
+    }
+}
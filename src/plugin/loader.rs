@@ -0,0 +1,269 @@
+use egui::Ui;
+use libloading::Library;
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::telemetry::TelemetryData;
+use super::{Plugin, PluginMetadata, PluginStatus};
+use super::abi::{self, PluginVTable};
+use super::draw::{self, DrawList};
+use super::manifest::PluginManifest;
+
+/// Folder scanned for plugin libraries at startup
+pub const PLUGIN_DIR: &str = "plugins";
+
+/// A plugin loaded from a shared library through the C ABI
+pub struct NativePlugin {
+    vtable: *const PluginVTable,
+    instance: *mut c_void,
+    metadata: PluginMetadata,
+    enabled: bool,
+    status: PluginStatus,
+    /// Panic caught during the last call
+    fault: Option<String>,
+    library: Option<Library>,
+    /// Private copy the library was opened from, see `shadow_copy`
+    shadow: Option<PathBuf>,
+}
+
+// The manager calls a plugin from one thread at a time, under its lock
+unsafe impl Send for NativePlugin {}
+
+impl NativePlugin {
+    /// Create a plugin instance from a function table, checking the ABI first.
+    ///
+    /// # Safety
+    /// `vtable` must point to a valid `PluginVTable` that stays alive as long
+    /// as `library`, or for the whole program when `library` is `None`.
+    pub unsafe fn from_vtable(vtable: *const PluginVTable, library: Option<Library>) -> Result<Self, String> {
+        let table = vtable.as_ref().ok_or("plugin returned no function table")?;
+        abi::check_compatibility(table.abi_version)?;
+        if (table.vtable_size as usize) < std::mem::size_of::<PluginVTable>() {
+            return Err(format!("plugin function table is too small ({} bytes)", table.vtable_size));
+        }
+
+        let instance = (table.create)();
+        if instance.is_null() {
+            return Err("plugin failed to create an instance".to_string());
+        }
+        let mut plugin = Self {
+            vtable,
+            instance,
+            metadata: PluginMetadata::default(),
+            enabled: true,
+            status: PluginStatus::Loading,
+            fault: None,
+            library,
+            shadow: None,
+        };
+        plugin.metadata = plugin
+            .read_json((table.metadata)(instance))
+            .ok_or_else(|| plugin.last_error().unwrap_or_else(|| "plugin returned invalid metadata".to_string()))?;
+        Ok(plugin)
+    }
+
+    /// Open a plugin library and check its ABI version before calling into it
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let shadow = shadow_copy(path).map_err(|e| format!("cannot copy library: {}", e))?;
+        match Self::load_from(&shadow) {
+            Ok(mut plugin) => {
+                plugin.shadow = Some(shadow);
+                Ok(plugin)
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&shadow);
+                Err(e)
+            }
+        }
+    }
+
+    fn load_from(path: &Path) -> Result<Self, String> {
+        unsafe {
+            let library = Library::new(path).map_err(|e| format!("cannot open library: {}", e))?;
+            let version = *library
+                .get::<extern "C" fn() -> u32>(abi::ABI_VERSION_SYMBOL)
+                .map_err(|_| "not a console plugin (no console_plugin_abi_version export)".to_string())?;
+            abi::check_compatibility(version())?;
+            let entry = *library
+                .get::<extern "C" fn() -> *const PluginVTable>(abi::ENTRY_SYMBOL)
+                .map_err(|_| "missing console_plugin_entry export".to_string())?;
+            let vtable = entry();
+            Self::from_vtable(vtable, Some(library))
+        }
+    }
+
+    fn table(&self) -> &PluginVTable {
+        unsafe { &*self.vtable }
+    }
+
+    /// Copy a JSON string returned by the plugin and parse it
+    fn read_json<T: serde::de::DeserializeOwned>(&self, text: *const c_char) -> Option<T> {
+        if text.is_null() {
+            return None;
+        }
+        serde_json::from_slice(unsafe { CStr::from_ptr(text) }.to_bytes()).ok()
+    }
+
+    /// Message of the panic caught during the last call, if any
+    pub fn last_error(&self) -> Option<String> {
+        let error = unsafe { (self.table().last_error)(self.instance) };
+        if error.is_null() {
+            return None;
+        }
+        Some(unsafe { CStr::from_ptr(error) }.to_string_lossy().to_string())
+    }
+
+    /// Record the outcome of a call in the plugin status
+    fn check_error(&mut self) {
+        self.fault = self.last_error();
+        self.status = match &self.fault {
+            Some(error) => PluginStatus::Error(error.clone()),
+            None => PluginStatus::Ready,
+        };
+    }
+
+    /// Fetch the plugin's draw list for this frame
+    pub fn draw_list(&self) -> Option<DrawList> {
+        self.read_json(unsafe { (self.table().render)(self.instance) })
+    }
+}
+
+impl Drop for NativePlugin {
+    fn drop(&mut self) {
+        unsafe { (self.table().destroy)(self.instance) };
+        // Unload the library before deleting the file it was opened from
+        drop(self.library.take());
+        if let Some(shadow) = &self.shadow {
+            let _ = fs::remove_file(shadow);
+        }
+    }
+}
+
+/// Copy a library to a unique temporary path before opening it.
+///
+/// The loader caches libraries by path, so reopening a rebuilt library at the
+/// same path can return the old code; a fresh path always loads the new
+/// build, and lets the compiler overwrite the original while it's in use.
+fn shadow_copy(path: &Path) -> std::io::Result<PathBuf> {
+    let dir = std::env::temp_dir().join("console-plugins");
+    fs::create_dir_all(&dir)?;
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+    let shadow = dir.join(format!("{}-{}-{}.{}", stem, std::process::id(), stamp, extension));
+    fs::copy(path, &shadow)?;
+    Ok(shadow)
+}
+
+impl Plugin for NativePlugin {
+    fn init(&mut self) {
+        unsafe { (self.table().init)(self.instance) };
+        self.check_error();
+    }
+
+    fn update(&mut self, data: &TelemetryData) {
+        let Ok(json) = serde_json::to_string(data).map(|j| CString::new(j).unwrap_or_default()) else {
+            return;
+        };
+        unsafe { (self.table().update)(self.instance, json.as_ptr()) };
+        self.check_error();
+    }
+
+    fn render(&self, ui: &mut Ui) {
+        match self.draw_list() {
+            Some(list) => draw::render_draw_list(ui, &list),
+            None => super::utils::display_status(
+                ui,
+                &PluginStatus::Error(self.last_error().unwrap_or_else(|| "plugin returned an invalid draw list".to_string())),
+            ),
+        }
+    }
+
+    fn get_metadata(&self) -> PluginMetadata {
+        self.metadata.clone()
+    }
+
+    fn get_config(&self) -> HashMap<String, String> {
+        self.read_json(unsafe { (self.table().get_config)(self.instance) }).unwrap_or_default()
+    }
+
+    fn set_config(&mut self, config: HashMap<String, String>) {
+        let json = CString::new(serde_json::to_string(&config).unwrap_or_default()).unwrap_or_default();
+        unsafe { (self.table().set_config)(self.instance, json.as_ptr()) };
+        self.check_error();
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn get_status(&self) -> PluginStatus {
+        if self.enabled { self.status.clone() } else { PluginStatus::Disabled }
+    }
+
+    fn take_fault(&mut self) -> Option<String> {
+        self.fault.take()
+    }
+
+    fn cleanup(&mut self) {
+        unsafe { (self.table().cleanup)(self.instance) };
+    }
+}
+
+/// Outcome of scanning the plugin folder
+#[derive(Debug, Clone, Default)]
+pub struct LoadReport {
+    /// Names of the plugins registered
+    pub loaded: Vec<String>,
+    /// Names of the plugins replaced by a new version
+    pub reloaded: Vec<String>,
+    /// Names of the plugins whose file was removed
+    pub unloaded: Vec<String>,
+    /// Files that were refused, with the reason
+    pub rejected: Vec<(PathBuf, String)>,
+}
+
+/// Shared libraries and WASM modules in a folder, sorted by name
+pub fn plugin_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == std::env::consts::DLL_EXTENSION || e == "wasm") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Load a plugin file with the runtime matching its extension, after
+/// checking it against its manifest. The manifest's name and id are
+/// filled in from the plugin when it doesn't declare them.
+pub fn load_plugin(path: &Path) -> Result<(Box<dyn Plugin>, PluginManifest), String> {
+    let (plugin, mut manifest): (Box<dyn Plugin>, PluginManifest) = if path.extension().is_some_and(|e| e == "wasm") {
+        let plugin = super::wasm::WasmPlugin::load(path)?;
+        let manifest = plugin.manifest().clone();
+        (Box::new(plugin), manifest)
+    } else {
+        let manifest = PluginManifest::for_plugin(path)?;
+        let library = fs::read(path).map_err(|e| format!("cannot read library: {}", e))?;
+        manifest.verify(&library, abi::PLUGIN_ABI_VERSION)?;
+        (Box::new(NativePlugin::load(path)?), manifest)
+    };
+    manifest.name = plugin.get_metadata().name;
+    if manifest.id.is_empty() {
+        manifest.id = manifest.name.clone();
+    }
+    Ok((plugin, manifest))
+}
@@ -0,0 +1,175 @@
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::storage::integrity::sha256_hex;
+use super::schedule::UpdateSchedule;
+
+pub const DEFAULT_MAX_MEMORY_BYTES: usize = 16 * 1024 * 1024;
+pub const DEFAULT_FUEL_PER_CALL: u64 = 10_000_000;
+
+fn default_max_memory_bytes() -> usize {
+    DEFAULT_MAX_MEMORY_BYTES
+}
+
+fn default_fuel_per_call() -> u64 {
+    DEFAULT_FUEL_PER_CALL
+}
+
+/// Identity, integrity and grants of a plugin, read from the `.json` file
+/// next to its library or module
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PluginManifest {
+    /// Unique identifier such as `com.example.wideband`; the name when empty
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub description: String,
+    /// ABI version the plugin was built for, checked before loading
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abi_version: Option<u32>,
+    /// Permissions the plugin asks for
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    /// Plugin file name, relative to the manifest
+    #[serde(default)]
+    pub entry: String,
+    /// SHA-256 of the plugin file as lowercase hex, not checked when empty
+    #[serde(default)]
+    pub checksum: String,
+    /// Largest size linear memory may grow to (WASM only)
+    #[serde(default = "default_max_memory_bytes")]
+    pub max_memory_bytes: usize,
+    /// Instructions a single call may execute before it is aborted (WASM only)
+    #[serde(default = "default_fuel_per_call")]
+    pub fuel_per_call: u64,
+    /// Files and folders the plugin may read; relative paths in a manifest
+    /// file are relative to the manifest
+    #[serde(default)]
+    pub read_paths: Vec<PathBuf>,
+    /// `host:port` addresses the plugin may connect to
+    #[serde(default)]
+    pub network_hosts: Vec<String>,
+    /// Updates per second the plugin wants, every tick when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_rate: Option<f64>,
+    /// Channels whose changes trigger an update, instead of a fixed rate
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub update_on: Vec<String>,
+    /// Folder relative paths the plugin asks for are resolved against: the
+    /// manifest file's folder, or the working directory when empty
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+impl Default for PluginManifest {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            name: String::new(),
+            version: String::new(),
+            author: String::new(),
+            description: String::new(),
+            abi_version: None,
+            permissions: Vec::new(),
+            entry: String::new(),
+            checksum: String::new(),
+            max_memory_bytes: DEFAULT_MAX_MEMORY_BYTES,
+            fuel_per_call: DEFAULT_FUEL_PER_CALL,
+            read_paths: Vec::new(),
+            network_hosts: Vec::new(),
+            update_rate: None,
+            update_on: Vec::new(),
+            base_dir: PathBuf::new(),
+        }
+    }
+}
+
+/// Manifest file of a plugin file: `wideband.wasm` is described by `wideband.json`
+pub fn manifest_path(plugin_path: &Path) -> PathBuf {
+    plugin_path.with_extension("json")
+}
+
+impl PluginManifest {
+    /// Load the manifest next to a plugin file, falling back to defaults named after the file
+    pub fn for_plugin(plugin_path: &Path) -> Result<Self, String> {
+        let path = manifest_path(plugin_path);
+        let mut manifest = if path.exists() {
+            let content = fs::read(&path).map_err(|e| format!("cannot read manifest: {}", e))?;
+            serde_json::from_slice(&content).map_err(|e| format!("invalid manifest: {}", e))?
+        } else {
+            Self::default()
+        };
+        if manifest.name.is_empty() {
+            manifest.name = plugin_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        }
+        if manifest.entry.is_empty() {
+            manifest.entry = plugin_path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        }
+        if let Some(dir) = path.parent() {
+            manifest.base_dir = dir.to_path_buf();
+            manifest.read_paths = manifest.read_paths.iter().map(|granted| manifest.resolve(granted)).collect();
+        }
+        Ok(manifest)
+    }
+
+    /// A path the plugin asked for, relative ones taken from `base_dir`
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        self.base_dir.join(path)
+    }
+
+    /// The id, or the name for manifests that don't declare one
+    pub fn id(&self) -> &str {
+        if self.id.is_empty() { &self.name } else { &self.id }
+    }
+
+    /// Check a plugin file against the manifest before loading it
+    pub fn verify(&self, content: &[u8], runtime_abi_version: u32) -> Result<(), String> {
+        if let Some(version) = self.abi_version.filter(|v| *v != runtime_abi_version) {
+            return Err(format!(
+                "manifest declares ABI version {}, this console supports version {}",
+                version, runtime_abi_version
+            ));
+        }
+        if !self.checksum.is_empty() && !self.checksum.eq_ignore_ascii_case(&sha256_hex(content)) {
+            return Err(format!("checksum of '{}' does not match its manifest", self.entry));
+        }
+        Ok(())
+    }
+
+    /// Declared permissions, including the paths and hosts the manifest grants
+    pub fn requested_permissions(&self) -> Vec<String> {
+        let mut requested = self.permissions.clone();
+        requested.extend(self.read_paths.iter().map(|path| format!("read_file:{}", path.display())));
+        requested.extend(self.network_hosts.iter().map(|host| format!("network:{}", host)));
+        requested
+    }
+
+    /// Whether a path lies inside one of the granted paths
+    pub fn may_read(&self, path: &Path) -> bool {
+        let Ok(path) = path.canonicalize() else {
+            return false;
+        };
+        self.read_paths
+            .iter()
+            .filter_map(|granted| granted.canonicalize().ok())
+            .any(|granted| path.starts_with(granted))
+    }
+
+    pub fn may_connect(&self, address: &str) -> bool {
+        self.network_hosts.iter().any(|host| host == address)
+    }
+    
+    /// Update schedule the manifest declares, if any
+    pub fn update_schedule(&self) -> Option<UpdateSchedule> {
+        if !self.update_on.is_empty() {
+            return Some(UpdateSchedule::OnChange(self.update_on.clone()));
+        }
+        self.update_rate.map(UpdateSchedule::hz)
+    }
+}
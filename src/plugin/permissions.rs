@@ -0,0 +1,255 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::storage::schema::{self, FileKind};
+use crate::telemetry::{TelemetryData, CHANNELS};
+
+/// Decisions the user made on permission requests, in the data directory
+pub const PERMISSIONS_FILE: &str = "plugin_permissions.json";
+
+/// Scope covering everything of a kind, e.g. `read_channel:*`
+pub const ANY: &str = "*";
+
+/// Channel name covering latitude, longitude and altitude
+pub const LOCATION_CHANNEL: &str = "location";
+
+/// Something a plugin may do, written `kind:scope` in its metadata or
+/// manifest, e.g. `read_channel:rpm`, `read_file:logs` or `storage`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Permission {
+    /// Read a telemetry channel, built-in or published by a plugin
+    ReadChannel(String),
+    /// Publish a telemetry channel as a data source
+    WriteChannel(String),
+    /// Read files inside a folder
+    ReadFile(PathBuf),
+    /// Open a serial port, e.g. `/dev/ttyUSB0`
+    SerialPort(String),
+    /// Keep data in the plugin's own persistent store
+    Storage,
+    /// Publish events of a kind (`lap_completed`) or custom topic
+    Publish(String),
+    /// Receive events of a kind or custom topic
+    Subscribe(String),
+    /// Connect to a `host:port` address
+    Network(String),
+}
+
+impl FromStr for Permission {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let (kind, scope) = match text.split_once(':') {
+            Some((kind, scope)) => (kind, scope),
+            None => (text, ""),
+        };
+        if scope.is_empty() && kind != "storage" {
+            return Err(format!("permission '{}' needs a scope, e.g. '{}:{}'", text, kind, ANY));
+        }
+        Ok(match kind {
+            "read_channel" => Permission::ReadChannel(scope.to_string()),
+            "write_channel" => Permission::WriteChannel(scope.to_string()),
+            "read_file" => Permission::ReadFile(PathBuf::from(scope)),
+            "serial_port" => Permission::SerialPort(scope.to_string()),
+            "storage" if scope.is_empty() => Permission::Storage,
+            "publish" => Permission::Publish(scope.to_string()),
+            "subscribe" => Permission::Subscribe(scope.to_string()),
+            "network" => Permission::Network(scope.to_string()),
+            _ => return Err(format!("unknown permission '{}'", text)),
+        })
+    }
+}
+
+impl TryFrom<String> for Permission {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        text.parse()
+    }
+}
+
+impl From<Permission> for String {
+    fn from(permission: Permission) -> Self {
+        permission.to_string()
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Permission::ReadChannel(scope) => write!(f, "read_channel:{}", scope),
+            Permission::WriteChannel(scope) => write!(f, "write_channel:{}", scope),
+            Permission::ReadFile(path) => write!(f, "read_file:{}", path.display()),
+            Permission::SerialPort(scope) => write!(f, "serial_port:{}", scope),
+            Permission::Storage => write!(f, "storage"),
+            Permission::Publish(scope) => write!(f, "publish:{}", scope),
+            Permission::Subscribe(scope) => write!(f, "subscribe:{}", scope),
+            Permission::Network(scope) => write!(f, "network:{}", scope),
+        }
+    }
+}
+
+fn scoped(scope: &str, what: &str, all: &str) -> String {
+    if scope == ANY { all.to_string() } else { format!("{} '{}'", what, scope) }
+}
+
+impl Permission {
+    /// What the permission allows, phrased for the permission prompt
+    pub fn describe(&self) -> String {
+        match self {
+            Permission::ReadChannel(scope) => format!("read {}", scoped(scope, "channel", "all telemetry channels")),
+            Permission::WriteChannel(scope) => format!("publish {}", scoped(scope, "channel", "any telemetry channel")),
+            Permission::ReadFile(path) => format!("read files in '{}'", path.display()),
+            Permission::SerialPort(scope) => format!("open {}", scoped(scope, "serial port", "any serial port")),
+            Permission::Storage => "store data on this head unit".to_string(),
+            Permission::Publish(scope) => format!("publish {}", scoped(scope, "events", "any event")),
+            Permission::Subscribe(scope) => format!("receive {}", scoped(scope, "events", "all events")),
+            Permission::Network(scope) if scope == ANY => "connect to any address".to_string(),
+            Permission::Network(scope) => format!("connect to {}", scope),
+        }
+    }
+
+    /// Whether granting this permission also grants `other`
+    pub fn covers(&self, other: &Permission) -> bool {
+        let matches = |granted: &str, wanted: &str| granted == ANY || granted == wanted;
+        match (self, other) {
+            (Permission::ReadChannel(a), Permission::ReadChannel(b))
+            | (Permission::WriteChannel(a), Permission::WriteChannel(b))
+            | (Permission::SerialPort(a), Permission::SerialPort(b))
+            | (Permission::Publish(a), Permission::Publish(b))
+            | (Permission::Subscribe(a), Permission::Subscribe(b))
+            | (Permission::Network(a), Permission::Network(b)) => matches(a, b),
+            (Permission::ReadFile(granted), Permission::ReadFile(path)) => {
+                match (granted.canonicalize(), path.canonicalize()) {
+                    (Ok(granted), Ok(path)) => path.starts_with(granted),
+                    _ => false,
+                }
+            }
+            (Permission::Storage, Permission::Storage) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Parse declared permissions, reporting the ones that aren't valid
+pub fn parse_all(declared: &[String]) -> (Vec<Permission>, Vec<String>) {
+    let mut permissions = Vec::new();
+    let mut errors = Vec::new();
+    for text in declared {
+        match text.parse::<Permission>() {
+            Ok(permission) if !permissions.contains(&permission) => permissions.push(permission),
+            Ok(_) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+    (permissions, errors)
+}
+
+/// Permissions granted to a plugin, checked by the host before acting for it.
+/// These checks are the only enforcement: a WebAssembly plugin can't get
+/// around them, but native code can do anything the console can.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PermissionSet {
+    pub granted: Vec<Permission>,
+}
+
+impl PermissionSet {
+    pub fn allows(&self, permission: &Permission) -> bool {
+        self.granted.iter().any(|g| g.covers(permission))
+    }
+
+    pub fn can_read_channel(&self, name: &str) -> bool {
+        self.allows(&Permission::ReadChannel(name.to_string()))
+    }
+
+    pub fn can_write_channel(&self, name: &str) -> bool {
+        self.allows(&Permission::WriteChannel(name.to_string()))
+    }
+
+    pub fn can_read_file(&self, path: &Path) -> bool {
+        self.allows(&Permission::ReadFile(path.to_path_buf()))
+    }
+
+    pub fn can_open_serial_port(&self, port: &str) -> bool {
+        self.allows(&Permission::SerialPort(port.to_string()))
+    }
+
+    pub fn can_store(&self) -> bool {
+        self.allows(&Permission::Storage)
+    }
+
+    pub fn can_publish(&self, topic: &str) -> bool {
+        self.allows(&Permission::Publish(topic.to_string()))
+    }
+
+    pub fn can_subscribe(&self, topic: &str) -> bool {
+        self.allows(&Permission::Subscribe(topic.to_string()))
+    }
+
+    pub fn can_connect(&self, address: &str) -> bool {
+        self.allows(&Permission::Network(address.to_string()))
+    }
+
+    /// Copy of a frame with only the channels that may be read; the others
+    /// are left at zero, or absent for location and plugin channels
+    pub fn filter_telemetry(&self, data: &TelemetryData) -> TelemetryData {
+        let mut filtered = TelemetryData { timestamp: data.timestamp, ..Default::default() };
+        for channel in CHANNELS.iter().filter(|c| self.can_read_channel(c.name)) {
+            if let Some(value) = data.channel_value(channel.name) {
+                filtered.set_channel_value(channel.name, value);
+            }
+        }
+        if self.can_read_channel(LOCATION_CHANNEL) {
+            filtered.latitude = data.latitude;
+            filtered.longitude = data.longitude;
+            filtered.altitude = data.altitude;
+        }
+        filtered.extra = data.extra.iter().filter(|(name, _)| self.can_read_channel(name)).map(|(n, v)| (n.clone(), *v)).collect();
+        filtered
+    }
+}
+
+/// Whether the user allowed or denied each permission, by plugin id
+/// (the manifest id, or the name of a plugin without a manifest)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PermissionDecisions {
+    pub plugins: BTreeMap<String, BTreeMap<Permission, bool>>,
+}
+
+impl PermissionDecisions {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        match fs::read(path) {
+            Ok(content) => schema::decode(FileKind::PluginPermissions, &content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, schema::encode(FileKind::PluginPermissions, self)?)?;
+        Ok(())
+    }
+
+    pub fn decision(&self, plugin: &str, permission: &Permission) -> Option<bool> {
+        self.plugins.get(plugin)?.get(permission).copied()
+    }
+
+    pub fn decide(&mut self, plugin: &str, permission: Permission, allowed: bool) {
+        self.plugins.entry(plugin.to_string()).or_default().insert(permission, allowed);
+    }
+
+    /// The requested permissions the user allowed
+    pub fn granted(&self, plugin: &str, requested: &[Permission]) -> PermissionSet {
+        let granted = requested.iter().filter(|p| self.decision(plugin, p) == Some(true)).cloned().collect();
+        PermissionSet { granted }
+    }
+
+    /// The requested permissions the user hasn't decided on yet
+    pub fn undecided(&self, plugin: &str, requested: &[Permission]) -> Vec<Permission> {
+        requested.iter().filter(|p| self.decision(plugin, p).is_none()).cloned().collect()
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Calls kept per plugin and call type
+pub const PROFILE_WINDOW: usize = 300;
+/// Time all of a plugin's calls together may take in one frame at 60 Hz
+pub const FRAME_BUDGET: Duration = Duration::from_micros(16_667);
+/// Shortest time between two over-budget warnings for the same plugin
+const WARNING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Statistics over the calls in the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CallSummary {
+    pub samples: usize,
+    pub min: Duration,
+    pub avg: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// Durations of the latest calls of one type
+#[derive(Debug, Clone, Default)]
+pub struct CallStats {
+    samples: VecDeque<Duration>,
+}
+
+impl CallStats {
+    pub fn record(&mut self, elapsed: Duration) {
+        if self.samples.len() >= PROFILE_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(elapsed);
+    }
+
+    pub fn summary(&self) -> Option<CallSummary> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort();
+        let samples = sorted.len();
+        let total: Duration = sorted.iter().sum();
+        Some(CallSummary {
+            samples,
+            min: sorted[0],
+            avg: total / samples as u32,
+            // Nearest-rank percentile
+            p99: sorted[(samples * 99).div_ceil(100) - 1],
+            max: sorted[samples - 1],
+        })
+    }
+}
+
+/// Timings of one plugin by call type (`update`, `render`, ...)
+#[derive(Debug, Clone, Default)]
+pub struct PluginProfile {
+    pub calls: BTreeMap<&'static str, CallStats>,
+    /// Time spent in the frame being measured
+    frame_time: Duration,
+    last_warning: Option<Instant>,
+}
+
+impl PluginProfile {
+    pub fn summary(&self, call: &str) -> Option<CallSummary> {
+        self.calls.get(call).and_then(|c| c.summary())
+    }
+}
+
+/// Rolling per-plugin timings, and the frame budget check
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    profiles: HashMap<String, PluginProfile>,
+}
+
+impl Profiler {
+    pub fn record(&mut self, plugin: &str, call: &'static str, elapsed: Duration) {
+        let profile = self.profiles.entry(plugin.to_string()).or_default();
+        profile.calls.entry(call).or_default().record(elapsed);
+        profile.frame_time += elapsed;
+    }
+
+    pub fn profile(&self, plugin: &str) -> Option<&PluginProfile> {
+        self.profiles.get(plugin)
+    }
+
+    pub fn remove(&mut self, plugin: &str) {
+        self.profiles.remove(plugin);
+    }
+
+    /// Close the current frame. Returns the plugins whose calls took longer
+    /// than the frame budget in total, logging each at most every few seconds.
+    pub fn end_frame(&mut self) -> Vec<(String, Duration)> {
+        let mut over_budget = Vec::new();
+        for (name, profile) in &mut self.profiles {
+            let frame_time = std::mem::take(&mut profile.frame_time);
+            if frame_time <= FRAME_BUDGET {
+                continue;
+            }
+            if profile.last_warning.is_none_or(|t| t.elapsed() >= WARNING_INTERVAL) {
+                eprintln!(
+                    "[PLUGIN PROFILER] {} took {:.1}ms this frame, over the {:.1}ms budget",
+                    name,
+                    frame_time.as_secs_f64() * 1000.0,
+                    FRAME_BUDGET.as_secs_f64() * 1000.0
+                );
+                profile.last_warning = Some(Instant::now());
+            }
+            over_budget.push((name.clone(), frame_time));
+        }
+        over_budget.sort();
+        over_budget
+    }
+}
@@ -0,0 +1,281 @@
+//! Plugins installed from archive files.
+//!
+//! An archive carries a manifest and the plugin file it describes. Installing
+//! one writes both into the plugin folder, where the hot reload watcher picks
+//! them up, and records the plugin in `registry.json` so it can later be
+//! upgraded, pinned at its version or removed.
+
+use semver::Version;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::storage::integrity::sha256_hex;
+use crate::storage::schema::{self, FileKind};
+use super::abi::PLUGIN_ABI_VERSION;
+use super::manifest::{self, PluginManifest};
+use super::wasm::WASM_ABI_VERSION;
+
+/// Index of installed plugins, kept in the plugin folder
+pub const REGISTRY_FILE: &str = "registry.json";
+
+/// Plugin file contents stored as a hex string
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 || !hex.is_ascii() {
+            return Err(serde::de::Error::custom("content is not hex"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+/// ABI version of the runtime that loads a plugin file, by its extension
+fn runtime_abi_version(entry: &str) -> Option<u32> {
+    match Path::new(entry).extension()?.to_str()? {
+        "wasm" => Some(WASM_ABI_VERSION),
+        extension if extension == std::env::consts::DLL_EXTENSION => Some(PLUGIN_ABI_VERSION),
+        _ => None,
+    }
+}
+
+/// A plugin packaged for installation: its manifest and its plugin file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginArchive {
+    pub manifest: PluginManifest,
+    #[serde(with = "hex_bytes")]
+    pub content: Vec<u8>,
+}
+
+impl PluginArchive {
+    /// Package a plugin file, recording its checksum in the manifest
+    pub fn pack(mut manifest: PluginManifest, content: Vec<u8>) -> Self {
+        manifest.checksum = sha256_hex(&content);
+        Self { manifest, content }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, schema::encode(FileKind::PluginArchive, self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        schema::decode(FileKind::PluginArchive, &fs::read(path)?)
+    }
+
+    /// Check the archive is complete, untampered and loadable by this console
+    pub fn verify(&self) -> Result<(), String> {
+        let manifest = &self.manifest;
+        if manifest.id.is_empty() {
+            return Err("manifest has no id".to_string());
+        }
+        Version::parse(&manifest.version).map_err(|e| format!("version '{}' is invalid: {}", manifest.version, e))?;
+        let entry = Path::new(&manifest.entry);
+        if manifest.entry.starts_with('.') || entry.file_name() != Some(entry.as_os_str()) {
+            return Err(format!("entry '{}' is not a plain file name", manifest.entry));
+        }
+        if manifest::manifest_path(entry) == Path::new(REGISTRY_FILE) {
+            return Err(format!("entry '{}' is a reserved name", manifest.entry));
+        }
+        let runtime = runtime_abi_version(&manifest.entry)
+            .ok_or_else(|| format!("entry '{}' is neither a plugin library nor a WASM module", manifest.entry))?;
+        if manifest.abi_version.is_none() {
+            return Err("manifest does not declare an ABI version".to_string());
+        }
+        if manifest.checksum.is_empty() {
+            return Err("manifest has no checksum".to_string());
+        }
+        manifest.verify(&self.content, runtime)
+    }
+}
+
+/// A plugin installed from an archive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstalledPlugin {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    /// Plugin file name in the plugin folder
+    pub entry: String,
+    pub checksum: String,
+    /// Pinned plugins are kept at their version
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+/// What installing an archive did
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstallOutcome {
+    Installed { id: String, version: String },
+    Upgraded { id: String, from: String, to: String },
+}
+
+/// Plugins installed into a plugin folder from archives
+pub struct PluginRegistry {
+    dir: PathBuf,
+    plugins: BTreeMap<String, InstalledPlugin>,
+}
+
+impl PluginRegistry {
+    /// Open the registry of a plugin folder, empty if nothing was installed yet
+    pub fn open(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let index = dir.join(REGISTRY_FILE);
+        let plugins = if index.exists() {
+            schema::decode(FileKind::PluginRegistry, &fs::read(&index)?)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self { dir: dir.to_path_buf(), plugins })
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(&self.dir)?;
+        write_staged(&self.dir.join(REGISTRY_FILE), schema::encode(FileKind::PluginRegistry, &self.plugins)?.as_bytes())
+    }
+
+    /// Installed plugins, by id
+    pub fn installed(&self) -> impl Iterator<Item = &InstalledPlugin> {
+        self.plugins.values()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&InstalledPlugin> {
+        self.plugins.get(id)
+    }
+
+    /// Install a plugin from an archive, or upgrade it to a newer version.
+    ///
+    /// Archives that fail verification, downgrades, upgrades of pinned
+    /// plugins and files that would overwrite anything the registry doesn't
+    /// own are refused, leaving the folder untouched.
+    pub fn install(&mut self, archive_path: &Path) -> Result<InstallOutcome, Box<dyn std::error::Error>> {
+        let archive = PluginArchive::load(archive_path)?;
+        archive.verify().map_err(|e| format!("{}: {}", archive_path.display(), e))?;
+        let manifest = &archive.manifest;
+        let version = Version::parse(&manifest.version)?;
+
+        let previous = self.plugins.get(&manifest.id).cloned();
+        if let Some(previous) = &previous {
+            if previous.pinned {
+                return Err(format!("'{}' is pinned at version {}", previous.id, previous.version).into());
+            }
+            if Version::parse(&previous.version).is_ok_and(|installed| version <= installed) {
+                return Err(format!("'{}' {} is installed, {} is not newer", previous.id, previous.version, manifest.version).into());
+            }
+        }
+        if let Some(owner) = self.plugins.values().find(|p| p.entry == manifest.entry && p.id != manifest.id) {
+            return Err(format!("'{}' belongs to '{}'", manifest.entry, owner.id).into());
+        }
+        let entry_path = self.dir.join(&manifest.entry);
+        let owned = previous.as_ref().is_some_and(|p| p.entry == manifest.entry);
+        if !owned && (entry_path.exists() || manifest::manifest_path(&entry_path).exists()) {
+            return Err(format!("'{}' is already in the plugin folder and was not installed from an archive", manifest.entry).into());
+        }
+
+        // Both files are staged before either is moved into place, manifest
+        // first: the watcher reloads the plugin once both files settle
+        fs::create_dir_all(&self.dir)?;
+        let manifest_path = manifest::manifest_path(&entry_path);
+        let staged_manifest = stage(&manifest_path, serde_json::to_string_pretty(manifest)?.as_bytes())?;
+        let staged_entry = stage(&entry_path, &archive.content).inspect_err(|_| {
+            let _ = fs::remove_file(&staged_manifest);
+        })?;
+        let previous_manifest = if owned { fs::read(&manifest_path).ok() } else { None };
+        fs::rename(&staged_manifest, &manifest_path).inspect_err(|_| {
+            let _ = fs::remove_file(&staged_manifest);
+            let _ = fs::remove_file(&staged_entry);
+        })?;
+        if let Err(e) = fs::rename(&staged_entry, &entry_path) {
+            // Put back the manifest of the plugin file still in place
+            let _ = fs::remove_file(&staged_entry);
+            let _ = match previous_manifest {
+                Some(content) => write_staged(&manifest_path, &content),
+                None => fs::remove_file(&manifest_path).map_err(Into::into),
+            };
+            return Err(e.into());
+        }
+        if let Some(previous) = previous.as_ref().filter(|p| p.entry != manifest.entry) {
+            self.remove_files(previous)?;
+        }
+
+        self.plugins.insert(manifest.id.clone(), InstalledPlugin {
+            id: manifest.id.clone(),
+            name: manifest.name.clone(),
+            version: manifest.version.clone(),
+            entry: manifest.entry.clone(),
+            checksum: manifest.checksum.clone(),
+            pinned: false,
+        });
+        self.save()?;
+        Ok(match previous {
+            Some(previous) => InstallOutcome::Upgraded { id: manifest.id.clone(), from: previous.version, to: manifest.version.clone() },
+            None => InstallOutcome::Installed { id: manifest.id.clone(), version: manifest.version.clone() },
+        })
+    }
+
+    /// Keep a plugin at its installed version, or allow upgrades again
+    pub fn set_pinned(&mut self, id: &str, pinned: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let plugin = self.plugins.get_mut(id).ok_or_else(|| format!("'{}' is not installed", id))?;
+        plugin.pinned = pinned;
+        self.save()
+    }
+
+    /// Delete an installed plugin's files; the watcher then unloads it
+    pub fn remove(&mut self, id: &str) -> Result<InstalledPlugin, Box<dyn std::error::Error>> {
+        let plugin = self.plugins.get(id).cloned().ok_or_else(|| format!("'{}' is not installed", id))?;
+        self.remove_files(&plugin)?;
+        self.plugins.remove(id);
+        self.save()?;
+        Ok(plugin)
+    }
+
+    fn remove_files(&self, plugin: &InstalledPlugin) -> Result<(), Box<dyn std::error::Error>> {
+        let entry_path = self.dir.join(&plugin.entry);
+        for path in [manifest::manifest_path(&entry_path), entry_path] {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check an installed plugin's file still matches the checksum it was installed with
+    pub fn verify(&self, id: &str) -> Result<(), String> {
+        let plugin = self.plugins.get(id).ok_or_else(|| format!("'{}' is not installed", id))?;
+        let content = fs::read(self.dir.join(&plugin.entry)).map_err(|e| format!("cannot read '{}': {}", plugin.entry, e))?;
+        if sha256_hex(&content) != plugin.checksum {
+            return Err(format!("'{}' was modified after it was installed", plugin.entry));
+        }
+        Ok(())
+    }
+}
+
+/// Write `content` to a hidden file next to `path`, removed again if the
+/// write fails, and return the staging file's path
+fn stage(path: &Path, content: &[u8]) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let name = path.file_name().ok_or_else(|| format!("'{}' is not a file path", path.display()))?;
+    let staging = path.with_file_name(format!(".{}.partial", name.to_string_lossy()));
+    if let Err(e) = fs::write(&staging, content) {
+        let _ = fs::remove_file(&staging);
+        return Err(e.into());
+    }
+    Ok(staging)
+}
+
+/// Replace the file at `path` with `content` in one step, so readers never
+/// see it half-written
+fn write_staged(path: &Path, content: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let staging = stage(path, content)?;
+    fs::rename(&staging, path).inspect_err(|_| {
+        let _ = fs::remove_file(&staging);
+    })?;
+    Ok(())
+}
@@ -0,0 +1,178 @@
+//! When plugins are updated, and the thread that updates them.
+//!
+//! Telemetry is acquired and plugins are updated on an `UpdateThread`, so
+//! the UI thread only renders. Each tick, a plugin is updated if its
+//! `UpdateSchedule` says so: every tick, at a lower rate, or only when
+//! channels it watches change.
+//!
+//! A plugin call that never returns keeps the manager locked, so the UI
+//! only waits `UI_LOCK_WAIT` for it with `lock_within`.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::telemetry::TelemetryData;
+use super::PluginManager;
+
+/// Time between two ticks of the update thread, 60 Hz
+pub const TICK_INTERVAL: Duration = Duration::from_micros(16_667);
+/// Longest the UI waits for the manager before showing what it last saw
+pub const UI_LOCK_WAIT: Duration = Duration::from_millis(5);
+/// Longest dropping an `UpdateThread` waits for its tick to finish
+pub const JOIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Lock the manager shared with the update thread, giving up after `wait`.
+/// `None` while the update thread holds it longer, e.g. in a hung plugin call.
+pub fn lock_within(manager: &Mutex<PluginManager>, wait: Duration) -> Option<MutexGuard<'_, PluginManager>> {
+    let deadline = Instant::now() + wait;
+    loop {
+        match manager.try_lock() {
+            Ok(guard) => return Some(guard),
+            Err(TryLockError::Poisoned(poisoned)) => return Some(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) if Instant::now() >= deadline => return None,
+            Err(TryLockError::WouldBlock) => thread::sleep(Duration::from_micros(200)),
+        }
+    }
+}
+
+/// How often a plugin wants `update` called
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum UpdateSchedule {
+    /// On every tick
+    #[default]
+    EveryTick,
+    /// At most once per interval
+    Interval(Duration),
+    /// Only when one of these channels changed since the last update
+    OnChange(Vec<String>),
+}
+
+impl UpdateSchedule {
+    /// At most `rate` updates a second; every tick for rates that aren't positive
+    pub fn hz(rate: f64) -> Self {
+        if rate.is_finite() && rate > 0.0 {
+            UpdateSchedule::Interval(Duration::from_secs_f64(1.0 / rate))
+        } else {
+            UpdateSchedule::EveryTick
+        }
+    }
+}
+
+/// Values of the watched channels, absent ones as `None`
+fn watched_values(channels: &[String], data: &TelemetryData) -> Vec<Option<f64>> {
+    channels.iter().map(|c| data.channel_value(c)).collect()
+}
+
+struct LastUpdate {
+    at: Instant,
+    values: Vec<Option<f64>>,
+}
+
+/// When each plugin was last updated, and what it saw then
+#[derive(Default)]
+pub struct Scheduler {
+    last: HashMap<String, LastUpdate>,
+}
+
+impl Scheduler {
+    /// Whether a plugin should be updated with this frame. A plugin that
+    /// was never updated always is.
+    pub fn due(&self, plugin: &str, schedule: &UpdateSchedule, data: &TelemetryData, now: Instant) -> bool {
+        let Some(last) = self.last.get(plugin) else {
+            return true;
+        };
+        match schedule {
+            UpdateSchedule::EveryTick => true,
+            UpdateSchedule::Interval(interval) => now.duration_since(last.at) >= *interval,
+            UpdateSchedule::OnChange(channels) => watched_values(channels, data) != last.values,
+        }
+    }
+
+    pub fn record(&mut self, plugin: &str, schedule: &UpdateSchedule, data: &TelemetryData, now: Instant) {
+        let values = match schedule {
+            UpdateSchedule::OnChange(channels) => watched_values(channels, data),
+            _ => Vec::new(),
+        };
+        self.last.insert(plugin.to_string(), LastUpdate { at: now, values });
+    }
+
+    /// Forget a plugin, so it is updated on the next tick
+    pub fn remove(&mut self, plugin: &str) {
+        self.last.remove(plugin);
+    }
+}
+
+/// Acquires telemetry and updates plugins on its own thread until dropped.
+/// A thread stuck in a plugin call is left behind after `JOIN_TIMEOUT`.
+pub struct UpdateThread {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl UpdateThread {
+    /// Start ticking every `interval`. Each tick `acquire` refreshes the
+    /// shared frame, plugin data sources add their channels to it and the
+    /// plugins that are due are updated. The two locks are never held at once.
+    pub fn start(
+        manager: Arc<Mutex<PluginManager>>,
+        telemetry: Arc<Mutex<TelemetryData>>,
+        interval: Duration,
+        mut acquire: impl FnMut(&mut TelemetryData) + Send + 'static,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let handle = thread::spawn(move || {
+            let mut next = Instant::now();
+            while !thread_stop.load(Ordering::Relaxed) {
+                let Ok(mut data) = telemetry.lock().map(|mut shared| {
+                    acquire(&mut shared);
+                    shared.clone()
+                }) else {
+                    return;
+                };
+                {
+                    let Ok(mut manager) = manager.lock() else {
+                        return;
+                    };
+                    manager.produce_telemetry(&mut data);
+                    manager.update_plugins(&data);
+                }
+                let Ok(mut shared) = telemetry.lock() else {
+                    return;
+                };
+                *shared = data;
+                drop(shared);
+
+                next += interval;
+                let now = Instant::now();
+                if next > now {
+                    thread::sleep(next - now);
+                } else {
+                    // Fell behind: skip the missed ticks rather than catching up
+                    next = now;
+                }
+            }
+        });
+        Self { stop, handle: Some(handle) }
+    }
+}
+
+impl Drop for UpdateThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        let deadline = Instant::now() + JOIN_TIMEOUT;
+        while !handle.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        if handle.is_finished() {
+            let _ = handle.join();
+        } else {
+            eprintln!("[PLUGIN] Update thread is stuck in a plugin call, not waiting for it");
+        }
+    }
+}
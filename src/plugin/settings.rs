@@ -0,0 +1,189 @@
+use egui::Color32;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use super::{PluginSetting, SettingType};
+
+/// A setting value parsed according to its declared type
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Color(Color32),
+    File(PathBuf),
+}
+
+/// Why one setting of a config was refused
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingError {
+    pub setting: String,
+    pub message: String,
+}
+
+impl fmt::Display for SettingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.setting, self.message)
+    }
+}
+
+impl std::error::Error for SettingError {}
+
+/// Parse a color written as `#RRGGBB` or `#RRGGBBAA`
+pub fn parse_color(value: &str) -> Option<Color32> {
+    let hex = value.strip_prefix('#')?;
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+    Some(Color32::from_rgba_unmultiplied(channel(0)?, channel(2)?, channel(4)?, alpha))
+}
+
+/// Write a color the way `parse_color` reads it
+pub fn format_color(color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    if a == 255 {
+        format!("#{:02X}{:02X}{:02X}", r, g, b)
+    } else {
+        format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+    }
+}
+
+/// Parse one value against its setting: type, range and choices
+pub fn parse_value(setting: &PluginSetting, value: &str) -> Result<SettingValue, String> {
+    if !setting.choices.is_empty() && !setting.choices.iter().any(|c| c == value) {
+        return Err(format!("'{}' is not one of {}", value, setting.choices.join(", ")));
+    }
+    let parsed = match setting.value_type {
+        SettingType::String => SettingValue::String(value.to_string()),
+        SettingType::Integer => SettingValue::Integer(value.trim().parse().map_err(|_| format!("'{}' is not a whole number", value))?),
+        SettingType::Float => SettingValue::Float(value.trim().parse().map_err(|_| format!("'{}' is not a number", value))?),
+        SettingType::Boolean => SettingValue::Boolean(value.trim().parse().map_err(|_| format!("'{}' is not true or false", value))?),
+        SettingType::Color => SettingValue::Color(parse_color(value.trim()).ok_or_else(|| format!("'{}' is not a #RRGGBB color", value))?),
+        SettingType::File => {
+            if !Path::new(value).exists() {
+                return Err(format!("file '{}' does not exist", value));
+            }
+            SettingValue::File(PathBuf::from(value))
+        }
+    };
+
+    let number = match parsed {
+        SettingValue::Integer(v) => Some(v as f64),
+        SettingValue::Float(v) => Some(v),
+        _ => None,
+    };
+    if let Some(number) = number {
+        if let Some(min) = setting.min.filter(|min| number < *min) {
+            return Err(format!("{} is below the minimum of {}", value, min));
+        }
+        if let Some(max) = setting.max.filter(|max| number > *max) {
+            return Err(format!("{} is above the maximum of {}", value, max));
+        }
+    }
+    Ok(parsed)
+}
+
+/// Typed view of a plugin config, checked against the plugin's settings
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    values: HashMap<String, SettingValue>,
+}
+
+impl Settings {
+    /// Validate a raw config. Missing optional settings take their default;
+    /// every invalid or missing required setting is reported.
+    pub fn parse(settings: &[PluginSetting], config: &HashMap<String, String>) -> Result<Self, Vec<SettingError>> {
+        let (config, errors) = sanitize(settings, config);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let values = settings
+            .iter()
+            .filter_map(|s| {
+                let raw = config.get(&s.name)?;
+                parse_value(s, raw).ok().map(|v| (s.name.clone(), v))
+            })
+            .collect();
+        Ok(Self { values })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SettingValue> {
+        self.values.get(name)
+    }
+
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            SettingValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn integer(&self, name: &str) -> Option<i64> {
+        match self.get(name)? {
+            SettingValue::Integer(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Value of a `Float` setting, or an `Integer` one converted
+    pub fn float(&self, name: &str) -> Option<f64> {
+        match self.get(name)? {
+            SettingValue::Float(v) => Some(*v),
+            SettingValue::Integer(v) => Some(*v as f64),
+            _ => None,
+        }
+    }
+
+    pub fn boolean(&self, name: &str) -> Option<bool> {
+        match self.get(name)? {
+            SettingValue::Boolean(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn color(&self, name: &str) -> Option<Color32> {
+        match self.get(name)? {
+            SettingValue::Color(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn file(&self, name: &str) -> Option<&Path> {
+        match self.get(name)? {
+            SettingValue::File(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// Check a raw config against the declared settings.
+///
+/// Returns the config to apply: missing optional settings and invalid values
+/// are replaced by their default, and keys that aren't declared settings are
+/// kept as they are. Each replaced or missing required value is reported.
+pub fn sanitize(settings: &[PluginSetting], config: &HashMap<String, String>) -> (HashMap<String, String>, Vec<SettingError>) {
+    let mut sanitized = config.clone();
+    let mut errors = Vec::new();
+    for setting in settings {
+        let error = match config.get(&setting.name) {
+            Some(value) => parse_value(setting, value).err(),
+            None if setting.required => Some("required setting is missing".to_string()),
+            None => None,
+        };
+        let valid_default = parse_value(setting, &setting.default_value).is_ok();
+        if let Some(message) = error {
+            errors.push(SettingError { setting: setting.name.clone(), message });
+            if valid_default {
+                sanitized.insert(setting.name.clone(), setting.default_value.clone());
+            } else {
+                sanitized.remove(&setting.name);
+            }
+        } else if !sanitized.contains_key(&setting.name) && valid_default {
+            sanitized.insert(setting.name.clone(), setting.default_value.clone());
+        }
+    }
+    (sanitized, errors)
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use crate::telemetry::TelemetryData;
+
+/// A telemetry channel published by a plugin data source
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PluginChannel {
+    pub name: String,
+    pub unit: String,
+    /// Raise an alert when the value drops below this
+    #[serde(default)]
+    pub alert_below: Option<f64>,
+    /// Raise an alert when the value rises above this
+    #[serde(default)]
+    pub alert_above: Option<f64>,
+}
+
+impl PluginChannel {
+    pub fn new(name: &str, unit: &str) -> Self {
+        Self { name: name.to_string(), unit: unit.to_string(), ..Self::default() }
+    }
+
+    /// Alert message for the channel's current value, if out of range
+    pub fn alert(&self, data: &TelemetryData) -> Option<String> {
+        let value = data.extra.get(&self.name).copied()?;
+        if self.alert_below.is_some_and(|limit| value < limit) {
+            return Some(format!("{} low: {:.2} {}", self.name, value, self.unit).trim_end().to_string());
+        }
+        if self.alert_above.is_some_and(|limit| value > limit) {
+            return Some(format!("{} high: {:.2} {}", self.name, value, self.unit).trim_end().to_string());
+        }
+        None
+    }
+}
+
+/// Channels registered by all plugins, checked for clashes
+#[derive(Debug, Clone, Default)]
+pub struct ChannelRegistry {
+    /// Owning plugin and channel, in registration order
+    pub channels: Vec<(String, PluginChannel)>,
+    /// Channels that were refused, with the plugin and the reason
+    pub rejected: Vec<(String, String)>,
+}
+
+impl ChannelRegistry {
+    /// Register a plugin's channels. Built-in names and names another
+    /// plugin already owns are refused.
+    pub fn register(&mut self, plugin: &str, channels: Vec<PluginChannel>) {
+        for channel in channels {
+            if TelemetryData::is_builtin_channel(&channel.name) {
+                self.rejected.push((plugin.to_string(), format!("'{}' is a built-in channel", channel.name)));
+            } else if let Some((owner, _)) = self.channels.iter().find(|(_, c)| c.name == channel.name) {
+                self.rejected.push((plugin.to_string(), format!("channel '{}' is already published by '{}'", channel.name, owner)));
+            } else {
+                self.channels.push((plugin.to_string(), channel));
+            }
+        }
+    }
+
+    /// Channels owned by a plugin
+    pub fn owned_by<'a>(&'a self, plugin: &'a str) -> impl Iterator<Item = &'a PluginChannel> + 'a {
+        self.channels.iter().filter(move |(owner, _)| owner == plugin).map(|(_, c)| c)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PluginChannel> {
+        self.channels.iter().map(|(_, c)| c).find(|c| c.name == name)
+    }
+}
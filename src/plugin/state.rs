@@ -0,0 +1,222 @@
+//! Persistent state of plugins.
+//!
+//! Every plugin gets a `PluginState` handle, a key-value store namespaced by
+//! its id, for what it must remember across restarts: lap records, an
+//! odometer, favourites. Values are JSON, read and written with serde.
+//! Writes are transactional: a transaction is applied in full or not at
+//! all, and is refused if the namespace would grow past its quota. The whole
+//! store is kept in one file next to the main data file, so snapshots back
+//! it up with everything else.
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use crate::storage::schema::{self, FileKind};
+
+/// Bytes of JSON a plugin may keep
+pub const DEFAULT_QUOTA_BYTES: usize = 256 * 1024;
+
+type Entries = BTreeMap<String, Value>;
+
+/// Size a namespace takes up, as serialized JSON
+fn entries_size(entries: &Entries) -> usize {
+    serde_json::to_vec(entries).map(|json| json.len()).unwrap_or(usize::MAX)
+}
+
+/// State of all plugins, by namespace
+pub struct StateStore {
+    /// File the store is saved to, `None` to keep it in memory only
+    path: Option<PathBuf>,
+    namespaces: BTreeMap<String, Entries>,
+    quota_bytes: usize,
+    /// Namespaces of plugins that aren't permitted to store data
+    denied: HashSet<String>,
+}
+
+impl Default for StateStore {
+    fn default() -> Self {
+        Self { path: None, namespaces: BTreeMap::new(), quota_bytes: DEFAULT_QUOTA_BYTES, denied: HashSet::new() }
+    }
+}
+
+impl StateStore {
+    /// Load the store saved at `path`, empty if there is none yet, and save
+    /// every later write there
+    pub fn open(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.namespaces = match fs::read(path) {
+            Ok(content) => schema::decode(FileKind::PluginState, &content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        self.path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Bytes each plugin may keep
+    pub fn set_quota(&mut self, bytes: usize) {
+        self.quota_bytes = bytes;
+    }
+
+    /// Allow or refuse access to a namespace
+    pub fn set_allowed(&mut self, namespace: &str, allowed: bool) {
+        if allowed {
+            self.denied.remove(namespace);
+        } else {
+            self.denied.insert(namespace.to_string());
+        }
+    }
+
+    /// Namespaces holding data
+    pub fn namespaces(&self) -> impl Iterator<Item = &str> {
+        self.namespaces.keys().map(|n| n.as_str())
+    }
+
+    /// Bytes a namespace takes up
+    pub fn usage(&self, namespace: &str) -> usize {
+        self.namespaces.get(namespace).map(entries_size).unwrap_or(0)
+    }
+
+    /// Delete everything a plugin stored
+    pub fn clear(&mut self, namespace: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(entries) = self.namespaces.remove(namespace) {
+            if let Err(e) = self.save() {
+                self.namespaces.insert(namespace.to_string(), entries);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn check_access(&self, namespace: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.denied.contains(namespace) {
+            return Err(format!("'{}' is not permitted to store data", namespace).into());
+        }
+        Ok(())
+    }
+
+    /// Write the whole store through a staging file, so a crash leaves either
+    /// the old or the new version
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let staging = path.with_extension("partial");
+        fs::write(&staging, schema::encode(FileKind::PluginState, &self.namespaces)?)?;
+        fs::rename(&staging, path)?;
+        Ok(())
+    }
+
+    /// Replace a namespace with the result of a transaction, unless it is
+    /// over quota or can't be saved
+    fn commit(&mut self, namespace: &str, entries: Entries) -> Result<(), Box<dyn std::error::Error>> {
+        let size = entries_size(&entries);
+        if size > self.quota_bytes {
+            return Err(format!("'{}' would use {} bytes, over its quota of {} bytes", namespace, size, self.quota_bytes).into());
+        }
+        let previous = if entries.is_empty() {
+            self.namespaces.remove(namespace)
+        } else {
+            self.namespaces.insert(namespace.to_string(), entries)
+        };
+        if let Err(e) = self.save() {
+            match previous {
+                Some(previous) => self.namespaces.insert(namespace.to_string(), previous),
+                None => self.namespaces.remove(namespace),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+/// Changes to a plugin's state, applied together when the transaction ends
+pub struct Transaction {
+    entries: Entries,
+}
+
+impl Transaction {
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, Box<dyn std::error::Error>> {
+        match self.entries.get(key) {
+            Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn put<T: Serialize>(&mut self, key: &str, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+        self.entries.insert(key.to_string(), serde_json::to_value(value)?);
+        Ok(())
+    }
+
+    /// Returns whether the key existed
+    pub fn remove(&mut self, key: &str) -> bool {
+        self.entries.remove(key).is_some()
+    }
+}
+
+/// A plugin's own namespace in the state store, handed to it by `connect_state`
+#[derive(Clone)]
+pub struct PluginState {
+    namespace: String,
+    store: Arc<Mutex<StateStore>>,
+}
+
+impl PluginState {
+    pub fn new(namespace: &str, store: Arc<Mutex<StateStore>>) -> Self {
+        Self { namespace: namespace.to_string(), store }
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, StateStore>, Box<dyn std::error::Error>> {
+        // Transactions work on a copy, so a plugin that panicked in one left the store intact
+        let store = self.store.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        store.check_access(&self.namespace)?;
+        Ok(store)
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, Box<dyn std::error::Error>> {
+        let value = self.lock()?.namespaces.get(&self.namespace).and_then(|entries| entries.get(key)).cloned();
+        match value {
+            Some(value) => Ok(Some(serde_json::from_value(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn put<T: Serialize>(&self, key: &str, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+        self.transaction(|tx| tx.put(key, value))
+    }
+
+    /// Returns whether the key existed
+    pub fn remove(&self, key: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut removed = false;
+        self.transaction(|tx| {
+            removed = tx.remove(key);
+            Ok(())
+        })?;
+        Ok(removed)
+    }
+
+    pub fn keys(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Ok(self.lock()?.namespaces.get(&self.namespace).map(|e| e.keys().cloned().collect()).unwrap_or_default())
+    }
+
+    /// Bytes this plugin's state takes up
+    pub fn usage(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        Ok(self.lock()?.usage(&self.namespace))
+    }
+
+    /// Make several changes at once. Nothing is written if `f` fails, the
+    /// result is over quota or it can't be saved. The store is locked
+    /// meanwhile, so `f` must use the transaction rather than this handle.
+    pub fn transaction(&self, f: impl FnOnce(&mut Transaction) -> Result<(), Box<dyn std::error::Error>>) -> Result<(), Box<dyn std::error::Error>> {
+        let mut store = self.lock()?;
+        let mut transaction = Transaction { entries: store.namespaces.get(&self.namespace).cloned().unwrap_or_default() };
+        f(&mut transaction)?;
+        store.commit(&self.namespace, transaction.entries)
+    }
+}
@@ -0,0 +1,498 @@
+//! Sandboxed WebAssembly plugins.
+//!
+//! A module exports `memory`, `alloc(len: i32) -> i32` and
+//! `render() -> i64`, and optionally `init()`, `update(ptr: i32, len: i32)`,
+//! `get_config() -> i64`, `set_config(ptr: i32, len: i32)`,
+//! `on_input(ptr: i32, len: i32) -> i32` (non-zero when the input was used)
+//! and `cleanup()`.
+//! Strings are UTF-8 JSON; results are returned as `(ptr << 32) | len`.
+//!
+//! Modules get no WASI. The only imports are the `console` host functions
+//! below, and those refuse anything the plugin's manifest doesn't grant, or,
+//! under the plugin manager, that the user hasn't allowed:
+//! - `log(ptr, len)`
+//! - `read_file(path_ptr, path_len, out_ptr, out_cap) -> i32`, relative
+//!   paths taken from the manifest's folder
+//! - `net_request(addr_ptr, addr_len, req_ptr, req_len, out_ptr, out_cap) -> i32`
+//! - `state_get(key_ptr, key_len, out_ptr, out_cap) -> i32`
+//! - `state_put(key_ptr, key_len, value_ptr, value_len) -> i32`
+//!
+//! `read_file`, `net_request` and `state_get` return the number of bytes
+//! written to `out`, or -1. A `net_request` gets two seconds in all and at
+//! most 1 MiB of response. `state_put` stores a JSON value in the plugin's
+//! persistent state and returns 0, or -1 when it isn't valid JSON, over
+//! quota or not permitted.
+
+use egui::Ui;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use wasmi::{Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TrapCode, TypedFunc};
+use crate::telemetry::TelemetryData;
+use super::{Plugin, PluginMetadata, PluginStatus};
+use super::manifest::PluginManifest;
+use super::permissions::PermissionSet;
+use super::state::PluginState;
+use super::draw::{self, DrawList};
+
+/// Version of the exports and host functions described above, checked
+/// against the `abi_version` a manifest declares
+pub const WASM_ABI_VERSION: u32 = 1;
+/// Largest JSON result accepted from a module
+const MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+/// Log lines kept per plugin
+const MAX_LOG_LINES: usize = 100;
+/// Time a whole `net_request` may take, fuel isn't used while it waits
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(2);
+/// Largest response read by `net_request`
+const MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+
+/// Per-plugin state visible to host functions
+struct HostState {
+    limits: StoreLimits,
+    manifest: PluginManifest,
+    /// Permissions allowed by the user, `None` when running outside the manager
+    granted: Option<PermissionSet>,
+    /// Persistent state, only available under the manager
+    state: Option<PluginState>,
+    log: Vec<String>,
+}
+
+fn guest_memory(caller: &Caller<'_, HostState>) -> Option<Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Some(memory),
+        _ => None,
+    }
+}
+
+fn read_guest(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Option<Vec<u8>> {
+    let memory = guest_memory(caller)?;
+    let mut buffer = vec![0; usize::try_from(len).ok()?.min(MAX_OUTPUT_BYTES)];
+    memory.read(caller, usize::try_from(ptr).ok()?, &mut buffer).ok()?;
+    Some(buffer)
+}
+
+/// Copy `data` into the guest's `out` buffer, returning the bytes written or -1
+fn write_guest(caller: &mut Caller<'_, HostState>, out_ptr: i32, out_cap: i32, data: &[u8]) -> i32 {
+    let (Some(memory), Ok(out_ptr), Ok(out_cap)) = (guest_memory(caller), usize::try_from(out_ptr), usize::try_from(out_cap)) else {
+        return -1;
+    };
+    let data = &data[..data.len().min(out_cap)];
+    match memory.write(caller, out_ptr, data) {
+        Ok(()) => data.len() as i32,
+        Err(_) => -1,
+    }
+}
+
+fn push_log(state: &mut HostState, line: String) {
+    if state.log.len() >= MAX_LOG_LINES {
+        state.log.remove(0);
+    }
+    state.log.push(line);
+}
+
+fn host_log(mut caller: Caller<'_, HostState>, ptr: i32, len: i32) {
+    if let Some(bytes) = read_guest(&caller, ptr, len) {
+        push_log(caller.data_mut(), String::from_utf8_lossy(&bytes).to_string());
+    }
+}
+
+fn host_read_file(mut caller: Caller<'_, HostState>, path_ptr: i32, path_len: i32, out_ptr: i32, out_cap: i32) -> i32 {
+    let Some(requested) = read_guest(&caller, path_ptr, path_len).map(|b| PathBuf::from(String::from_utf8_lossy(&b).to_string())) else {
+        return -1;
+    };
+    let state = caller.data();
+    // Read the very path that was checked, so a symlink swapped in after
+    // the check can't redirect the read
+    let path = state.manifest.resolve(&requested).canonicalize().ok().filter(|path| {
+        state.manifest.may_read(path) && state.granted.as_ref().is_none_or(|g| g.can_read_file(path))
+    });
+    let Some(path) = path else {
+        push_log(caller.data_mut(), format!("denied: read {}", requested.display()));
+        return -1;
+    };
+    match fs::read(&path) {
+        Ok(content) => write_guest(&mut caller, out_ptr, out_cap, &content),
+        Err(_) => -1,
+    }
+}
+
+/// Time left until `deadline`, or a timeout once it passed
+fn time_left(deadline: Instant) -> std::io::Result<Duration> {
+    let left = deadline.saturating_duration_since(Instant::now());
+    if left.is_zero() {
+        Err(std::io::ErrorKind::TimedOut.into())
+    } else {
+        Ok(left)
+    }
+}
+
+/// Resolve `address` before `deadline`. Lookups can't be given a timeout,
+/// so one that stalls is left to finish on its own thread.
+fn resolve(address: &str, deadline: Instant) -> std::io::Result<SocketAddr> {
+    let (sender, receiver) = mpsc::channel();
+    let address = address.to_string();
+    thread::spawn(move || {
+        let _ = sender.send(address.to_socket_addrs().map(|mut addresses| addresses.next()));
+    });
+    match receiver.recv_timeout(time_left(deadline)?) {
+        Ok(resolved) => resolved?.ok_or_else(|| std::io::ErrorKind::NotFound.into()),
+        Err(_) => Err(std::io::ErrorKind::TimedOut.into()),
+    }
+}
+
+/// Send `request` and read the response until the peer closes or `cap`
+/// bytes arrived, all within `NETWORK_TIMEOUT`
+fn exchange(address: &str, request: &[u8], cap: usize) -> std::io::Result<Vec<u8>> {
+    let deadline = Instant::now() + NETWORK_TIMEOUT;
+    let socket = resolve(address, deadline)?;
+    let mut stream = TcpStream::connect_timeout(&socket, time_left(deadline)?)?;
+    let mut written = 0;
+    while written < request.len() {
+        stream.set_write_timeout(Some(time_left(deadline)?))?;
+        match stream.write(&request[written..])? {
+            0 => return Err(std::io::ErrorKind::WriteZero.into()),
+            n => written += n,
+        }
+    }
+    let mut response = Vec::new();
+    let mut chunk = [0; 4096];
+    while response.len() < cap {
+        stream.set_read_timeout(Some(time_left(deadline)?))?;
+        let wanted = (cap - response.len()).min(chunk.len());
+        match stream.read(&mut chunk[..wanted])? {
+            0 => break,
+            n => response.extend_from_slice(&chunk[..n]),
+        }
+    }
+    Ok(response)
+}
+
+fn host_net_request(mut caller: Caller<'_, HostState>, addr_ptr: i32, addr_len: i32, req_ptr: i32, req_len: i32, out_ptr: i32, out_cap: i32) -> i32 {
+    let (Some(address), Some(request)) = (read_guest(&caller, addr_ptr, addr_len), read_guest(&caller, req_ptr, req_len)) else {
+        return -1;
+    };
+    let address = String::from_utf8_lossy(&address).to_string();
+    let state = caller.data();
+    if !state.manifest.may_connect(&address) || state.granted.as_ref().is_some_and(|g| !g.can_connect(&address)) {
+        push_log(caller.data_mut(), format!("denied: connect {}", address));
+        return -1;
+    }
+
+    let cap = usize::try_from(out_cap).unwrap_or(0).min(MAX_RESPONSE_BYTES);
+    match exchange(&address, &request, cap) {
+        Ok(response) => write_guest(&mut caller, out_ptr, out_cap, &response),
+        Err(_) => -1,
+    }
+}
+
+fn host_state_get(mut caller: Caller<'_, HostState>, key_ptr: i32, key_len: i32, out_ptr: i32, out_cap: i32) -> i32 {
+    let Some(key) = read_guest(&caller, key_ptr, key_len).map(|b| String::from_utf8_lossy(&b).to_string()) else {
+        return -1;
+    };
+    let Some(state) = caller.data().state.clone() else {
+        return -1;
+    };
+    match state.get::<serde_json::Value>(&key) {
+        Ok(Some(value)) => write_guest(&mut caller, out_ptr, out_cap, value.to_string().as_bytes()),
+        Ok(None) => -1,
+        Err(e) => {
+            push_log(caller.data_mut(), format!("denied: state {}: {}", key, e));
+            -1
+        }
+    }
+}
+
+fn host_state_put(mut caller: Caller<'_, HostState>, key_ptr: i32, key_len: i32, value_ptr: i32, value_len: i32) -> i32 {
+    let (Some(key), Some(value)) = (read_guest(&caller, key_ptr, key_len), read_guest(&caller, value_ptr, value_len)) else {
+        return -1;
+    };
+    let key = String::from_utf8_lossy(&key).to_string();
+    let (Some(state), Ok(value)) = (caller.data().state.clone(), serde_json::from_slice::<serde_json::Value>(&value)) else {
+        return -1;
+    };
+    match state.put(&key, &value) {
+        Ok(()) => 0,
+        Err(e) => {
+            push_log(caller.data_mut(), format!("denied: state {}: {}", key, e));
+            -1
+        }
+    }
+}
+
+/// A plugin running inside the WASM sandbox
+pub struct WasmPlugin {
+    store: Store<HostState>,
+    instance: Instance,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    render: TypedFunc<(), i64>,
+    metadata: PluginMetadata,
+    /// Draw list from the last update, shown by `render`
+    draw_list: DrawList,
+    /// Config as last reported by the module
+    config: HashMap<String, String>,
+    enabled: bool,
+    status: PluginStatus,
+    /// Trap or CPU budget overrun of the last call
+    fault: Option<String>,
+}
+
+impl WasmPlugin {
+    /// Compile and instantiate a module under the limits of its manifest
+    pub fn new(wasm: &[u8], manifest: PluginManifest) -> Result<Self, String> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm).map_err(|e| format!("invalid module: {}", e))?;
+
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(manifest.max_memory_bytes)
+            .instances(1)
+            .build();
+        let fuel = manifest.fuel_per_call;
+        let metadata = PluginMetadata {
+            name: manifest.name.clone(),
+            version: if manifest.version.is_empty() { PluginMetadata::default().version } else { manifest.version.clone() },
+            author: manifest.author.clone(),
+            description: manifest.description.clone(),
+            permissions: manifest.requested_permissions(),
+            ..Default::default()
+        };
+        let mut store = Store::new(&engine, HostState { limits, manifest, granted: None, state: None, log: Vec::new() });
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(fuel).map_err(|e| e.to_string())?;
+
+        let mut linker = Linker::new(&engine);
+        linker.func_wrap("console", "log", host_log).map_err(|e| e.to_string())?;
+        linker.func_wrap("console", "read_file", host_read_file).map_err(|e| e.to_string())?;
+        linker.func_wrap("console", "net_request", host_net_request).map_err(|e| e.to_string())?;
+        linker.func_wrap("console", "state_get", host_state_get).map_err(|e| e.to_string())?;
+        linker.func_wrap("console", "state_put", host_state_put).map_err(|e| e.to_string())?;
+        let instance = linker
+            .instantiate_and_start(&mut store, &module)
+            .map_err(|e| format!("cannot instantiate module: {}", e))?;
+
+        let memory = instance.get_memory(&store, "memory").ok_or("module does not export memory")?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, "alloc")
+            .map_err(|_| "module does not export alloc(i32) -> i32")?;
+        let render = instance
+            .get_typed_func::<(), i64>(&store, "render")
+            .map_err(|_| "module does not export render() -> i64")?;
+
+        Ok(Self {
+            store,
+            instance,
+            memory,
+            alloc,
+            render,
+            metadata,
+            draw_list: DrawList::default(),
+            config: HashMap::new(),
+            enabled: true,
+            status: PluginStatus::Loading,
+            fault: None,
+        })
+    }
+
+    /// Load `<name>.wasm` together with its manifest, checking it matches
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let wasm = fs::read(path).map_err(|e| format!("cannot read module: {}", e))?;
+        let manifest = PluginManifest::for_plugin(path)?;
+        manifest.verify(&wasm, WASM_ABI_VERSION)?;
+        Self::new(&wasm, manifest)
+    }
+
+    /// Draw list from the last update
+    pub fn draw_list(&self) -> &DrawList {
+        &self.draw_list
+    }
+
+    /// Messages the plugin logged, oldest first
+    pub fn log(&self) -> &[String] {
+        &self.store.data().log
+    }
+
+    pub fn manifest(&self) -> &PluginManifest {
+        &self.store.data().manifest
+    }
+
+    /// Describe a failed call, naming the limit that was hit, and keep it as
+    /// the fault of this call
+    fn call_error(&mut self, function: &str, error: wasmi::Error) -> String {
+        let message = match error.as_trap_code() {
+            Some(TrapCode::OutOfFuel) => format!(
+                "{} exceeded its CPU budget of {} instructions",
+                function,
+                self.manifest().fuel_per_call
+            ),
+            _ => format!("{} failed: {}", function, error),
+        };
+        self.fault = Some(message.clone());
+        message
+    }
+
+    /// Refill the fuel for a call and copy a JSON argument into guest memory
+    fn write_argument(&mut self, json: &[u8]) -> Result<(i32, i32), String> {
+        self.store.set_fuel(self.manifest().fuel_per_call).map_err(|e| e.to_string())?;
+        let ptr = self.alloc.call(&mut self.store, json.len() as i32).map_err(|e| self.call_error("alloc", e))?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, json)
+            .map_err(|e| format!("alloc returned an invalid buffer: {}", e))?;
+        Ok((ptr, json.len() as i32))
+    }
+
+    /// Call an optional export taking `(ptr, len)` of a JSON argument
+    fn call_with_json(&mut self, function: &str, json: &[u8]) -> Result<(), String> {
+        let Ok(func) = self.instance.get_typed_func::<(i32, i32), ()>(&self.store, function) else {
+            return Ok(());
+        };
+        let argument = self.write_argument(json)?;
+        func.call(&mut self.store, argument).map_err(|e| self.call_error(function, e))
+    }
+
+    /// Pass an `Interaction` to the optional `on_input` export
+    fn call_on_input(&mut self, json: &[u8]) -> Result<bool, String> {
+        let Ok(func) = self.instance.get_typed_func::<(i32, i32), i32>(&self.store, "on_input") else {
+            return Ok(false);
+        };
+        let argument = self.write_argument(json)?;
+        let handled = func.call(&mut self.store, argument).map_err(|e| self.call_error("on_input", e))?;
+        Ok(handled != 0)
+    }
+
+    /// Call an optional export without arguments or results
+    fn call_unit(&mut self, function: &str) -> Result<(), String> {
+        let Ok(func) = self.instance.get_typed_func::<(), ()>(&self.store, function) else {
+            return Ok(());
+        };
+        self.store.set_fuel(self.manifest().fuel_per_call).map_err(|e| e.to_string())?;
+        func.call(&mut self.store, ()).map_err(|e| self.call_error(function, e))
+    }
+
+    /// Call an export returning `(ptr << 32) | len` and copy the result out
+    fn call_for_bytes(&mut self, func: TypedFunc<(), i64>, function: &str) -> Result<Vec<u8>, String> {
+        self.store.set_fuel(self.manifest().fuel_per_call).map_err(|e| e.to_string())?;
+        let packed = func.call(&mut self.store, ()).map_err(|e| self.call_error(function, e))? as u64;
+        let (ptr, len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        if len > MAX_OUTPUT_BYTES {
+            return Err(format!("{} returned {} bytes, more than the {} allowed", function, len, MAX_OUTPUT_BYTES));
+        }
+        let mut buffer = vec![0; len];
+        self.memory
+            .read(&self.store, ptr, &mut buffer)
+            .map_err(|e| format!("{} returned an invalid buffer: {}", function, e))?;
+        Ok(buffer)
+    }
+
+    /// Ask the module for a fresh draw list
+    fn refresh_draw_list(&mut self) -> Result<(), String> {
+        let bytes = self.call_for_bytes(self.render, "render")?;
+        self.draw_list = serde_json::from_slice(&bytes).map_err(|e| format!("render returned an invalid draw list: {}", e))?;
+        Ok(())
+    }
+
+    /// Refresh the cached config if the module exports `get_config`
+    fn refresh_config(&mut self) -> Result<(), String> {
+        let Ok(func) = self.instance.get_typed_func::<(), i64>(&self.store, "get_config") else {
+            return Ok(());
+        };
+        let bytes = self.call_for_bytes(func, "get_config")?;
+        self.config = serde_json::from_slice(&bytes).map_err(|e| format!("get_config returned invalid JSON: {}", e))?;
+        Ok(())
+    }
+
+    /// Record the outcome of a call in the plugin status
+    fn record(&mut self, result: Result<(), String>) {
+        self.status = match result {
+            Ok(()) => PluginStatus::Ready,
+            Err(e) => PluginStatus::Error(e),
+        };
+    }
+}
+
+impl Plugin for WasmPlugin {
+    fn init(&mut self) {
+        let result = self.call_unit("init")
+            .and_then(|_| self.refresh_config())
+            .and_then(|_| self.refresh_draw_list());
+        self.record(result);
+    }
+
+    fn update(&mut self, data: &TelemetryData) {
+        let json = serde_json::to_vec(data).unwrap_or_default();
+        let result = self.call_with_json("update", &json)
+            .and_then(|_| self.refresh_config())
+            .and_then(|_| self.refresh_draw_list());
+        self.record(result);
+    }
+
+    /// Errors in the status are shown by `PluginManager::render_plugin`
+    fn render(&self, ui: &mut Ui) {
+        draw::render_draw_list(ui, &self.draw_list);
+    }
+
+    fn get_metadata(&self) -> PluginMetadata {
+        self.metadata.clone()
+    }
+
+    fn get_config(&self) -> HashMap<String, String> {
+        self.config.clone()
+    }
+
+    fn set_config(&mut self, config: HashMap<String, String>) {
+        let json = serde_json::to_vec(&config).unwrap_or_default();
+        self.config = config;
+        let result = self.call_with_json("set_config", &json).and_then(|_| self.refresh_config());
+        self.record(result);
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn get_status(&self) -> PluginStatus {
+        if self.enabled { self.status.clone() } else { PluginStatus::Disabled }
+    }
+
+    fn take_fault(&mut self) -> Option<String> {
+        self.fault.take()
+    }
+
+    fn cleanup(&mut self) {
+        let result = self.call_unit("cleanup");
+        self.record(result);
+    }
+
+    fn handle_input(&mut self, input: &super::input::Interaction) -> bool {
+        let json = serde_json::to_vec(input).unwrap_or_default();
+        let result = self.call_on_input(&json);
+        let handled = *result.as_ref().unwrap_or(&false);
+        // The module may have changed what it shows
+        let result = result.and_then(|_| self.refresh_draw_list());
+        self.record(result);
+        handled
+    }
+
+    fn set_permissions(&mut self, granted: &PermissionSet) {
+        self.store.data_mut().granted = Some(granted.clone());
+    }
+
+    fn connect_state(&mut self, state: PluginState) {
+        self.store.data_mut().state = Some(state);
+    }
+
+    fn memory_usage(&self) -> Option<usize> {
+        Some(self.memory.data_size(&self.store))
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use super::loader;
+
+/// What happened to a plugin file between two polls
+#[derive(Debug, Clone, PartialEq)]
+pub enum PluginChange {
+    Added(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+}
+
+/// Modification time and size of a plugin file and its manifest
+type Signature = Vec<(SystemTime, u64)>;
+
+fn signature(path: &Path) -> Option<Signature> {
+    let mut signature = Vec::new();
+    for file in [path.to_path_buf(), path.with_extension("json")] {
+        if let Ok(metadata) = fs::metadata(&file) {
+            signature.push((metadata.modified().ok()?, metadata.len()));
+        }
+    }
+    (!signature.is_empty()).then_some(signature)
+}
+
+/// Polls the plugin folder for changed libraries and modules.
+///
+/// A change is only reported once the file looks the same on two polls in a
+/// row, so a library that is still being written by the compiler isn't loaded
+/// half-finished.
+pub struct PluginWatcher {
+    dir: PathBuf,
+    /// Signatures of the files as last reported
+    known: HashMap<PathBuf, Signature>,
+    /// Signatures seen on the previous poll that differ from `known`
+    pending: HashMap<PathBuf, Signature>,
+}
+
+impl PluginWatcher {
+    /// Start watching, treating the files present now as already loaded
+    pub fn new(dir: &str) -> Self {
+        let mut watcher = Self {
+            dir: PathBuf::from(dir),
+            known: HashMap::new(),
+            pending: HashMap::new(),
+        };
+        watcher.known = watcher.scan();
+        watcher
+    }
+
+    fn scan(&self) -> HashMap<PathBuf, Signature> {
+        loader::plugin_files(&self.dir)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|path| signature(&path).map(|s| (path, s)))
+            .collect()
+    }
+
+    /// Changes that have settled since the last poll
+    pub fn poll(&mut self) -> Vec<PluginChange> {
+        let current = self.scan();
+        let mut changes = Vec::new();
+
+        for (path, signature) in &current {
+            if self.known.get(path) == Some(signature) {
+                self.pending.remove(path);
+                continue;
+            }
+            if self.pending.get(path) != Some(signature) {
+                self.pending.insert(path.clone(), signature.clone());
+                continue;
+            }
+            self.pending.remove(path);
+            let change = if self.known.contains_key(path) {
+                PluginChange::Modified(path.clone())
+            } else {
+                PluginChange::Added(path.clone())
+            };
+            self.known.insert(path.clone(), signature.clone());
+            changes.push(change);
+        }
+
+        let removed: Vec<PathBuf> = self.known.keys().filter(|p| !current.contains_key(*p)).cloned().collect();
+        for path in removed {
+            self.known.remove(&path);
+            self.pending.remove(&path);
+            changes.push(PluginChange::Removed(path));
+        }
+        changes.sort_by_key(|c| match c {
+            PluginChange::Added(p) | PluginChange::Modified(p) | PluginChange::Removed(p) => p.clone(),
+        });
+        changes
+    }
+}
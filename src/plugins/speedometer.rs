@@ -1,21 +1,22 @@
-use egui::Ui;
-use crate::plugin::Plugin;
-use crate::telemetry::TelemetryData;
+use console::plugin::{PluginCategory, PluginMetadata};
+use console::plugin::abi::ExportedPlugin;
+use console::plugin::draw::DrawList;
+use console::telemetry::TelemetryData;
 
+#[derive(Default)]
 pub struct SpeedometerPlugin {
     speed: f64,
     rpm: f64,
 }
 
-impl SpeedometerPlugin {
-    pub fn new() -> Self {
-        SpeedometerPlugin { speed: 0.0, rpm: 0.0 }
-    }
-}
-
-impl Plugin for SpeedometerPlugin {
-    fn init(&mut self) {
-        // Initialization logic if needed
+impl ExportedPlugin for SpeedometerPlugin {
+    fn metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: "Speedometer".to_string(),
+            description: "Speed and RPM readout".to_string(),
+            category: PluginCategory::Speedometer,
+            ..Default::default()
+        }
     }
 
     fn update(&mut self, data: &TelemetryData) {
@@ -23,14 +24,13 @@ impl Plugin for SpeedometerPlugin {
         self.rpm = data.rpm;
     }
 
-    fn render(&self, ui: &mut Ui) {
-        ui.heading("Speedometer");
-        ui.label(format!("Speed: {:.1} km/h", self.speed));
-        ui.label(format!("RPM: {:.0}", self.rpm));
+    fn draw(&self) -> DrawList {
+        let mut list = DrawList::new();
+        list.heading("Speedometer")
+            .gauge("Speed", self.speed, 0.0, 160.0, "km/h")
+            .gauge("RPM", self.rpm, 0.0, 8000.0, "rpm");
+        list
     }
 }
 
-#[no_mangle]
-pub extern "C" fn init_plugin() -> Box<dyn Plugin> {
-    Box::new(SpeedometerPlugin::new())
-} 
\ No newline at end of file
+console::export_plugin!(SpeedometerPlugin);
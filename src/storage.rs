@@ -4,6 +4,27 @@ use serde_json;
 use crate::telemetry::TelemetryData;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+pub mod bundle;
+pub mod catalog;
+pub mod compression;
+pub mod export;
+pub mod integrity;
+pub mod recorder;
+pub mod retention;
+pub mod rollup;
+pub mod schema;
+pub mod snapshot;
+
+use bundle::{ConsoleBundle, ImportOptions, ImportPreview, ImportReport, ItemStatus, PreviewItem};
+use catalog::{Catalog, SessionFilter, SessionInfo, SessionListing, SessionMetadata, SessionSummary};
+use retention::{RetentionPolicy, RetentionReport};
+use schema::{FileKind, MigrationReport};
+use snapshot::{ObjectStore, SnapshotFile, SnapshotManifest};
+use std::path::PathBuf;
+
+/// Writes data of a kind back out, e.g. `schema::encode`
+type Encoder<T> = fn(FileKind, &T) -> Result<String, Box<dyn std::error::Error>>;
+
 pub struct Storage {
     pub file_path: String,
     pub backup_dir: String,
@@ -19,35 +40,336 @@ impl Storage {
     }
 
     pub fn save(&self, data: &TelemetryData) -> Result<(), Box<dyn std::error::Error>> {
-        let json = serde_json::to_string_pretty(data)?;
+        let json = schema::encode(FileKind::Telemetry, data)?;
         fs::write(&self.file_path, json)?;
         Ok(())
     }
 
     pub fn load(&self) -> Result<TelemetryData, Box<dyn std::error::Error>> {
-        let json = fs::read_to_string(&self.file_path)?;
-        let data: TelemetryData = serde_json::from_str(&json)?;
+        let json = fs::read(&self.file_path)?;
+        let data: TelemetryData = schema::decode(FileKind::Telemetry, &json)?;
         Ok(data)
     }
 
-    /// Save multiple telemetry data points as a session
-    pub fn save_session(&self, data_points: &[TelemetryData]) -> Result<(), Box<dyn std::error::Error>> {
-        let session_data = serde_json::to_string_pretty(data_points)?;
+    /// Save multiple telemetry data points as a session, returns the session id
+    pub fn save_session(&self, data_points: &[TelemetryData]) -> Result<String, Box<dyn std::error::Error>> {
+        let session_data = schema::encode(FileKind::Session, &data_points)?;
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
-        let session_file = format!("{}.session_{}", self.file_path, timestamp);
-        fs::write(session_file, session_data)?;
-        Ok(())
+        let session_id = timestamp.to_string();
+        fs::write(self.session_path(&session_id), &session_data)?;
+        self.write_integrity_manifest(&session_id, session_data.as_bytes(), data_points)?;
+        self.write_rollups(&session_id, data_points)?;
+
+        let mut catalog = self.load_catalog()?;
+        catalog.sessions.entry(session_id.clone()).or_default().summary = Some(SessionSummary::from_data(data_points));
+        self.save_catalog(&catalog)?;
+        Ok(session_id)
     }
 
     /// Load a session file
     pub fn load_session(&self, session_id: &str) -> Result<Vec<TelemetryData>, Box<dyn std::error::Error>> {
-        let session_file = format!("{}.session_{}", self.file_path, session_id);
-        let json = fs::read_to_string(session_file)?;
-        let data: Vec<TelemetryData> = serde_json::from_str(&json)?;
-        Ok(data)
+        let json = fs::read(self.session_path(session_id))?;
+        decode_session(&json)
+    }
+
+    /// Path of the file holding a session's data points
+    pub fn session_path(&self, session_id: &str) -> String {
+        format!("{}.session_{}", self.file_path, session_id)
+    }
+
+    /// Path of the integrity manifest written alongside a session
+    pub fn integrity_path(&self, session_id: &str) -> String {
+        format!("{}.sha256.json", self.session_path(session_id))
+    }
+
+    fn write_integrity_manifest(&self, session_id: &str, content: &[u8], data_points: &[TelemetryData]) -> Result<(), Box<dyn std::error::Error>> {
+        let manifest = integrity::seal(session_id, content, data_points, integrity::DEFAULT_RANGE_BYTES);
+        fs::write(self.integrity_path(session_id), schema::encode(FileKind::IntegrityManifest, &manifest)?)?;
+        Ok(())
+    }
+
+    /// Write (or rewrite) the integrity manifest for an existing session
+    pub fn seal_session(&self, session_id: &str) -> Result<integrity::IntegrityManifest, Box<dyn std::error::Error>> {
+        let content = fs::read(self.session_path(session_id))?;
+        let data = decode_session(&content)?;
+        self.write_integrity_manifest(session_id, &content, &data)?;
+        self.load_integrity_manifest(session_id)
+    }
+
+    /// Load the integrity manifest of a session
+    pub fn load_integrity_manifest(&self, session_id: &str) -> Result<integrity::IntegrityManifest, Box<dyn std::error::Error>> {
+        let json = fs::read(self.integrity_path(session_id))?;
+        schema::decode(FileKind::IntegrityManifest, &json)
+    }
+
+    /// Verify a session file against its integrity manifest
+    pub fn verify_session(&self, session_id: &str) -> Result<integrity::IntegrityReport, Box<dyn std::error::Error>> {
+        let manifest = self.load_integrity_manifest(session_id)
+            .map_err(|e| format!("No integrity manifest for session {}: {}", session_id, e))?;
+        let content = fs::read(self.session_path(session_id))?;
+        Ok(integrity::verify(&content, &manifest))
+    }
+
+    /// Remove a session file together with its integrity manifest and rollups
+    fn remove_session_files(&self, session_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::remove_file(self.session_path(session_id))?;
+        for path in self.session_sidecar_paths(session_id) {
+            if Path::new(&path).exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Integrity manifest and rollup files kept next to a session
+    fn session_sidecar_paths(&self, session_id: &str) -> Vec<String> {
+        let rollups = rollup::ROLLUP_RESOLUTIONS.iter().map(|r| self.rollup_path(session_id, *r));
+        std::iter::once(self.integrity_path(session_id)).chain(rollups).collect()
+    }
+
+    /// Bytes a session takes on disk, its integrity manifest and rollups included
+    fn session_bytes(&self, session_id: &str) -> u64 {
+        std::iter::once(self.session_path(session_id))
+            .chain(self.session_sidecar_paths(session_id))
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    /// Replace a session with its error-bounded compressed form.
+    /// Rollups are built from the full data first so overviews stay exact.
+    pub fn archive_session(&self, session_id: &str, tolerances: &compression::CompressionTolerances) -> Result<compression::CompressionReport, Box<dyn std::error::Error>> {
+        let path = self.session_path(session_id);
+        let original = fs::read(&path)?;
+        if schema::peek_kind(&original).as_deref() == Some(FileKind::CompressedSession.name()) {
+            return Err(format!("Session {} is already archived", session_id).into());
+        }
+        let data = decode_session(&original)?;
+        if !self.has_rollups(session_id) {
+            self.write_rollups(session_id, &data)?;
+        }
+
+        let (compressed, mut report) = compression::compress_session(&data, tolerances);
+        let content = schema::encode(FileKind::CompressedSession, &compressed)?;
+        report.original_bytes = original.len() as u64;
+        report.compressed_bytes = content.len() as u64;
+        fs::write(&path, &content)?;
+        // Seal what a reader will decode, not the in-memory values, so float parsing can't disagree
+        self.write_integrity_manifest(session_id, content.as_bytes(), &decode_session(content.as_bytes())?)?;
+
+        let mut catalog = self.load_catalog()?;
+        catalog.sessions.entry(session_id.to_string()).or_default().archived = true;
+        self.save_catalog(&catalog)?;
+        Ok(report)
+    }
+
+    /// Path of one overview tier written alongside a session
+    pub fn rollup_path(&self, session_id: &str, resolution_secs: u64) -> String {
+        format!("{}.rollup_{}s.json", self.session_path(session_id), resolution_secs)
+    }
+
+    fn has_rollups(&self, session_id: &str) -> bool {
+        rollup::ROLLUP_RESOLUTIONS.iter().all(|r| Path::new(&self.rollup_path(session_id, *r)).exists())
+    }
+
+    fn write_rollups(&self, session_id: &str, data_points: &[TelemetryData]) -> Result<rollup::SessionRollups, Box<dyn std::error::Error>> {
+        let rollups = rollup::build_rollups(data_points);
+        for tier in &rollups.tiers {
+            let file = rollup::TierFile { start_time: rollups.start_time, end_time: rollups.end_time, tier: tier.clone() };
+            fs::write(self.rollup_path(session_id, tier.resolution_secs), schema::encode_compact(FileKind::Rollups, &file)?)?;
+        }
+        Ok(rollups)
+    }
+
+    fn load_rollup_tier(&self, session_id: &str, resolution_secs: u64) -> Result<rollup::TierFile, Box<dyn std::error::Error>> {
+        schema::decode(FileKind::Rollups, &fs::read(self.rollup_path(session_id, resolution_secs))?)
+    }
+
+    /// Build and store the overview tiers of a finished session
+    pub fn finalize_session(&self, session_id: &str) -> Result<rollup::SessionRollups, Box<dyn std::error::Error>> {
+        let data = self.load_session(session_id)?;
+        self.write_rollups(session_id, &data)
+    }
+
+    /// Load a session's overview tiers, building them first for sessions saved without
+    pub fn load_rollups(&self, session_id: &str) -> Result<rollup::SessionRollups, Box<dyn std::error::Error>> {
+        if !self.has_rollups(session_id) {
+            return self.finalize_session(session_id);
+        }
+        let mut rollups = rollup::SessionRollups { start_time: 0, end_time: 0, tiers: Vec::new() };
+        for resolution in rollup::ROLLUP_RESOLUTIONS {
+            let file = self.load_rollup_tier(session_id, *resolution)?;
+            rollups.start_time = file.start_time;
+            rollups.end_time = file.end_time;
+            rollups.tiers.push(file.tier);
+        }
+        Ok(rollups)
+    }
+
+    /// Query a channel over a time range at the resolution suited to
+    /// `pixel_width`, reading only that tier
+    pub fn query_session_range(&self, session_id: &str, channel: &str, from: u64, to: u64, pixel_width: u32) -> Result<rollup::RollupSeries, Box<dyn std::error::Error>> {
+        let resolution = rollup::select_resolution(rollup::ROLLUP_RESOLUTIONS, to.saturating_sub(from), pixel_width).ok_or("No rollup tiers")?;
+        let tier = if self.has_rollups(session_id) {
+            self.load_rollup_tier(session_id, resolution)?.tier
+        } else {
+            let mut rollups = self.finalize_session(session_id)?;
+            let index = rollups.tiers.iter().position(|t| t.resolution_secs == resolution).ok_or("No rollup tiers")?;
+            rollups.tiers.swap_remove(index)
+        };
+        tier.query(channel, from, to)
+            .ok_or_else(|| format!("Channel '{}' has no data in session {}", channel, session_id).into())
+    }
+
+    /// Path of the session catalogue index
+    pub fn catalog_path(&self) -> String {
+        format!("{}.catalog.json", self.file_path)
+    }
+
+    /// Path of the state plugins keep, see `plugin::state`. It is stored
+    /// under the main file's name so snapshots include it.
+    pub fn plugin_state_path(&self) -> String {
+        format!("{}.plugin_state.json", self.file_path)
+    }
+
+    /// Path of the plugin configs, in the data directory where bundles and
+    /// snapshots look for them
+    pub fn plugin_configs_path(&self) -> PathBuf {
+        self.data_dir().join(bundle::PLUGIN_CONFIGS_FILE)
+    }
+
+    /// Path of the user's plugin permission decisions, next to the plugin configs
+    pub fn plugin_permissions_path(&self) -> PathBuf {
+        self.data_dir().join(crate::plugin::permissions::PERMISSIONS_FILE)
+    }
+
+    /// Path of the key bindings, next to the plugin configs
+    pub fn key_bindings_path(&self) -> PathBuf {
+        self.data_dir().join(crate::plugin::input::KEY_BINDINGS_FILE)
+    }
+
+    /// Load the session catalogue index, empty if it doesn't exist yet
+    pub fn load_catalog(&self) -> Result<Catalog, Box<dyn std::error::Error>> {
+        if !Path::new(&self.catalog_path()).exists() {
+            return Ok(Catalog::default());
+        }
+        let json = fs::read(self.catalog_path())?;
+        schema::decode(FileKind::Catalog, &json)
+    }
+
+    /// Save the session catalogue index
+    pub fn save_catalog(&self, catalog: &Catalog) -> Result<(), Box<dyn std::error::Error>> {
+        let json = schema::encode(FileKind::Catalog, catalog)?;
+        fs::write(self.catalog_path(), json)?;
+        Ok(())
+    }
+
+    /// Directory holding the main data file
+    pub fn data_dir(&self) -> PathBuf {
+        match Path::new(&self.file_path).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        }
+    }
+
+    fn file_name(&self) -> String {
+        Path::new(&self.file_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// List the ids of all session files on disk, oldest first
+    pub fn list_session_ids(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let dir = self.data_dir();
+        let prefix = format!("{}.session_", self.file_name());
+
+        let mut ids = Vec::new();
+        if !dir.exists() {
+            return Ok(ids);
+        }
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if let Some(id) = name.strip_prefix(&prefix) {
+                if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+        ids.sort_by_key(|id| id.parse::<u64>().unwrap_or(0));
+        Ok(ids)
+    }
+
+    /// Get catalogue details for one session, computing its summary if it isn't cached
+    pub fn get_session_info(&self, session_id: &str) -> Result<SessionInfo, Box<dyn std::error::Error>> {
+        let mut catalog = self.load_catalog()?;
+        let info = self.session_info_from_catalog(&mut catalog, session_id)?;
+        self.save_catalog(&catalog)?;
+        Ok(info)
+    }
+
+    /// List sessions matching a filter, newest first. Sessions that can't be
+    /// read are skipped and reported in the listing.
+    pub fn list_sessions(&self, filter: &SessionFilter) -> Result<SessionListing, Box<dyn std::error::Error>> {
+        let mut catalog = self.load_catalog()?;
+        let mut listing = SessionListing::default();
+        for id in self.list_session_ids()? {
+            match self.session_info_from_catalog(&mut catalog, &id) {
+                Ok(info) if filter.matches(&info) => listing.sessions.push(info),
+                Ok(_) => {}
+                Err(e) => listing.failed.push((id, e.to_string())),
+            }
+        }
+        self.save_catalog(&catalog)?;
+        listing.sessions.sort_by_key(|s| std::cmp::Reverse(s.summary.start_time));
+        Ok(listing)
+    }
+
+    fn session_info_from_catalog(&self, catalog: &mut Catalog, session_id: &str) -> Result<SessionInfo, Box<dyn std::error::Error>> {
+        let file_size = fs::metadata(self.session_path(session_id))?.len();
+        if catalog.sessions.get(session_id).is_none_or(|entry| entry.summary.is_none()) {
+            let data = self.load_session(session_id)?;
+            catalog.sessions.entry(session_id.to_string()).or_default().summary = Some(SessionSummary::from_data(&data));
+        }
+        let entry = &catalog.sessions[session_id];
+        Ok(SessionInfo {
+            id: session_id.to_string(),
+            file_size,
+            metadata: entry.metadata.clone(),
+            summary: entry.summary.clone().unwrap_or_default(),
+        })
+    }
+
+    /// Replace the user-editable details of a session
+    pub fn update_session_metadata(&self, session_id: &str, metadata: SessionMetadata) -> Result<(), Box<dyn std::error::Error>> {
+        if !Path::new(&self.session_path(session_id)).exists() {
+            return Err("Session not found".into());
+        }
+        let mut catalog = self.load_catalog()?;
+        catalog.sessions.entry(session_id.to_string()).or_default().metadata = metadata;
+        self.save_catalog(&catalog)
+    }
+
+    /// Rename a session. The session id and file name stay the same.
+    pub fn rename_session(&self, session_id: &str, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut metadata = self.get_session_info(session_id)?.metadata;
+        metadata.name = name.to_string();
+        self.update_session_metadata(session_id, metadata)
+    }
+
+    /// Delete a session file and its catalogue entry
+    pub fn delete_session(&self, session_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if !Path::new(&self.session_path(session_id)).exists() {
+            return Err("Session not found".into());
+        }
+        self.remove_session_files(session_id)?;
+
+        let mut catalog = self.load_catalog()?;
+        catalog.sessions.remove(session_id);
+        self.save_catalog(&catalog)
     }
 
     /// Create a backup of current data
@@ -117,7 +439,7 @@ impl Storage {
             data.brake_pressure,
             data.gear,
             data.acceleration,
-            data.brake_temperature,
+            data.brake_temp,
             data.tire_pressure_fl,
             data.tire_pressure_fr,
             data.tire_pressure_rl,
@@ -128,6 +450,36 @@ impl Storage {
         Ok(())
     }
 
+    /// Export a session's GPS track as GPX
+    pub fn export_session_to_gpx(&self, session_id: &str, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let data = self.load_session(session_id)?;
+        let gpx = export::to_gpx(&format!("Session {}", session_id), &data)?;
+        fs::write(output_path, gpx)?;
+        Ok(())
+    }
+
+    /// Export a session's GPS track as KML, coloured by speed
+    pub fn export_session_to_kml(&self, session_id: &str, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let data = self.load_session(session_id)?;
+        let kml = export::to_kml(&format!("Session {}", session_id), &data)?;
+        fs::write(output_path, kml)?;
+        Ok(())
+    }
+
+    /// Export a session as a MoTeC i2 compatible CSV
+    pub fn export_session_to_motec(&self, session_id: &str, options: &export::MotorsportExport, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let data = self.load_session(session_id)?;
+        fs::write(output_path, export::to_motec_csv(options, &data))?;
+        Ok(())
+    }
+
+    /// Export a session as an AiM Race Studio compatible CSV
+    pub fn export_session_to_aim(&self, session_id: &str, options: &export::MotorsportExport, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let data = self.load_session(session_id)?;
+        fs::write(output_path, export::to_aim_csv(options, &data))?;
+        Ok(())
+    }
+
     /// Get storage statistics
     pub fn get_stats(&self) -> Result<StorageStats, Box<dyn std::error::Error>> {
         let mut stats = StorageStats::default();
@@ -163,6 +515,608 @@ impl Storage {
         Ok(to_delete)
     }
 
+    /// Timestamp of a backup, taken from its `backup_<secs>.json` name
+    fn backup_timestamp(name: &str) -> Option<u64> {
+        name.strip_prefix("backup_")?.strip_suffix(".json")?.parse().ok()
+    }
+
+    /// Check that writing `bytes` more would leave at least `min_free_bytes` free
+    pub fn has_room_for(&self, bytes: u64, min_free_bytes: u64) -> Result<bool, Box<dyn std::error::Error>> {
+        let available = retention::available_space(&self.file_path)?;
+        Ok(available >= bytes.saturating_add(min_free_bytes))
+    }
+
+    /// Apply a retention policy to sessions, backups and logs
+    pub fn apply_retention(&self, policy: &RetentionPolicy) -> Result<RetentionReport, Box<dyn std::error::Error>> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.apply_retention_at(policy, now)
+    }
+
+    /// Apply a retention policy as if the current time were `now`
+    pub fn apply_retention_at(&self, policy: &RetentionPolicy, now: u64) -> Result<RetentionReport, Box<dyn std::error::Error>> {
+        let mut report = RetentionReport::default();
+        let mut catalog = self.load_catalog()?;
+        let age_cutoff = policy.max_age_secs.map(|age| now.saturating_sub(age));
+
+        // Sessions, oldest first, sized with their sidecar files. Sessions
+        // that can't be read are skipped and reported, but still take space.
+        let mut sessions = Vec::new();
+        let mut skipped_bytes = 0;
+        for id in self.list_session_ids()? {
+            match self.session_info_from_catalog(&mut catalog, &id) {
+                Ok(mut info) => {
+                    info.file_size = self.session_bytes(&id);
+                    sessions.push(info);
+                }
+                Err(e) => {
+                    skipped_bytes += self.session_bytes(&id);
+                    report.skipped_sessions.push((id, e.to_string()));
+                }
+            }
+        }
+        sessions.sort_by_key(|s| s.summary.start_time);
+
+        let mut kept_sessions = Vec::new();
+        for mut session in sessions {
+            let pinned = session.metadata.pinned;
+            if !pinned && age_cutoff.is_some_and(|cutoff| session.summary.start_time < cutoff) {
+                self.remove_session_files(&session.id)?;
+                catalog.sessions.remove(&session.id);
+                report.bytes_freed += session.file_size;
+                report.deleted_sessions.push(session.id);
+                continue;
+            }
+            if !pinned {
+                if let Err(e) = self.age_session(policy, now, &mut catalog, &mut session, &mut report) {
+                    report.skipped_sessions.push((session.id.clone(), e.to_string()));
+                }
+            }
+            kept_sessions.push(session);
+        }
+
+        // Backups, oldest first
+        let mut backups = Vec::new();
+        for name in self.list_backups()? {
+            let path = format!("{}/{}", self.backup_dir, name);
+            let size = fs::metadata(&path)?.len();
+            let timestamp = Self::backup_timestamp(&name).unwrap_or(0);
+            backups.push((name, path, size, timestamp));
+        }
+        backups.sort_by_key(|(_, _, _, timestamp)| *timestamp);
+        if let Some(keep) = policy.keep_backups {
+            let excess = backups.len().saturating_sub(keep);
+            for (name, path, size, _) in backups.drain(..excess) {
+                fs::remove_file(path)?;
+                report.bytes_freed += size;
+                report.deleted_backups.push(name);
+            }
+        }
+        if let Some(cutoff) = age_cutoff {
+            let (expired, kept): (Vec<_>, Vec<_>) = backups.into_iter().partition(|b| b.3 < cutoff);
+            for (name, path, size, _) in expired {
+                fs::remove_file(path)?;
+                report.bytes_freed += size;
+                report.deleted_backups.push(name);
+            }
+            backups = kept;
+        }
+
+        // Logs, oldest first
+        let mut logs = match &policy.log_dir {
+            Some(dir) => retention::list_files(dir)?,
+            None => Vec::new(),
+        };
+        if let Some(cutoff) = age_cutoff {
+            let (expired, kept): (Vec<_>, Vec<_>) = logs.into_iter().partition(|l| l.modified < cutoff);
+            for log in expired {
+                fs::remove_file(&log.path)?;
+                report.bytes_freed += log.size;
+                report.deleted_logs.push(log.path);
+            }
+            logs = kept;
+        }
+
+        if let Some(keep) = policy.keep_snapshots {
+            self.prune_snapshots_within_retention(keep, &mut report)?;
+        }
+
+        // Enforce the quota: logs go first, then backups, then snapshots but
+        // the newest, then unpinned sessions
+        let mut total: u64 = kept_sessions.iter().map(|s| s.file_size).sum::<u64>()
+            + skipped_bytes
+            + backups.iter().map(|b| b.2).sum::<u64>()
+            + logs.iter().map(|l| l.size).sum::<u64>()
+            + self.snapshot_bytes()?;
+        if let Some(max_total) = policy.max_total_bytes {
+            for log in logs {
+                if total <= max_total {
+                    break;
+                }
+                fs::remove_file(&log.path)?;
+                total -= log.size;
+                report.bytes_freed += log.size;
+                report.deleted_logs.push(log.path);
+            }
+            for (name, path, size, _) in backups {
+                if total <= max_total {
+                    break;
+                }
+                fs::remove_file(path)?;
+                total -= size;
+                report.bytes_freed += size;
+                report.deleted_backups.push(name);
+            }
+            let mut snapshots = self.list_snapshots()?.len();
+            while total > max_total && snapshots > 1 {
+                snapshots -= 1;
+                total = total.saturating_sub(self.prune_snapshots_within_retention(snapshots, &mut report)?);
+            }
+            for session in kept_sessions.iter().filter(|s| !s.metadata.pinned) {
+                if total <= max_total {
+                    break;
+                }
+                self.remove_session_files(&session.id)?;
+                catalog.sessions.remove(&session.id);
+                total -= session.file_size;
+                report.bytes_freed += session.file_size;
+                report.deleted_sessions.push(session.id.clone());
+            }
+        }
+
+        report.total_bytes = total;
+        self.save_catalog(&catalog)?;
+        Ok(report)
+    }
+
+    /// Archive or downsample an unpinned session once it is old enough,
+    /// updating its size and the catalogue
+    fn age_session(
+        &self,
+        policy: &RetentionPolicy,
+        now: u64,
+        catalog: &mut Catalog,
+        session: &mut SessionInfo,
+        report: &mut RetentionReport,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let archive = policy.archive_after_secs
+            .is_some_and(|after| session.summary.start_time < now.saturating_sub(after));
+        let archived = catalog.sessions.get(&session.id).is_some_and(|e| e.archived);
+        if archive && !archived {
+            // Save the catalogue first, archiving updates it on disk
+            self.save_catalog(catalog)?;
+            self.archive_session(&session.id, &policy.archive_tolerances)?;
+            *catalog = self.load_catalog()?;
+            let new_size = self.session_bytes(&session.id);
+            report.bytes_freed += session.file_size.saturating_sub(new_size);
+            session.file_size = new_size;
+            report.archived_sessions.push(session.id.clone());
+        }
+
+        let downsample = policy.downsample_after_secs
+            .is_some_and(|after| session.summary.start_time < now.saturating_sub(after));
+        let entry = catalog.sessions.entry(session.id.clone()).or_default();
+        if downsample && !entry.downsampled && !entry.archived {
+            let data = self.load_session(&session.id)?;
+            let reduced = retention::downsample_session(&data, policy.downsample_interval_secs);
+            let content = schema::encode(FileKind::Session, &reduced)?;
+            fs::write(self.session_path(&session.id), &content)?;
+            self.write_integrity_manifest(&session.id, content.as_bytes(), &reduced)?;
+            let new_size = self.session_bytes(&session.id);
+            report.bytes_freed += session.file_size.saturating_sub(new_size);
+            session.file_size = new_size;
+            entry.downsampled = true;
+            report.downsampled_sessions.push(session.id.clone());
+        }
+        Ok(())
+    }
+
+    /// `prune_snapshots` for a retention run, returns the bytes it freed
+    fn prune_snapshots_within_retention(&self, keep_count: usize, report: &mut RetentionReport) -> Result<u64, Box<dyn std::error::Error>> {
+        let before = self.snapshot_bytes()?;
+        report.deleted_snapshots += self.prune_snapshots(keep_count)?;
+        let freed = before.saturating_sub(self.snapshot_bytes()?);
+        report.bytes_freed += freed;
+        Ok(freed)
+    }
+
+    /// Bytes taken by snapshot manifests and their stored objects
+    fn snapshot_bytes(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        let mut total = 0;
+        for dir in [self.snapshot_dir(), self.object_store().dir] {
+            total += retention::list_files(&dir)?.iter().map(|f| f.size).sum::<u64>();
+        }
+        Ok(total)
+    }
+
+    /// Rewrite a file in the current schema version if it's stored in an older one.
+    /// Returns whether the file was rewritten.
+    fn migrate_file<T>(&self, kind: FileKind, path: &str) -> Result<bool, Box<dyn std::error::Error>>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        self.migrate_file_with::<T>(kind, path, schema::encode)
+    }
+
+    /// `migrate_file`, writing the file back with `encode`
+    fn migrate_file_with<T>(&self, kind: FileKind, path: &str, encode: Encoder<T>) -> Result<bool, Box<dyn std::error::Error>>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        let content = fs::read(path)?;
+        let (data, version): (T, u32) = schema::decode_with_version(kind, &content)?;
+        if version == schema::CURRENT_SCHEMA_VERSION {
+            return Ok(false);
+        }
+        fs::write(path, encode(kind, &data)?)?;
+        Ok(true)
+    }
+
+    /// Upgrade every persisted file (main file, sessions, integrity manifests,
+    /// catalogue, plugin state and backups) to the current schema version.
+    ///
+    /// Sessions are verified against their integrity manifest first and are
+    /// resealed after migration; sessions that fail verification are left untouched.
+    pub fn migrate_all(&self) -> Result<MigrationReport, Box<dyn std::error::Error>> {
+        let mut report = MigrationReport::default();
+        let mut record = |path: String, result: Result<bool, Box<dyn std::error::Error>>| match result {
+            Ok(true) => report.migrated.push(path),
+            Ok(false) => report.up_to_date.push(path),
+            Err(e) => report.failed.push((path, e.to_string())),
+        };
+
+        if Path::new(&self.file_path).exists() {
+            record(self.file_path.clone(), self.migrate_file::<TelemetryData>(FileKind::Telemetry, &self.file_path));
+        }
+
+        for id in self.list_session_ids()? {
+            let path = self.session_path(&id);
+            let has_manifest = Path::new(&self.integrity_path(&id)).exists();
+            let result = (|| {
+                if has_manifest {
+                    self.migrate_file::<integrity::IntegrityManifest>(FileKind::IntegrityManifest, &self.integrity_path(&id))?;
+                    let verification = self.verify_session(&id)?;
+                    if !verification.is_valid() {
+                        return Err(format!("integrity check failed: {}", verification.describe()).into());
+                    }
+                }
+                let migrated = if schema::peek_kind(&fs::read(&path)?).as_deref() == Some(FileKind::CompressedSession.name()) {
+                    self.migrate_file::<compression::CompressedSession>(FileKind::CompressedSession, &path)?
+                } else {
+                    self.migrate_file::<Vec<TelemetryData>>(FileKind::Session, &path)?
+                };
+                if migrated && has_manifest {
+                    self.seal_session(&id)?;
+                }
+                for resolution in rollup::ROLLUP_RESOLUTIONS {
+                    let path = self.rollup_path(&id, *resolution);
+                    if Path::new(&path).exists() {
+                        self.migrate_file_with::<rollup::TierFile>(FileKind::Rollups, &path, schema::encode_compact)?;
+                    }
+                }
+                Ok(migrated)
+            })();
+            record(path, result);
+        }
+
+        if Path::new(&self.catalog_path()).exists() {
+            record(self.catalog_path(), self.migrate_file::<Catalog>(FileKind::Catalog, &self.catalog_path()));
+        }
+
+        if Path::new(&self.plugin_state_path()).exists() {
+            record(self.plugin_state_path(), self.migrate_file::<serde_json::Value>(FileKind::PluginState, &self.plugin_state_path()));
+        }
+
+        for name in self.list_backups()? {
+            let path = format!("{}/{}", self.backup_dir, name);
+            let result = self.migrate_file::<TelemetryData>(FileKind::Telemetry, &path);
+            record(path, result);
+        }
+
+        Ok(report)
+    }
+
+    fn snapshot_dir(&self) -> String {
+        format!("{}/snapshots", self.backup_dir)
+    }
+
+    fn object_store(&self) -> ObjectStore {
+        ObjectStore::new(&format!("{}/objects", self.backup_dir))
+    }
+
+    /// Files covered by snapshots, relative to the data directory: the main file,
+    /// everything stored under its name (sessions, manifests, catalogue) and the
+    /// shared config files in `snapshot::DEFAULT_EXTRA_FILES` that exist
+    pub fn snapshot_file_list(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let dir = self.data_dir();
+        let own_name = self.file_name();
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let owned = name == own_name || name.starts_with(&format!("{}.", own_name));
+            if owned && entry.file_type()?.is_file() {
+                files.push(name);
+            }
+        }
+        files.extend(
+            snapshot::DEFAULT_EXTRA_FILES
+                .iter()
+                .filter(|name| dir.join(name).is_file())
+                .map(|name| name.to_string()),
+        );
+        files.sort();
+        Ok(files)
+    }
+
+    /// Take a snapshot of the data directory, storing only content that changed
+    /// since earlier snapshots, and verify it after writing
+    pub fn create_snapshot(&self) -> Result<SnapshotManifest, Box<dyn std::error::Error>> {
+        let store = self.object_store();
+        let parent = self.list_snapshots()?.last().map(|s| s.timestamp_ms);
+        let mut timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        // Snapshot ids must stay unique and increasing
+        if let Some(parent) = parent {
+            timestamp_ms = timestamp_ms.max(parent + 1);
+        }
+
+        let mut manifest = SnapshotManifest {
+            timestamp_ms,
+            parent,
+            files: Vec::new(),
+            new_objects: 0,
+            new_bytes: 0,
+        };
+        for path in self.snapshot_file_list()? {
+            let content = fs::read(self.data_dir().join(&path))?;
+            let (hash, is_new) = store.put(&content)?;
+            if is_new {
+                manifest.new_objects += 1;
+                manifest.new_bytes += content.len() as u64;
+            }
+            manifest.files.push(SnapshotFile { path, hash, size: content.len() as u64 });
+        }
+
+        fs::create_dir_all(self.snapshot_dir())?;
+        fs::write(self.snapshot_path(timestamp_ms), schema::encode(FileKind::Snapshot, &manifest)?)?;
+
+        let problems = self.verify_snapshot(timestamp_ms)?;
+        if !problems.is_empty() {
+            return Err(format!("Snapshot verification failed: {}", problems.join("; ")).into());
+        }
+        Ok(manifest)
+    }
+
+    fn snapshot_path(&self, timestamp_ms: u64) -> String {
+        format!("{}/snapshot_{}.json", self.snapshot_dir(), timestamp_ms)
+    }
+
+    /// List all snapshots, oldest first
+    pub fn list_snapshots(&self) -> Result<Vec<SnapshotManifest>, Box<dyn std::error::Error>> {
+        let mut snapshots = Vec::new();
+        if !Path::new(&self.snapshot_dir()).exists() {
+            return Ok(snapshots);
+        }
+        for entry in fs::read_dir(self.snapshot_dir())? {
+            let content = fs::read(entry?.path())?;
+            snapshots.push(schema::decode::<SnapshotManifest>(FileKind::Snapshot, &content)?);
+        }
+        snapshots.sort_by_key(|s| s.timestamp_ms);
+        Ok(snapshots)
+    }
+
+    /// Load one snapshot manifest
+    pub fn load_snapshot(&self, timestamp_ms: u64) -> Result<SnapshotManifest, Box<dyn std::error::Error>> {
+        let content = fs::read(self.snapshot_path(timestamp_ms))
+            .map_err(|e| format!("Snapshot {} not found: {}", timestamp_ms, e))?;
+        schema::decode(FileKind::Snapshot, &content)
+    }
+
+    /// Check every object a snapshot references, returns a list of problems
+    pub fn verify_snapshot(&self, timestamp_ms: u64) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let manifest = self.load_snapshot(timestamp_ms)?;
+        let store = self.object_store();
+        Ok(manifest
+            .files
+            .iter()
+            .filter_map(|f| store.verify(&f.hash).err().map(|e| format!("{}: {}", f.path, e)))
+            .collect())
+    }
+
+    /// Restore the data directory to the state captured by a snapshot.
+    ///
+    /// A snapshot of the current state is taken first so the restore can be undone.
+    /// Files owned by this storage that didn't exist at snapshot time are removed.
+    pub fn restore_snapshot(&self, timestamp_ms: u64) -> Result<SnapshotManifest, Box<dyn std::error::Error>> {
+        let manifest = self.load_snapshot(timestamp_ms)?;
+        let problems = self.verify_snapshot(timestamp_ms)?;
+        if !problems.is_empty() {
+            return Err(format!("Snapshot is damaged: {}", problems.join("; ")).into());
+        }
+
+        self.create_snapshot()?;
+
+        let store = self.object_store();
+        for path in self.snapshot_file_list()? {
+            if manifest.file(&path).is_none() {
+                fs::remove_file(self.data_dir().join(&path))?;
+            }
+        }
+        for file in &manifest.files {
+            let path = self.data_dir().join(&file.path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, store.get(&file.hash)?)?;
+        }
+        Ok(manifest)
+    }
+
+    /// Restore the latest snapshot taken at or before `timestamp_ms`
+    pub fn restore_to_point_in_time(&self, timestamp_ms: u64) -> Result<SnapshotManifest, Box<dyn std::error::Error>> {
+        let snapshot = self.list_snapshots()?
+            .into_iter()
+            .rev()
+            .find(|s| s.timestamp_ms <= timestamp_ms)
+            .ok_or("No snapshot exists at or before the requested time")?;
+        self.restore_snapshot(snapshot.timestamp_ms)
+    }
+
+    /// Keep only the newest `keep_count` snapshots and drop unreferenced objects.
+    /// Returns the number of snapshots removed.
+    pub fn prune_snapshots(&self, keep_count: usize) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut snapshots = self.list_snapshots()?;
+        let excess = snapshots.len().saturating_sub(keep_count);
+        for snapshot in snapshots.drain(..excess) {
+            fs::remove_file(self.snapshot_path(snapshot.timestamp_ms))?;
+        }
+        self.object_store().garbage_collect(&snapshots)?;
+        Ok(excess)
+    }
+
+    /// Collect the configuration files and the chosen sessions into a bundle
+    pub fn export_bundle(&self, session_ids: &[String]) -> Result<ConsoleBundle, Box<dyn std::error::Error>> {
+        let mut bundle = ConsoleBundle {
+            created_ms: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64,
+            ..Default::default()
+        };
+        for name in bundle::CONFIG_FILES {
+            let path = self.data_dir().join(name);
+            if path.exists() {
+                bundle.files.insert(name.to_string(), fs::read_to_string(path)?);
+            }
+        }
+
+        let catalog = self.load_catalog()?;
+        for id in session_ids {
+            let content = fs::read_to_string(self.session_path(id))
+                .map_err(|e| format!("Session {}: {}", id, e))?;
+            let metadata = catalog.sessions.get(id).map(|e| e.metadata.clone()).unwrap_or_default();
+            bundle.sessions.push(bundle::BundledSession { id: id.clone(), metadata, content });
+        }
+        Ok(bundle)
+    }
+
+    /// Reject bundles naming files we don't manage, so an import can't write elsewhere
+    fn check_bundle(bundle: &ConsoleBundle) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(name) = bundle.files.keys().find(|n| !bundle::CONFIG_FILES.contains(&n.as_str())) {
+            return Err(format!("Bundle contains unknown file '{}'", name).into());
+        }
+        if let Some(session) = bundle.sessions.iter().find(|s| s.id.is_empty() || !s.id.chars().all(|c| c.is_ascii_digit())) {
+            return Err(format!("Bundle contains invalid session id '{}'", session.id).into());
+        }
+        Ok(())
+    }
+
+    /// Compare a bundle with the installed configuration and sessions
+    pub fn preview_bundle_import(&self, bundle: &ConsoleBundle) -> Result<ImportPreview, Box<dyn std::error::Error>> {
+        Self::check_bundle(bundle)?;
+        let mut preview = ImportPreview::default();
+
+        for (name, content) in &bundle.files {
+            let local = fs::read_to_string(self.data_dir().join(name)).ok();
+            if name.ends_with(".json") {
+                let incoming = bundle::parse_config(name, content)?;
+                let existing = local.map(|c| bundle::parse_config(name, &c)).transpose()?;
+                bundle::compare_json(name, existing.as_ref(), &incoming, &mut preview.items);
+            } else {
+                let status = match &local {
+                    None => ItemStatus::New,
+                    Some(local) if local == content => ItemStatus::Identical,
+                    Some(_) => ItemStatus::Conflict,
+                };
+                preview.items.push(PreviewItem {
+                    name: name.clone(),
+                    status,
+                    existing: local.map(|c| format!("{} bytes", c.len())),
+                    incoming: format!("{} bytes", content.len()),
+                });
+            }
+        }
+
+        for session in &bundle.sessions {
+            let local = fs::read_to_string(self.session_path(&session.id)).ok();
+            let status = match &local {
+                None => ItemStatus::New,
+                Some(local) if *local == session.content => ItemStatus::Identical,
+                Some(_) => ItemStatus::Conflict,
+            };
+            preview.items.push(PreviewItem {
+                name: format!("session {}", session.id),
+                status,
+                existing: local.map(|c| format!("{} bytes", c.len())),
+                incoming: format!("{} bytes ({})", session.content.len(), session.metadata.name),
+            });
+        }
+        Ok(preview)
+    }
+
+    /// Import a bundle, merging JSON settings one by one. Sessions never
+    /// overwrite local ones: a different session with the same id is imported
+    /// under a new id. A snapshot is taken first so the import can be undone.
+    pub fn import_bundle(&self, bundle: &ConsoleBundle, options: &ImportOptions) -> Result<ImportReport, Box<dyn std::error::Error>> {
+        Self::check_bundle(bundle)?;
+        self.create_snapshot()?;
+        let mut report = ImportReport::default();
+
+        for (name, content) in &bundle.files {
+            let path = self.data_dir().join(name);
+            let local = fs::read_to_string(&path).ok();
+            let merged = match (&local, name.ends_with(".json")) {
+                (None, _) => Some(content.clone()),
+                (Some(local), _) if local == content => None,
+                (Some(local), true) => {
+                    let mut value = bundle::parse_config(name, local)?;
+                    let before = value.clone();
+                    report.kept_existing.extend(bundle::merge_json(name, &mut value, &bundle::parse_config(name, content)?, options.resolution));
+                    (value != before).then(|| bundle::format_config(name, &value)).transpose()?
+                }
+                (Some(_), false) => match options.resolution {
+                    bundle::ConflictResolution::UseBundle => Some(content.clone()),
+                    bundle::ConflictResolution::KeepExisting => {
+                        report.kept_existing.push(name.clone());
+                        None
+                    }
+                },
+            };
+            if let Some(merged) = merged {
+                fs::write(&path, merged)?;
+                report.updated_files.push(name.clone());
+            }
+        }
+
+        if options.include_sessions {
+            let mut catalog = self.load_catalog()?;
+            for session in &bundle.sessions {
+                let mut id = session.id.clone();
+                if let Ok(local) = fs::read_to_string(self.session_path(&id)) {
+                    if local == session.content {
+                        continue;
+                    }
+                    let mut next = id.parse::<u64>()?;
+                    while Path::new(&self.session_path(&next.to_string())).exists() {
+                        next += 1;
+                    }
+                    id = next.to_string();
+                }
+                fs::write(self.session_path(&id), &session.content)?;
+                self.seal_session(&id)?;
+                self.finalize_session(&id)?;
+                let entry = catalog.sessions.entry(id.clone()).or_default();
+                entry.metadata = session.metadata.clone();
+                entry.archived = schema::peek_kind(session.content.as_bytes()).as_deref() == Some(FileKind::CompressedSession.name());
+                report.imported_sessions.push(id);
+            }
+            self.save_catalog(&catalog)?;
+        }
+        Ok(report)
+    }
+
     /// Check if storage is healthy
     pub fn is_healthy(&self) -> Result<bool, Box<dyn std::error::Error>> {
         // Check if main file is readable
@@ -200,6 +1154,15 @@ impl Storage {
     }
 }
 
+/// Parse session file content, whether stored raw or archived with compression
+pub fn decode_session(content: &[u8]) -> Result<Vec<TelemetryData>, Box<dyn std::error::Error>> {
+    if schema::peek_kind(content).as_deref() == Some(FileKind::CompressedSession.name()) {
+        let compressed: compression::CompressedSession = schema::decode(FileKind::CompressedSession, content)?;
+        return Ok(compression::decompress_session(&compressed));
+    }
+    schema::decode(FileKind::Session, content)
+}
+
 #[derive(Default, Debug)]
 pub struct StorageStats {
     pub main_file_size: u64,
@@ -220,6 +1183,7 @@ impl StorageStats {
 /// Utility functions for data compression and optimization
 pub mod utils {
     use super::*;
+    use sha2::Digest;
 
     /// Compress telemetry data by removing redundant information
     pub fn compress_telemetry_data(data_points: &[TelemetryData]) -> Vec<TelemetryData> {
@@ -253,19 +1217,13 @@ pub mod utils {
         a.gear != b.gear
     }
 
-    /// Calculate data integrity hash
+    /// Calculate data integrity hash from the SHA-256 of all fields.
+    /// Stable across Rust versions, unlike `DefaultHasher`.
     pub fn calculate_data_hash(data: &TelemetryData) -> u64 {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-        
-        let mut hasher = DefaultHasher::new();
-        data.timestamp.hash(&mut hasher);
-        ((data.speed * 100.0) as u64).hash(&mut hasher);
-        ((data.rpm * 10.0) as u64).hash(&mut hasher);
-        ((data.engine_temp * 10.0) as u64).hash(&mut hasher);
-        data.gear.hash(&mut hasher);
-        
-        hasher.finish()
+        let digest = sha2::Sha256::digest(serde_json::to_vec(data).unwrap_or_default());
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        u64::from_be_bytes(bytes)
     }
 
     /// Validate data integrity
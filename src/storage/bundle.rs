@@ -0,0 +1,194 @@
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use super::catalog::SessionMetadata;
+use super::schema::{self, FileKind};
+
+pub const LAYOUT_FILE: &str = "ui_layout.xml";
+pub const PLUGIN_CONFIGS_FILE: &str = "plugin_configs.json";
+pub const VEHICLE_PROFILE_FILE: &str = "vehicle_profile.json";
+pub const ALERT_RULES_FILE: &str = "alert_rules.json";
+pub const THEME_FILE: &str = "theme.json";
+
+/// Configuration files carried by a bundle, relative to the data directory
+pub const CONFIG_FILES: &[&str] = &[LAYOUT_FILE, PLUGIN_CONFIGS_FILE, VEHICLE_PROFILE_FILE, ALERT_RULES_FILE, THEME_FILE];
+
+/// A session carried by a bundle, stored exactly as it was on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundledSession {
+    pub id: String,
+    pub metadata: SessionMetadata,
+    pub content: String,
+}
+
+/// Everything needed to set up another head unit like this one
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConsoleBundle {
+    pub created_ms: u64,
+    /// Configuration file contents keyed by file name
+    pub files: BTreeMap<String, String>,
+    #[serde(default)]
+    pub sessions: Vec<BundledSession>,
+}
+
+impl ConsoleBundle {
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, schema::encode(FileKind::Bundle, self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        schema::decode(FileKind::Bundle, &fs::read(path)?)
+    }
+}
+
+/// How a bundle item compares with what is already installed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ItemStatus {
+    /// Not present locally, will be added
+    New,
+    /// Present locally with the same value, nothing to do
+    Identical,
+    /// Present locally with a different value
+    Conflict,
+}
+
+/// One setting, file or session of a bundle and its status
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewItem {
+    /// File name, followed by the setting path for settings inside JSON files
+    /// (`theme.json: accent_color`) or `session <id>` for sessions
+    pub name: String,
+    pub status: ItemStatus,
+    pub existing: Option<String>,
+    pub incoming: String,
+}
+
+/// What importing a bundle would change
+#[derive(Debug, Clone, Default)]
+pub struct ImportPreview {
+    pub items: Vec<PreviewItem>,
+}
+
+impl ImportPreview {
+    pub fn conflicts(&self) -> impl Iterator<Item = &PreviewItem> {
+        self.items.iter().filter(|i| i.status == ItemStatus::Conflict)
+    }
+
+    pub fn has_conflicts(&self) -> bool {
+        self.conflicts().next().is_some()
+    }
+}
+
+/// Which side wins when a setting differs
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ConflictResolution {
+    #[default]
+    KeepExisting,
+    UseBundle,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    pub resolution: ConflictResolution,
+    /// Import the sessions carried by the bundle
+    pub include_sessions: bool,
+}
+
+/// Outcome of importing a bundle
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    /// Configuration files written
+    pub updated_files: Vec<String>,
+    /// Conflicting items left at their existing value
+    pub kept_existing: Vec<String>,
+    /// Local ids of the imported sessions
+    pub imported_sessions: Vec<String>,
+}
+
+/// Read a configuration file as JSON, unwrapping the schema envelope where it has one
+pub fn parse_config(name: &str, content: &str) -> Result<Value, Box<dyn std::error::Error>> {
+    if name == PLUGIN_CONFIGS_FILE {
+        return schema::decode(FileKind::PluginConfigs, content.as_bytes());
+    }
+    Ok(serde_json::from_str(content)?)
+}
+
+/// Serialize a configuration file the way its owner writes it
+pub fn format_config(name: &str, value: &Value) -> Result<String, Box<dyn std::error::Error>> {
+    if name == PLUGIN_CONFIGS_FILE {
+        return schema::encode(FileKind::PluginConfigs, value);
+    }
+    Ok(serde_json::to_string_pretty(value)?)
+}
+
+/// `theme.json: colors` for top-level settings, `theme.json: colors.accent` below that
+fn setting_name(prefix: &str, key: &str) -> String {
+    if prefix.ends_with(".json") {
+        format!("{}: {}", prefix, key)
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Compare two JSON values setting by setting, recursing into objects
+pub fn compare_json(prefix: &str, existing: Option<&Value>, incoming: &Value, items: &mut Vec<PreviewItem>) {
+    match (existing, incoming) {
+        (None | Some(Value::Object(_)), Value::Object(incoming)) if !incoming.is_empty() => {
+            for (key, value) in incoming {
+                let name = setting_name(prefix, key);
+                compare_json(&name, existing.and_then(|e| e.get(key)), value, items);
+            }
+        }
+        (existing, incoming) => {
+            let status = match existing {
+                None => ItemStatus::New,
+                Some(e) if e == incoming => ItemStatus::Identical,
+                Some(_) => ItemStatus::Conflict,
+            };
+            items.push(PreviewItem {
+                name: prefix.to_string(),
+                status,
+                existing: existing.map(describe),
+                incoming: describe(incoming),
+            });
+        }
+    }
+}
+
+/// Merge `incoming` into `existing`. Settings only present on one side are
+/// kept, differing settings are resolved by `resolution`. Returns the names
+/// of conflicting settings that kept their existing value.
+pub fn merge_json(prefix: &str, existing: &mut Value, incoming: &Value, resolution: ConflictResolution) -> Vec<String> {
+    let mut kept = Vec::new();
+    match (existing, incoming) {
+        (Value::Object(existing), Value::Object(incoming)) => {
+            for (key, value) in incoming {
+                let name = setting_name(prefix, key);
+                match existing.get_mut(key) {
+                    Some(current) => kept.extend(merge_json(&name, current, value, resolution)),
+                    None => {
+                        existing.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (existing, incoming) => {
+            if existing != incoming {
+                match resolution {
+                    ConflictResolution::UseBundle => *existing = incoming.clone(),
+                    ConflictResolution::KeepExisting => kept.push(prefix.to_string()),
+                }
+            }
+        }
+    }
+    kept
+}
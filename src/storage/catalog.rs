@@ -0,0 +1,156 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use crate::telemetry::TelemetryData;
+use super::export::track_distance;
+
+/// User-editable details attached to a recorded session
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SessionMetadata {
+    pub name: String,
+    pub vehicle_profile: String,
+    pub driver: String,
+    pub tags: Vec<String>,
+    pub notes: String,
+    /// Pinned sessions are never deleted or downsampled by retention
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+/// Figures computed from a session's data points
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SessionSummary {
+    pub start_time: u64,
+    pub end_time: u64,
+    pub sample_count: usize,
+    pub distance_meters: f64,
+    pub max_speed: f64,
+}
+
+impl SessionSummary {
+    pub fn from_data(data_points: &[TelemetryData]) -> Self {
+        Self {
+            start_time: data_points.first().map(|p| p.timestamp).unwrap_or(0),
+            end_time: data_points.last().map(|p| p.timestamp).unwrap_or(0),
+            sample_count: data_points.len(),
+            distance_meters: track_distance(data_points),
+            max_speed: data_points.iter().map(|p| p.speed).fold(0.0, f64::max),
+        }
+    }
+
+    pub fn duration_secs(&self) -> u64 {
+        self.end_time.saturating_sub(self.start_time)
+    }
+}
+
+/// Catalogue record for one session, persisted in the catalogue index
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub metadata: SessionMetadata,
+    pub summary: Option<SessionSummary>,
+    /// Set once retention has replaced the session with a downsampled copy
+    #[serde(default)]
+    pub downsampled: bool,
+    /// Set once retention has archived the session with lossy compression
+    #[serde(default)]
+    pub archived: bool,
+}
+
+/// Sessions found by `Storage::list_sessions`
+#[derive(Debug, Clone, Default)]
+pub struct SessionListing {
+    pub sessions: Vec<SessionInfo>,
+    /// Sessions that couldn't be read, with the reason
+    pub failed: Vec<(String, String)>,
+}
+
+/// A session as listed by the catalogue
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub id: String,
+    pub file_size: u64,
+    pub metadata: SessionMetadata,
+    pub summary: SessionSummary,
+}
+
+impl SessionInfo {
+    /// Name to show in lists, falls back to the session id
+    pub fn display_name(&self) -> String {
+        if self.metadata.name.is_empty() {
+            format!("Session {}", self.id)
+        } else {
+            self.metadata.name.clone()
+        }
+    }
+}
+
+/// The catalogue index file, keyed by session id
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Catalog {
+    pub sessions: HashMap<String, CatalogEntry>,
+}
+
+/// Criteria for searching the session catalogue
+#[derive(Debug, Clone, Default)]
+pub struct SessionFilter {
+    /// Case-insensitive text matched against name, notes, driver, vehicle and tags
+    pub text: Option<String>,
+    pub tag: Option<String>,
+    pub driver: Option<String>,
+    pub vehicle_profile: Option<String>,
+    pub started_after: Option<u64>,
+    pub started_before: Option<u64>,
+    pub min_duration_secs: Option<u64>,
+}
+
+impl SessionFilter {
+    pub fn matches(&self, session: &SessionInfo) -> bool {
+        let metadata = &session.metadata;
+
+        if let Some(text) = &self.text {
+            let needle = text.to_lowercase();
+            let haystack = [
+                session.display_name(),
+                metadata.notes.clone(),
+                metadata.driver.clone(),
+                metadata.vehicle_profile.clone(),
+                metadata.tags.join(" "),
+            ]
+            .join(" ")
+            .to_lowercase();
+            if !haystack.contains(&needle) {
+                return false;
+            }
+        }
+        if let Some(tag) = &self.tag {
+            if !metadata.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                return false;
+            }
+        }
+        if let Some(driver) = &self.driver {
+            if !metadata.driver.eq_ignore_ascii_case(driver) {
+                return false;
+            }
+        }
+        if let Some(vehicle) = &self.vehicle_profile {
+            if !metadata.vehicle_profile.eq_ignore_ascii_case(vehicle) {
+                return false;
+            }
+        }
+        if let Some(after) = self.started_after {
+            if session.summary.start_time < after {
+                return false;
+            }
+        }
+        if let Some(before) = self.started_before {
+            if session.summary.start_time > before {
+                return false;
+            }
+        }
+        if let Some(min_duration) = self.min_duration_secs {
+            if session.summary.duration_secs() < min_duration {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,230 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use crate::telemetry::{TelemetryData, CHANNELS};
+
+/// Maximum reconstruction error allowed per channel
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CompressionTolerances {
+    /// Tolerance for channels without their own entry
+    pub default: f64,
+    pub per_channel: HashMap<String, f64>,
+}
+
+impl Default for CompressionTolerances {
+    fn default() -> Self {
+        let per_channel = [
+            ("speed", 0.5),
+            ("rpm", 50.0),
+            ("engine_temp", 1.0),
+            ("fuel_level", 0.5),
+            ("gear", 0.0),
+            ("latitude", 0.00001),
+            ("longitude", 0.00001),
+            ("altitude", 1.0),
+        ]
+        .iter()
+        .map(|(name, tolerance)| (name.to_string(), *tolerance))
+        .collect();
+
+        Self { default: 0.1, per_channel }
+    }
+}
+
+impl CompressionTolerances {
+    pub fn tolerance(&self, channel: &str) -> f64 {
+        self.per_channel.get(channel).copied().unwrap_or(self.default).max(0.0)
+    }
+}
+
+/// One channel reduced to the samples needed to rebuild it within tolerance
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CompressedChannel {
+    pub name: String,
+    /// Sample indexes of the kept points, ascending
+    pub indexes: Vec<u32>,
+    pub values: Vec<f64>,
+    /// Sample indexes where the channel had no value (GPS without a fix)
+    #[serde(default)]
+    pub missing: Vec<u32>,
+}
+
+/// A session stored as per-channel piecewise linear segments
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CompressedSession {
+    pub sample_count: usize,
+    pub tolerances: CompressionTolerances,
+    pub channels: Vec<CompressedChannel>,
+}
+
+/// Result of compressing one channel
+#[derive(Debug, Clone)]
+pub struct ChannelReport {
+    pub name: String,
+    pub tolerance: f64,
+    pub kept_points: usize,
+    pub max_error: f64,
+}
+
+/// Achieved compression and the measured reconstruction error
+#[derive(Debug, Clone, Default)]
+pub struct CompressionReport {
+    pub original_points: usize,
+    pub kept_points: usize,
+    pub original_bytes: u64,
+    pub compressed_bytes: u64,
+    pub channels: Vec<ChannelReport>,
+}
+
+impl CompressionReport {
+    /// Original size divided by compressed size, by stored points
+    pub fn point_ratio(&self) -> f64 {
+        if self.kept_points == 0 {
+            return 1.0;
+        }
+        self.original_points as f64 / self.kept_points as f64
+    }
+
+    /// Original size divided by compressed size, by bytes on disk
+    pub fn byte_ratio(&self) -> f64 {
+        if self.compressed_bytes == 0 {
+            return 1.0;
+        }
+        self.original_bytes as f64 / self.compressed_bytes as f64
+    }
+
+    /// Largest reconstruction error relative to its channel's tolerance (<= 1.0 when within bounds)
+    pub fn worst_error_ratio(&self) -> f64 {
+        self.channels
+            .iter()
+            .map(|c| if c.tolerance > 0.0 { c.max_error / c.tolerance } else if c.max_error > 1e-9 { f64::INFINITY } else { 0.0 })
+            .fold(0.0, f64::max)
+    }
+}
+
+/// Swinging door compression of `(index, value)` points.
+///
+/// Keeps a fan of slopes from the last kept point that pass within `tolerance`
+/// of every point since. When the fan closes, the previous point is kept with
+/// its value moved onto the fan so linear interpolation stays within bounds.
+pub fn swinging_door(points: &[(u32, f64)], tolerance: f64) -> Vec<(u32, f64)> {
+    if points.len() <= 2 {
+        return points.to_vec();
+    }
+
+    let mut kept = vec![points[0]];
+    let (mut anchor_x, mut anchor_y) = (points[0].0 as f64, points[0].1);
+    let mut low = f64::NEG_INFINITY;
+    let mut high = f64::INFINITY;
+    let mut slope = 0.0;
+
+    for i in 1..points.len() {
+        let (x, y) = (points[i].0 as f64, points[i].1);
+        let dx = x - anchor_x;
+        let new_low = low.max((y - tolerance - anchor_y) / dx);
+        let new_high = high.min((y + tolerance - anchor_y) / dx);
+
+        if new_low > new_high {
+            // Fan closed: keep the previous point on the last valid slope
+            let (prev_x, _) = points[i - 1];
+            let prev_y = anchor_y + slope * (prev_x as f64 - anchor_x);
+            kept.push((prev_x, prev_y));
+            anchor_x = prev_x as f64;
+            anchor_y = prev_y;
+
+            let dx = x - anchor_x;
+            low = (y - tolerance - anchor_y) / dx;
+            high = (y + tolerance - anchor_y) / dx;
+        } else {
+            low = new_low;
+            high = new_high;
+        }
+        slope = ((y - anchor_y) / (x - anchor_x)).clamp(low, high);
+    }
+
+    let (last_x, _) = *points.last().unwrap();
+    kept.push((last_x, anchor_y + slope * (last_x as f64 - anchor_x)));
+    kept
+}
+
+/// Linear interpolation of kept points at a sample index
+fn interpolate(indexes: &[u32], values: &[f64], index: u32) -> f64 {
+    match indexes.binary_search(&index) {
+        Ok(i) => values[i],
+        Err(0) => values.first().copied().unwrap_or(0.0),
+        Err(i) if i >= indexes.len() => values.last().copied().unwrap_or(0.0),
+        Err(i) => {
+            let (x0, x1) = (indexes[i - 1] as f64, indexes[i] as f64);
+            let t = (index as f64 - x0) / (x1 - x0);
+            values[i - 1] + (values[i] - values[i - 1]) * t
+        }
+    }
+}
+
+/// Compress a session channel by channel, timestamps included
+pub fn compress_session(data_points: &[TelemetryData], tolerances: &CompressionTolerances) -> (CompressedSession, CompressionReport) {
+    let mut channels = Vec::new();
+    let mut report = CompressionReport::default();
+
+    let extra = TelemetryData::extra_channel_names(data_points);
+    let names = std::iter::once("timestamp")
+        .chain(CHANNELS.iter().map(|c| c.name))
+        .chain(extra.iter().map(|n| n.as_str()));
+    for name in names {
+        // Timestamps are kept exact so reconstruction never reorders samples
+        let tolerance = if name == "timestamp" { 0.0 } else { tolerances.tolerance(name) };
+        let mut present = Vec::new();
+        let mut missing = Vec::new();
+        for (i, point) in data_points.iter().enumerate() {
+            let value = if name == "timestamp" { Some(point.timestamp as f64) } else { point.channel_value(name) };
+            match value {
+                Some(v) => present.push((i as u32, v)),
+                None => missing.push(i as u32),
+            }
+        }
+
+        let kept = swinging_door(&present, tolerance);
+        let indexes: Vec<u32> = kept.iter().map(|(i, _)| *i).collect();
+        let values: Vec<f64> = kept.iter().map(|(_, v)| *v).collect();
+        let max_error = present
+            .iter()
+            .map(|(i, v)| (interpolate(&indexes, &values, *i) - v).abs())
+            .fold(0.0, f64::max);
+
+        report.original_points += present.len();
+        report.kept_points += kept.len();
+        report.channels.push(ChannelReport {
+            name: name.to_string(),
+            tolerance,
+            kept_points: kept.len(),
+            max_error,
+        });
+        channels.push(CompressedChannel { name: name.to_string(), indexes, values, missing });
+    }
+
+    let compressed = CompressedSession {
+        sample_count: data_points.len(),
+        tolerances: tolerances.clone(),
+        channels,
+    };
+    (compressed, report)
+}
+
+/// Rebuild every sample of a compressed session
+pub fn decompress_session(compressed: &CompressedSession) -> Vec<TelemetryData> {
+    let mut points = vec![TelemetryData::default(); compressed.sample_count];
+    for channel in &compressed.channels {
+        for (i, point) in points.iter_mut().enumerate() {
+            let index = i as u32;
+            if channel.indexes.is_empty() || channel.missing.binary_search(&index).is_ok() {
+                continue;
+            }
+            let value = interpolate(&channel.indexes, &channel.values, index);
+            if channel.name == "timestamp" {
+                point.timestamp = value.round() as u64;
+            } else if !point.set_channel_value(&channel.name, value) {
+                point.extra.insert(channel.name.clone(), value);
+            }
+        }
+    }
+    points
+}
@@ -0,0 +1,362 @@
+use crate::telemetry::{TelemetryData, CHANNELS};
+use crate::telemetry::utils::calculate_distance;
+use quick_xml::escape::escape;
+
+/// Number of colour bands used for the speed-graded KML line
+const KML_SPEED_BANDS: usize = 8;
+
+/// Return only the points that carry a GPS fix
+pub fn gps_points(data_points: &[TelemetryData]) -> Vec<&TelemetryData> {
+    data_points
+        .iter()
+        .filter(|p| p.latitude.is_some() && p.longitude.is_some())
+        .collect()
+}
+
+/// Total distance of a track in meters, summed over consecutive GPS fixes
+pub fn track_distance(data_points: &[TelemetryData]) -> f64 {
+    let points = gps_points(data_points);
+    points
+        .windows(2)
+        .map(|w| {
+            calculate_distance(
+                w[0].latitude.unwrap(),
+                w[0].longitude.unwrap(),
+                w[1].latitude.unwrap(),
+                w[1].longitude.unwrap(),
+            )
+        })
+        .sum()
+}
+
+/// Build a GPX 1.1 track from the session's GPS fixes.
+///
+/// Speed (m/s), RPM and engine temperature are written as track point
+/// extensions in the `console` namespace.
+pub fn to_gpx(name: &str, data_points: &[TelemetryData]) -> Result<String, String> {
+    let points = gps_points(data_points);
+    if points.is_empty() {
+        return Err("Session has no GPS fixes".to_string());
+    }
+
+    let mut gpx = String::new();
+    gpx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    gpx.push_str("<gpx version=\"1.1\" creator=\"Console\" xmlns=\"http://www.topografix.com/GPX/1/1\" xmlns:console=\"https://github.com/makalin/console/gpx/1\">\n");
+    gpx.push_str(&format!("  <trk>\n    <name>{}</name>\n    <trkseg>\n", escape(name)));
+
+    for point in points {
+        gpx.push_str(&format!(
+            "      <trkpt lat=\"{:.7}\" lon=\"{:.7}\">\n",
+            point.latitude.unwrap(),
+            point.longitude.unwrap()
+        ));
+        if let Some(altitude) = point.altitude {
+            gpx.push_str(&format!("        <ele>{:.1}</ele>\n", altitude));
+        }
+        gpx.push_str(&format!("        <time>{}</time>\n", format_iso8601(point.timestamp)));
+        gpx.push_str("        <extensions>\n");
+        gpx.push_str(&format!("          <console:speed>{:.2}</console:speed>\n", point.speed_ms()));
+        gpx.push_str(&format!("          <console:rpm>{:.0}</console:rpm>\n", point.rpm));
+        gpx.push_str(&format!("          <console:engine_temp>{:.1}</console:engine_temp>\n", point.engine_temp));
+        gpx.push_str("        </extensions>\n");
+        gpx.push_str("      </trkpt>\n");
+    }
+
+    gpx.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+    Ok(gpx)
+}
+
+/// Build a KML document with the track drawn as a line coloured by speed.
+///
+/// Consecutive fixes falling in the same speed band are merged into one
+/// `LineString`, so the colour changes only where the speed band does.
+pub fn to_kml(name: &str, data_points: &[TelemetryData]) -> Result<String, String> {
+    let points = gps_points(data_points);
+    if points.is_empty() {
+        return Err("Session has no GPS fixes".to_string());
+    }
+
+    let max_speed = points.iter().map(|p| p.speed).fold(0.0, f64::max);
+
+    let mut kml = String::new();
+    kml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    kml.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n");
+    kml.push_str(&format!("  <Document>\n    <name>{}</name>\n", escape(name)));
+
+    for band in 0..KML_SPEED_BANDS {
+        kml.push_str(&format!(
+            "    <Style id=\"speed{}\"><LineStyle><color>{}</color><width>4</width></LineStyle></Style>\n",
+            band,
+            band_color(band)
+        ));
+    }
+
+    let mut segment: Vec<&TelemetryData> = Vec::new();
+    let mut segment_band = speed_band(points[0].speed, max_speed);
+
+    for point in &points {
+        let band = speed_band(point.speed, max_speed);
+        if band != segment_band && segment.len() > 1 {
+            push_kml_segment(&mut kml, &segment, segment_band);
+            // Start the next segment at the last point so the line stays continuous
+            segment = vec![*segment.last().unwrap()];
+        }
+        segment_band = band;
+        segment.push(point);
+    }
+    push_kml_segment(&mut kml, &segment, segment_band);
+
+    kml.push_str("  </Document>\n</kml>\n");
+    Ok(kml)
+}
+
+fn push_kml_segment(kml: &mut String, segment: &[&TelemetryData], band: usize) {
+    if segment.is_empty() {
+        return;
+    }
+
+    let coordinates: Vec<String> = segment
+        .iter()
+        .map(|p| {
+            format!(
+                "{:.7},{:.7},{:.1}",
+                p.longitude.unwrap(),
+                p.latitude.unwrap(),
+                p.altitude.unwrap_or(0.0)
+            )
+        })
+        .collect();
+
+    kml.push_str("    <Placemark>\n");
+    kml.push_str(&format!("      <styleUrl>#speed{}</styleUrl>\n", band));
+    kml.push_str("      <LineString>\n        <tessellate>1</tessellate>\n");
+    kml.push_str(&format!("        <coordinates>{}</coordinates>\n", coordinates.join(" ")));
+    kml.push_str("      </LineString>\n    </Placemark>\n");
+}
+
+/// Map a speed onto one of the KML colour bands
+fn speed_band(speed: f64, max_speed: f64) -> usize {
+    if max_speed <= 0.0 {
+        return 0;
+    }
+    let ratio = (speed / max_speed).clamp(0.0, 1.0);
+    ((ratio * KML_SPEED_BANDS as f64) as usize).min(KML_SPEED_BANDS - 1)
+}
+
+/// Colour for a speed band, graded green -> yellow -> red (KML uses aabbggrr)
+fn band_color(band: usize) -> String {
+    let t = band as f64 / (KML_SPEED_BANDS - 1) as f64;
+    let (r, g) = if t < 0.5 {
+        ((t * 2.0 * 255.0) as u8, 255u8)
+    } else {
+        (255u8, ((1.0 - t) * 2.0 * 255.0) as u8)
+    };
+    format!("ff00{:02x}{:02x}", g, r)
+}
+
+/// Format a unix timestamp (seconds) as an ISO 8601 UTC string
+pub fn format_iso8601(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds_of_day = timestamp % 86400;
+
+    // Civil-from-days conversion (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        (seconds_of_day % 3600) / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Session details written into the header block of motorsport CSV exports
+#[derive(Debug, Clone, Default)]
+pub struct MotorsportExport {
+    pub venue: String,
+    pub vehicle: String,
+    pub driver: String,
+    pub comment: String,
+    /// Beacon (lap marker) times in seconds from the start of the session
+    pub beacons: Vec<f64>,
+}
+
+/// Estimate a fixed sample rate in Hz from the session timestamps.
+///
+/// Timestamps only have one second resolution, so the rate is the number of
+/// samples per elapsed second rounded to a whole number (at least 1 Hz).
+pub fn estimate_sample_rate(data_points: &[TelemetryData]) -> u32 {
+    if data_points.len() < 2 {
+        return 1;
+    }
+    let first = data_points.first().unwrap().timestamp;
+    let last = data_points.last().unwrap().timestamp;
+    let duration = last.saturating_sub(first) + 1;
+    ((data_points.len() as f64 / duration as f64).round() as u32).max(1)
+}
+
+/// Seconds from the start of the session to each sample.
+///
+/// Timestamps only have one second resolution, so samples that share a
+/// second are spread evenly across it. Gaps in the recording stay gaps.
+pub fn sample_times(data_points: &[TelemetryData]) -> Vec<f64> {
+    let start = data_points.iter().map(|p| p.timestamp).min().unwrap_or(0);
+    data_points
+        .chunk_by(|a, b| a.timestamp == b.timestamp)
+        .flat_map(|second| {
+            let offset = (second[0].timestamp - start) as f64;
+            (0..second.len()).map(move |i| offset + i as f64 / second.len() as f64)
+        })
+        .collect()
+}
+
+/// Detect beacon times from GPS by finding each crossing into a radius around
+/// the start/finish line. Crossings closer together than `min_lap_seconds` are ignored.
+pub fn detect_beacons(
+    data_points: &[TelemetryData],
+    line_latitude: f64,
+    line_longitude: f64,
+    radius_meters: f64,
+    min_lap_seconds: f64,
+) -> Vec<f64> {
+    let mut beacons: Vec<f64> = Vec::new();
+    let mut inside = true; // Don't count the start of the session as a lap
+
+    for (point, time) in data_points.iter().zip(sample_times(data_points)) {
+        let (lat, lon) = match (point.latitude, point.longitude) {
+            (Some(lat), Some(lon)) => (lat, lon),
+            _ => continue,
+        };
+        let near = calculate_distance(lat, lon, line_latitude, line_longitude) <= radius_meters;
+        if near && !inside {
+            let since_last = time - beacons.last().copied().unwrap_or(0.0);
+            if since_last >= min_lap_seconds {
+                beacons.push(time);
+            }
+        }
+        inside = near;
+    }
+    beacons
+}
+
+/// Build a CSV in the MoTeC i2 import format
+pub fn to_motec_csv(options: &MotorsportExport, data_points: &[TelemetryData]) -> String {
+    let rate = estimate_sample_rate(data_points);
+    let times = sample_times(data_points);
+    let duration = times.iter().copied().fold(0.0, f64::max);
+    let start = data_points.iter().map(|p| p.timestamp).min().unwrap_or(0);
+    let iso = format_iso8601(start);
+    let date = format!("{}/{}/{}", &iso[8..10], &iso[5..7], &iso[0..4]);
+    let time = &iso[11..19];
+    let beacons: Vec<String> = options.beacons.iter().map(|b| format!("{:.3}", b)).collect();
+
+    let mut csv = String::new();
+    csv.push_str("\"Format\",\"MoTeC CSV File\",,,\"Workbook\",\"\"\n");
+    csv.push_str(&format!("\"Venue\",{},,,\"Worksheet\",\"\"\n", csv_quote(&options.venue)));
+    csv.push_str(&format!("\"Vehicle\",{},,,\"Vehicle Desc\",\"\"\n", csv_quote(&options.vehicle)));
+    csv.push_str(&format!("\"Driver\",{},,,\"Engine ID\",\"\"\n", csv_quote(&options.driver)));
+    csv.push_str("\"Device\",\"Console\",,,\"Session\",\"\"\n");
+    csv.push_str(&format!("\"Comment\",{},,,\"Origin Time\",\"0.000\",\"s\"\n", csv_quote(&options.comment)));
+    csv.push_str(&format!("\"Log Date\",\"{}\",,,\"Start Time\",\"0.000\",\"s\"\n", date));
+    csv.push_str(&format!("\"Log Time\",\"{}\",,,\"End Time\",\"{:.3}\",\"s\"\n", time, duration));
+    csv.push_str(&format!("\"Sample Rate\",\"{}\",\"Hz\",,\"Start Distance\",\"0\",\"m\"\n", rate));
+    csv.push_str(&format!(
+        "\"Duration\",\"{:.3}\",\"s\",,\"End Distance\",\"{:.0}\",\"m\"\n",
+        duration,
+        track_distance(data_points)
+    ));
+    csv.push_str("\"Range\",\"entire outing\",,,,\n");
+    csv.push_str(&format!("\"Beacon Markers\",\"{}\"\n", beacons.join(" ")));
+    csv.push_str("\n\n");
+
+    push_channel_rows(&mut csv, data_points, &times, "s");
+    csv
+}
+
+/// Build a CSV in the AiM Race Studio import format
+pub fn to_aim_csv(options: &MotorsportExport, data_points: &[TelemetryData]) -> String {
+    let rate = estimate_sample_rate(data_points);
+    let times = sample_times(data_points);
+    let duration = times.iter().copied().fold(0.0, f64::max);
+    let start = data_points.iter().map(|p| p.timestamp).min().unwrap_or(0);
+    let iso = format_iso8601(start);
+    let beacons: Vec<String> = options.beacons.iter().map(|b| format!("{:.3}", b)).collect();
+
+    // Segment times are the lap durations between consecutive beacons
+    let mut segment_times = Vec::new();
+    let mut previous = 0.0;
+    for beacon in &options.beacons {
+        segment_times.push(format!("\"{}\"", format_lap_time(beacon - previous)));
+        previous = *beacon;
+    }
+
+    let mut csv = String::new();
+    csv.push_str("\"Format\",\"AiM CSV File\"\n");
+    csv.push_str(&format!("\"Session\",{}\n", csv_quote(&options.venue)));
+    csv.push_str(&format!("\"Vehicle\",{}\n", csv_quote(&options.vehicle)));
+    csv.push_str(&format!("\"Racer\",{}\n", csv_quote(&options.driver)));
+    csv.push_str("\"Championship\",\"\"\n");
+    csv.push_str(&format!("\"Comment\",{}\n", csv_quote(&options.comment)));
+    csv.push_str(&format!("\"Date\",\"{}\"\n", &iso[0..10]));
+    csv.push_str(&format!("\"Time\",\"{}\"\n", &iso[11..19]));
+    csv.push_str(&format!("\"Sample Rate\",\"{}\"\n", rate));
+    csv.push_str(&format!("\"Duration\",\"{:.3}\"\n", duration));
+    csv.push_str("\"Segment\",\"Session\"\n");
+    csv.push_str(&format!("\"Beacon Markers\",\"{}\"\n", beacons.join(" ")));
+    csv.push_str(&format!("\"Segment Times\",{}\n", segment_times.join(",")));
+    csv.push('\n');
+
+    push_channel_rows(&mut csv, data_points, &times, "sec");
+    csv
+}
+
+/// Write the channel name row, unit row and one row per sample. Plugin
+/// channels follow the built-in ones; values a sample lacks are left empty.
+fn push_channel_rows(csv: &mut String, data_points: &[TelemetryData], times: &[f64], time_unit: &str) {
+    let extra = TelemetryData::extra_channel_names(data_points);
+    let channels: Vec<(&str, &str)> = CHANNELS
+        .iter()
+        .map(|c| (c.name, c.unit))
+        .chain(extra.iter().map(|name| (name.as_str(), "")))
+        .collect();
+    let mut names = vec!["\"Time\"".to_string()];
+    let mut units = vec![format!("\"{}\"", time_unit)];
+    for (name, unit) in &channels {
+        names.push(csv_quote(name));
+        units.push(format!("\"{}\"", unit));
+    }
+    csv.push_str(&names.join(","));
+    csv.push('\n');
+    csv.push_str(&units.join(","));
+    csv.push_str("\n\n\n");
+
+    for (point, time) in data_points.iter().zip(times) {
+        let mut row = vec![format!("\"{:.3}\"", time)];
+        for (name, _) in &channels {
+            row.push(point.channel_value(name).map(|value| format!("\"{}\"", value)).unwrap_or_default());
+        }
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+}
+
+fn csv_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// Format a lap time in seconds as m:ss.sss
+fn format_lap_time(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).floor();
+    format!("{}:{:06.3}", minutes as u64, seconds - minutes * 60.0)
+}
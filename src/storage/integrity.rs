@@ -0,0 +1,181 @@
+use serde::{Serialize, Deserialize};
+use std::ops::Range;
+use sha2::{Digest, Sha256};
+use crate::telemetry::TelemetryData;
+use super::decode_session;
+
+/// Bytes of the session file covered by each range digest
+pub const DEFAULT_RANGE_BYTES: usize = 16 * 1024;
+
+/// SHA-256 of a byte slice as lowercase hex
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    to_hex(&Sha256::digest(bytes))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Digest of one byte range of the session file and the chain value up to and including it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RangeDigest {
+    pub index: usize,
+    pub offset: usize,
+    pub len: usize,
+    pub hash: String,
+    /// SHA-256 of the previous range's chain value followed by this range's hash
+    pub chain: String,
+}
+
+impl RangeDigest {
+    pub fn bytes(&self) -> Range<usize> {
+        self.offset..self.offset + self.len
+    }
+}
+
+/// Integrity record written alongside a session file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IntegrityManifest {
+    pub algorithm: String,
+    pub session_id: String,
+    /// Size of each range; manifests written before ranges were recorded have none
+    #[serde(default)]
+    pub range_bytes: usize,
+    pub sample_count: usize,
+    /// SHA-256 over the complete session file bytes
+    pub content_hash: String,
+    #[serde(default)]
+    pub ranges: Vec<RangeDigest>,
+}
+
+impl IntegrityManifest {
+    /// Final chain value; record it elsewhere to detect a rewritten manifest
+    pub fn chain_head(&self) -> &str {
+        self.ranges.last().map(|r| r.chain.as_str()).unwrap_or("")
+    }
+}
+
+/// Result of verifying a session against its manifest
+#[derive(Debug, Clone, Default)]
+pub struct IntegrityReport {
+    pub content_ok: bool,
+    pub chain_ok: bool,
+    /// Byte ranges of the file that no longer match their digest, including
+    /// bytes missing from or appended to the end
+    pub corrupted_ranges: Vec<Range<usize>>,
+    /// Set when the session can no longer be parsed
+    pub parse_error: Option<String>,
+    pub expected_samples: usize,
+    pub found_samples: usize,
+}
+
+impl IntegrityReport {
+    pub fn is_valid(&self) -> bool {
+        self.content_ok && self.chain_ok && self.corrupted_ranges.is_empty() && self.parse_error.is_none()
+    }
+
+    /// Human readable description of the result
+    pub fn describe(&self) -> String {
+        if self.is_valid() {
+            return format!("OK: {} samples verified", self.found_samples);
+        }
+        let mut lines = Vec::new();
+        if let Some(error) = &self.parse_error {
+            lines.push(format!("Session cannot be parsed: {}", error));
+        }
+        if !self.content_ok {
+            lines.push("File content digest does not match".to_string());
+        }
+        if !self.chain_ok {
+            lines.push("Range hash chain is broken".to_string());
+        }
+        if self.expected_samples != self.found_samples {
+            lines.push(format!(
+                "Expected {} samples, found {}",
+                self.expected_samples, self.found_samples
+            ));
+        }
+        for range in &self.corrupted_ranges {
+            lines.push(format!("Bytes {}..{} are corrupted", range.start, range.end));
+        }
+        lines.join("\n")
+    }
+}
+
+fn chain_hash(previous: &str, hash: &str) -> String {
+    sha256_hex(format!("{}{}", previous, hash).as_bytes())
+}
+
+/// Hash the raw bytes of a session file in ranges of `range_bytes`, chaining
+/// the digests. The chain is seeded with the session id so ranges can't be
+/// moved between sessions.
+pub fn compute_ranges(session_id: &str, content: &[u8], range_bytes: usize) -> Vec<RangeDigest> {
+    let range_bytes = range_bytes.max(1);
+    let mut previous = sha256_hex(session_id.as_bytes());
+    content
+        .chunks(range_bytes)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let hash = sha256_hex(chunk);
+            let chain = chain_hash(&previous, &hash);
+            previous = chain.clone();
+            RangeDigest {
+                index,
+                offset: index * range_bytes,
+                len: chunk.len(),
+                hash,
+                chain,
+            }
+        })
+        .collect()
+}
+
+/// Build the manifest for a session from its file bytes and parsed samples
+pub fn seal(session_id: &str, content: &[u8], points: &[TelemetryData], range_bytes: usize) -> IntegrityManifest {
+    IntegrityManifest {
+        algorithm: "sha256".to_string(),
+        session_id: session_id.to_string(),
+        range_bytes,
+        sample_count: points.len(),
+        content_hash: sha256_hex(content),
+        ranges: compute_ranges(session_id, content, range_bytes),
+    }
+}
+
+/// Verify a session file's bytes against its manifest
+pub fn verify(content: &[u8], manifest: &IntegrityManifest) -> IntegrityReport {
+    let mut report = IntegrityReport {
+        content_ok: sha256_hex(content) == manifest.content_hash,
+        expected_samples: manifest.sample_count,
+        ..Default::default()
+    };
+
+    // The stored chain must be consistent with the stored range hashes
+    let mut previous = sha256_hex(manifest.session_id.as_bytes());
+    report.chain_ok = true;
+    for range in &manifest.ranges {
+        let chain = chain_hash(&previous, &range.hash);
+        if chain != range.chain {
+            report.chain_ok = false;
+        }
+        previous = chain;
+    }
+
+    // Each recorded range must still hold the same bytes
+    for range in &manifest.ranges {
+        let intact = content.get(range.bytes()).is_some_and(|bytes| sha256_hex(bytes) == range.hash);
+        if !intact {
+            report.corrupted_ranges.push(range.bytes());
+        }
+    }
+    let covered = manifest.ranges.last().map(|r| r.bytes().end).unwrap_or(0);
+    if !manifest.ranges.is_empty() && content.len() > covered {
+        report.corrupted_ranges.push(covered..content.len());
+    }
+
+    match decode_session(content) {
+        Ok(points) => report.found_samples = points.len(),
+        Err(e) => report.parse_error = Some(e.to_string()),
+    }
+    report
+}
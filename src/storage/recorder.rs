@@ -0,0 +1,76 @@
+use crate::telemetry::TelemetryData;
+use super::Storage;
+use super::retention::RetentionPolicy;
+
+/// Upper estimate of the on-disk size of one recorded sample
+const BYTES_PER_SAMPLE: u64 = 1024;
+
+/// Free disk space is checked once every this many samples
+const DISK_CHECK_INTERVAL: usize = 100;
+
+/// Buffers telemetry for a session and stops recording before the disk fills
+pub struct SessionRecorder {
+    points: Vec<TelemetryData>,
+    min_free_bytes: u64,
+    stop_reason: Option<String>,
+}
+
+impl SessionRecorder {
+    pub fn new(policy: &RetentionPolicy) -> Self {
+        Self {
+            points: Vec::new(),
+            min_free_bytes: policy.min_free_bytes,
+            stop_reason: None,
+        }
+    }
+
+    /// Record a sample, returns false once recording has been stopped
+    pub fn record(&mut self, storage: &Storage, data: &TelemetryData) -> bool {
+        if self.stop_reason.is_some() {
+            return false;
+        }
+
+        if self.points.len().is_multiple_of(DISK_CHECK_INTERVAL) {
+            let needed = (self.points.len() + DISK_CHECK_INTERVAL) as u64 * BYTES_PER_SAMPLE;
+            match storage.has_room_for(needed, self.min_free_bytes) {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.stop_reason = Some("Recording stopped: low disk space".to_string());
+                    return false;
+                }
+                Err(e) => {
+                    self.stop_reason = Some(format!("Recording stopped: {}", e));
+                    return false;
+                }
+            }
+        }
+
+        self.points.push(data.clone());
+        true
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.stop_reason.is_none()
+    }
+
+    /// Why recording stopped, if it did
+    pub fn stop_reason(&self) -> Option<&str> {
+        self.stop_reason.as_deref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Save the recorded samples as a session, returns the session id if anything was recorded
+    pub fn finish(self, storage: &Storage) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if self.points.is_empty() {
+            return Ok(None);
+        }
+        storage.save_session(&self.points).map(Some)
+    }
+}
@@ -0,0 +1,129 @@
+use std::fs;
+use std::path::Path;
+use crate::telemetry::TelemetryData;
+use super::compression::CompressionTolerances;
+
+/// Limits applied to sessions, backups, snapshots and logs by `Storage::apply_retention`
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    /// Maximum combined size of sessions with their integrity manifests and
+    /// rollups, backups, snapshots and logs
+    pub max_total_bytes: Option<u64>,
+    /// Files older than this are deleted (pinned sessions excepted)
+    pub max_age_secs: Option<u64>,
+    /// Number of most recent backups to keep
+    pub keep_backups: Option<usize>,
+    /// Number of most recent snapshots to keep; objects only older ones use are removed
+    pub keep_snapshots: Option<usize>,
+    /// Sessions older than this are downsampled instead of kept at full rate
+    pub downsample_after_secs: Option<u64>,
+    /// Interval between samples kept when downsampling
+    pub downsample_interval_secs: u64,
+    /// Sessions older than this are archived with error-bounded compression
+    pub archive_after_secs: Option<u64>,
+    pub archive_tolerances: CompressionTolerances,
+    /// Directory holding log files covered by the policy
+    pub log_dir: Option<String>,
+    /// Recording stops when free disk space would drop below this
+    pub min_free_bytes: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_total_bytes: None,
+            max_age_secs: None,
+            keep_backups: None,
+            keep_snapshots: None,
+            downsample_after_secs: None,
+            downsample_interval_secs: 10,
+            archive_after_secs: None,
+            archive_tolerances: CompressionTolerances::default(),
+            log_dir: None,
+            min_free_bytes: 50 * 1024 * 1024,
+        }
+    }
+}
+
+/// What a retention run changed
+#[derive(Debug, Clone, Default)]
+pub struct RetentionReport {
+    pub deleted_sessions: Vec<String>,
+    pub downsampled_sessions: Vec<String>,
+    pub archived_sessions: Vec<String>,
+    pub deleted_backups: Vec<String>,
+    pub deleted_logs: Vec<String>,
+    pub deleted_snapshots: usize,
+    /// Sessions that couldn't be read or aged, with the error; they are left as they are
+    pub skipped_sessions: Vec<(String, String)>,
+    pub bytes_freed: u64,
+    pub total_bytes: u64,
+}
+
+/// Keep the first sample of every `interval_secs` window
+pub fn downsample_session(data_points: &[TelemetryData], interval_secs: u64) -> Vec<TelemetryData> {
+    let interval = interval_secs.max(1);
+    let mut result: Vec<TelemetryData> = Vec::new();
+    for point in data_points {
+        let keep = match result.last() {
+            Some(last) => point.timestamp >= last.timestamp + interval,
+            None => true,
+        };
+        if keep {
+            result.push(point.clone());
+        }
+    }
+    // Always keep the final sample so the session's end time is preserved
+    if let (Some(last), Some(kept)) = (data_points.last(), result.last()) {
+        if last.timestamp != kept.timestamp {
+            result.push(last.clone());
+        }
+    }
+    result
+}
+
+/// Free space in bytes on the filesystem holding `path`
+pub fn available_space(path: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    let path = Path::new(path);
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Ok(fs2::available_space(dir)?)
+}
+
+/// A file covered by the retention policy
+#[derive(Debug, Clone)]
+pub struct StoredFile {
+    pub path: String,
+    pub size: u64,
+    /// Modification time in unix seconds
+    pub modified: u64,
+}
+
+/// Files in a directory, oldest first
+pub fn list_files(dir: &str) -> Result<Vec<StoredFile>, Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    if !Path::new(dir).exists() {
+        return Ok(files);
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        files.push(StoredFile {
+            path: entry.path().to_string_lossy().to_string(),
+            size: metadata.len(),
+            modified,
+        });
+    }
+    files.sort_by_key(|f| f.modified);
+    Ok(files)
+}
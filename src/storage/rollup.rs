@@ -0,0 +1,210 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use crate::telemetry::{TelemetryData, CHANNELS};
+
+/// Bucket sizes, in seconds, of the overview tiers kept for each session
+pub const ROLLUP_RESOLUTIONS: &[u64] = &[1, 10, 60];
+
+/// Aggregate of one channel over one time bucket
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct RollupBucket {
+    /// Bucket start in unix seconds
+    pub start: u64,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub count: usize,
+}
+
+/// All channels aggregated at one resolution, stored as `TierColumns`
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(try_from = "TierColumns")]
+pub struct RollupTier {
+    pub resolution_secs: u64,
+    pub channels: BTreeMap<String, Vec<RollupBucket>>,
+}
+
+/// Buckets of one channel as parallel columns. Bucket starts are kept as
+/// steps from the previous bucket, in multiples of the resolution, so a
+/// gapless channel stores a run of ones.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BucketColumns {
+    pub first_start: u64,
+    pub steps: Vec<u64>,
+    pub min: Vec<f64>,
+    pub max: Vec<f64>,
+    pub avg: Vec<f64>,
+    pub count: Vec<usize>,
+}
+
+/// On-disk form of a tier
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TierColumns {
+    pub resolution_secs: u64,
+    pub channels: BTreeMap<String, BucketColumns>,
+}
+
+impl TryFrom<RollupTier> for TierColumns {
+    type Error = String;
+
+    fn try_from(tier: RollupTier) -> Result<Self, String> {
+        let resolution = tier.resolution_secs.max(1);
+        let mut channels = BTreeMap::new();
+        for (name, buckets) in tier.channels {
+            let mut columns = BucketColumns { first_start: buckets.first().map(|b| b.start).unwrap_or(0), ..Default::default() };
+            let mut previous = columns.first_start;
+            for bucket in &buckets {
+                let step = bucket.start.checked_sub(previous)
+                    .ok_or_else(|| format!("rollup buckets of '{}' aren't in time order", name))?;
+                columns.steps.push(step / resolution);
+                previous = bucket.start;
+                columns.min.push(bucket.min);
+                columns.max.push(bucket.max);
+                columns.avg.push(bucket.avg);
+                columns.count.push(bucket.count);
+            }
+            channels.insert(name, columns);
+        }
+        Ok(TierColumns { resolution_secs: resolution, channels })
+    }
+}
+
+impl Serialize for RollupTier {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        TierColumns::try_from(self.clone()).map_err(serde::ser::Error::custom)?.serialize(serializer)
+    }
+}
+
+impl TryFrom<TierColumns> for RollupTier {
+    type Error = String;
+
+    fn try_from(tier: TierColumns) -> Result<Self, String> {
+        let resolution = tier.resolution_secs.max(1);
+        let mut channels = BTreeMap::new();
+        for (name, columns) in tier.channels {
+            let len = columns.steps.len();
+            if [columns.min.len(), columns.max.len(), columns.avg.len(), columns.count.len()].iter().any(|l| *l != len) {
+                return Err(format!("rollup columns of '{}' have different lengths", name));
+            }
+            let mut start = columns.first_start;
+            let mut buckets = Vec::with_capacity(len);
+            for i in 0..len {
+                start = columns.steps[i].checked_mul(resolution).and_then(|step| start.checked_add(step))
+                    .ok_or_else(|| format!("rollup bucket starts of '{}' overflow", name))?;
+                buckets.push(RollupBucket { start, min: columns.min[i], max: columns.max[i], avg: columns.avg[i], count: columns.count[i] });
+            }
+            channels.insert(name, buckets);
+        }
+        Ok(RollupTier { resolution_secs: resolution, channels })
+    }
+}
+
+/// One tier as written to its own file next to the session
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TierFile {
+    pub start_time: u64,
+    pub end_time: u64,
+    pub tier: RollupTier,
+}
+
+/// Overview tiers kept for a session, one file each
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionRollups {
+    pub start_time: u64,
+    pub end_time: u64,
+    pub tiers: Vec<RollupTier>,
+}
+
+/// Buckets of one channel returned by a range query
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollupSeries {
+    pub channel: String,
+    pub resolution_secs: u64,
+    pub buckets: Vec<RollupBucket>,
+}
+
+/// Aggregate every channel of a session at one resolution
+pub fn build_tier(data_points: &[TelemetryData], resolution_secs: u64) -> RollupTier {
+    let resolution = resolution_secs.max(1);
+    let mut channels = BTreeMap::new();
+
+    let extra = TelemetryData::extra_channel_names(data_points);
+    let names = CHANNELS.iter().map(|c| c.name).chain(extra.iter().map(|n| n.as_str()));
+    for name in names {
+        // Keyed by start so samples whose timestamps go backwards still
+        // land in their own bucket and the tier comes out in time order
+        let mut sums: BTreeMap<u64, (RollupBucket, f64)> = BTreeMap::new();
+        for point in data_points {
+            let Some(value) = point.channel_value(name) else {
+                continue;
+            };
+            let start = point.timestamp - point.timestamp % resolution;
+            let (bucket, sum) = sums
+                .entry(start)
+                .or_insert((RollupBucket { start, min: value, max: value, avg: 0.0, count: 0 }, 0.0));
+            bucket.min = bucket.min.min(value);
+            bucket.max = bucket.max.max(value);
+            bucket.count += 1;
+            *sum += value;
+            bucket.avg = *sum / bucket.count as f64;
+        }
+        let buckets: Vec<RollupBucket> = sums.into_values().map(|(bucket, _)| bucket).collect();
+        if !buckets.is_empty() {
+            channels.insert(name.to_string(), buckets);
+        }
+    }
+
+    RollupTier { resolution_secs: resolution, channels }
+}
+
+/// Build all overview tiers for a session
+pub fn build_rollups(data_points: &[TelemetryData]) -> SessionRollups {
+    SessionRollups {
+        start_time: data_points.iter().map(|p| p.timestamp).min().unwrap_or(0),
+        end_time: data_points.iter().map(|p| p.timestamp).max().unwrap_or(0),
+        tiers: ROLLUP_RESOLUTIONS.iter().map(|r| build_tier(data_points, *r)).collect(),
+    }
+}
+
+/// Pick the coarsest resolution that still gives at least one bucket per
+/// pixel for the requested span, falling back to the finest one
+pub fn select_resolution(resolutions: &[u64], span_secs: u64, pixel_width: u32) -> Option<u64> {
+    let secs_per_pixel = span_secs as f64 / pixel_width.max(1) as f64;
+    resolutions
+        .iter()
+        .copied()
+        .filter(|r| *r as f64 <= secs_per_pixel)
+        .max()
+        .or_else(|| resolutions.iter().copied().min())
+}
+
+impl RollupTier {
+    /// Buckets of a channel between `from` and `to` (unix seconds, inclusive)
+    pub fn query(&self, channel: &str, from: u64, to: u64) -> Option<RollupSeries> {
+        let buckets = self.channels.get(channel)?;
+        Some(RollupSeries {
+            channel: channel.to_string(),
+            resolution_secs: self.resolution_secs,
+            buckets: buckets
+                .iter()
+                .filter(|b| b.start + self.resolution_secs > from && b.start <= to)
+                .copied()
+                .collect(),
+        })
+    }
+}
+
+impl SessionRollups {
+    /// See `select_resolution`
+    pub fn select_tier(&self, span_secs: u64, pixel_width: u32) -> Option<&RollupTier> {
+        let resolutions: Vec<u64> = self.tiers.iter().map(|t| t.resolution_secs).collect();
+        let resolution = select_resolution(&resolutions, span_secs, pixel_width)?;
+        self.tiers.iter().find(|t| t.resolution_secs == resolution)
+    }
+
+    /// Buckets of a channel between `from` and `to` (unix seconds, inclusive)
+    /// at the tier suited to drawing that span across `pixel_width` pixels
+    pub fn query(&self, channel: &str, from: u64, to: u64, pixel_width: u32) -> Option<RollupSeries> {
+        self.select_tier(to.saturating_sub(from), pixel_width)?.query(channel, from, to)
+    }
+}
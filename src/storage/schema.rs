@@ -0,0 +1,178 @@
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{json, Value};
+
+/// Schema version written into every persisted file.
+///
+/// Version history:
+/// 1. Bare JSON with no version information
+/// 2. Files wrapped in a `{ "schema_version", "kind", "data" }` envelope
+/// 3. The `brake_temperature` channel renamed to `brake_temp`, like `engine_temp`
+pub const CURRENT_SCHEMA_VERSION: u32 = 3;
+
+/// Kinds of persisted files, used to pick the right migration steps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileKind {
+    /// A single `TelemetryData` record (the main data file and its backups)
+    Telemetry,
+    /// An array of `TelemetryData` records
+    Session,
+    Catalog,
+    IntegrityManifest,
+    PluginConfigs,
+    Snapshot,
+    Rollups,
+    /// A session stored with lossy per-channel compression
+    CompressedSession,
+    /// Configuration and sessions exported for another head unit
+    Bundle,
+    /// A plugin packaged for installation
+    PluginArchive,
+    /// Index of the plugins installed from archives
+    PluginRegistry,
+    /// Permissions the user allowed or denied plugins
+    PluginPermissions,
+    /// Key-value state plugins keep across restarts
+    PluginState,
+}
+
+impl FileKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileKind::Telemetry => "telemetry",
+            FileKind::Session => "session",
+            FileKind::Catalog => "catalog",
+            FileKind::IntegrityManifest => "integrity_manifest",
+            FileKind::PluginConfigs => "plugin_configs",
+            FileKind::Snapshot => "snapshot",
+            FileKind::Rollups => "rollups",
+            FileKind::CompressedSession => "compressed_session",
+            FileKind::Bundle => "bundle",
+            FileKind::PluginArchive => "plugin_archive",
+            FileKind::PluginRegistry => "plugin_registry",
+            FileKind::PluginPermissions => "plugin_permissions",
+            FileKind::PluginState => "plugin_state",
+        }
+    }
+}
+
+/// A migration step upgrades the `data` of a file from version N to N + 1
+type MigrationStep = fn(FileKind, Value) -> Result<Value, String>;
+
+/// Migration steps in order; `MIGRATIONS[i]` upgrades version `i + 1` to `i + 2`
+const MIGRATIONS: &[MigrationStep] = &[migrate_v1_to_v2, migrate_v2_to_v3];
+
+/// Version 2 only introduced the envelope, the data itself is unchanged
+fn migrate_v1_to_v2(_kind: FileKind, data: Value) -> Result<Value, String> {
+    Ok(data)
+}
+
+/// Rename the `brake_temperature` channel wherever a file stores channels
+fn migrate_v2_to_v3(kind: FileKind, mut data: Value) -> Result<Value, String> {
+    const OLD: &str = "brake_temperature";
+    const NEW: &str = "brake_temp";
+    fn rename_key(value: &mut Value) {
+        if let Some(map) = value.as_object_mut() {
+            if let Some(v) = map.remove(OLD) {
+                map.insert(NEW.to_string(), v);
+            }
+        }
+    }
+    match kind {
+        FileKind::Telemetry => rename_key(&mut data),
+        FileKind::Session => data.as_array_mut().into_iter().flatten().for_each(rename_key),
+        FileKind::CompressedSession => {
+            rename_key(&mut data["tolerances"]["per_channel"]);
+            for channel in data["channels"].as_array_mut().into_iter().flatten() {
+                if channel["name"] == OLD {
+                    channel["name"] = Value::from(NEW);
+                }
+            }
+        }
+        FileKind::Rollups => rename_key(&mut data["tier"]["channels"]),
+        _ => {}
+    }
+    Ok(data)
+}
+
+/// Split a parsed file into its schema version and data
+pub fn detect_version(value: Value) -> (u32, Value) {
+    if let Value::Object(map) = &value {
+        if let (Some(version), Some(_)) = (map.get("schema_version"), map.get("data")) {
+            let version = version.as_u64().unwrap_or(1) as u32;
+            if let Value::Object(mut map) = value {
+                return (version, map.remove("data").unwrap_or(Value::Null));
+            }
+        }
+    }
+    (1, value)
+}
+
+/// Upgrade data from `version` to the current schema version
+pub fn migrate(kind: FileKind, version: u32, data: Value) -> Result<Value, String> {
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "{} file has schema version {}, newer than supported version {}",
+            kind.name(),
+            version,
+            CURRENT_SCHEMA_VERSION
+        ));
+    }
+    let mut data = data;
+    for step in MIGRATIONS.iter().skip(version.saturating_sub(1) as usize) {
+        data = step(kind, data)?;
+    }
+    Ok(data)
+}
+
+/// Read the `kind` recorded in a file's envelope, `None` for unversioned files
+pub fn peek_kind(content: &[u8]) -> Option<String> {
+    #[derive(serde::Deserialize)]
+    struct Envelope {
+        kind: Option<String>,
+    }
+    serde_json::from_slice::<Envelope>(content).ok()?.kind
+}
+
+/// Serialize a value wrapped in the current schema envelope
+pub fn encode<T: Serialize>(kind: FileKind, value: &T) -> Result<String, Box<dyn std::error::Error>> {
+    let envelope = json!({
+        "schema_version": CURRENT_SCHEMA_VERSION,
+        "kind": kind.name(),
+        "data": value,
+    });
+    Ok(serde_json::to_string_pretty(&envelope)?)
+}
+
+/// Like `encode`, without whitespace, for large files nobody reads by hand
+pub fn encode_compact<T: Serialize>(kind: FileKind, value: &T) -> Result<String, Box<dyn std::error::Error>> {
+    let envelope = json!({
+        "schema_version": CURRENT_SCHEMA_VERSION,
+        "kind": kind.name(),
+        "data": value,
+    });
+    Ok(serde_json::to_string(&envelope)?)
+}
+
+/// Parse a file of any known schema version, migrating it to the current one
+pub fn decode<T: DeserializeOwned>(kind: FileKind, content: &[u8]) -> Result<T, Box<dyn std::error::Error>> {
+    Ok(decode_with_version(kind, content)?.0)
+}
+
+/// Like `decode`, also returning the version the file was stored in
+pub fn decode_with_version<T: DeserializeOwned>(kind: FileKind, content: &[u8]) -> Result<(T, u32), Box<dyn std::error::Error>> {
+    let value: Value = serde_json::from_slice(content)?;
+    let (version, data) = detect_version(value);
+    let data = migrate(kind, version, data)?;
+    Ok((serde_json::from_value(data)?, version))
+}
+
+/// Outcome of a bulk migration
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    /// Files rewritten in the current schema version
+    pub migrated: Vec<String>,
+    /// Files already in the current schema version
+    pub up_to_date: Vec<String>,
+    /// Files that could not be migrated, with the reason
+    pub failed: Vec<(String, String)>,
+}
@@ -0,0 +1,163 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::plugin::input::KEY_BINDINGS_FILE;
+use crate::plugin::permissions::PERMISSIONS_FILE;
+use super::bundle;
+use super::integrity::sha256_hex;
+
+/// Files outside the storage's own prefix that are included in snapshots,
+/// relative to the data directory: the bundle's config files, permission
+/// decisions, key bindings and the plugin registry
+pub const DEFAULT_EXTRA_FILES: &[&str] = &[
+    bundle::LAYOUT_FILE,
+    bundle::PLUGIN_CONFIGS_FILE,
+    bundle::VEHICLE_PROFILE_FILE,
+    bundle::ALERT_RULES_FILE,
+    bundle::THEME_FILE,
+    PERMISSIONS_FILE,
+    KEY_BINDINGS_FILE,
+    "plugins/registry.json",
+];
+
+/// One file captured by a snapshot
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SnapshotFile {
+    /// Path relative to the data directory
+    pub path: String,
+    /// SHA-256 of the content, also the name of the stored object
+    pub hash: String,
+    pub size: u64,
+}
+
+/// A point-in-time record of the data directory.
+///
+/// Every snapshot lists the complete file set, but file contents are stored
+/// once per distinct hash, so each snapshot only adds the files that changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    /// Creation time in unix milliseconds, also the snapshot id
+    pub timestamp_ms: u64,
+    /// Snapshot this one was taken after, if any
+    pub parent: Option<u64>,
+    pub files: Vec<SnapshotFile>,
+    /// Number of objects this snapshot had to store
+    pub new_objects: usize,
+    pub new_bytes: u64,
+}
+
+impl SnapshotManifest {
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|f| f.size).sum()
+    }
+
+    pub fn file(&self, path: &str) -> Option<&SnapshotFile> {
+        self.files.iter().find(|f| f.path == path)
+    }
+}
+
+/// Differences between two snapshots
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapshotDiff {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Compare two snapshots, reporting what changed going from `base` to `target`
+pub fn diff(base: &SnapshotManifest, target: &SnapshotManifest) -> SnapshotDiff {
+    let base_files: HashMap<&str, &str> = base.files.iter().map(|f| (f.path.as_str(), f.hash.as_str())).collect();
+    let target_files: HashMap<&str, &str> = target.files.iter().map(|f| (f.path.as_str(), f.hash.as_str())).collect();
+
+    let mut result = SnapshotDiff::default();
+    for (path, hash) in &target_files {
+        match base_files.get(path) {
+            None => result.added.push(path.to_string()),
+            Some(base_hash) if base_hash != hash => result.modified.push(path.to_string()),
+            _ => {}
+        }
+    }
+    for path in base_files.keys() {
+        if !target_files.contains_key(path) {
+            result.removed.push(path.to_string());
+        }
+    }
+    result.added.sort();
+    result.modified.sort();
+    result.removed.sort();
+    result
+}
+
+/// Content-addressed store of file contents
+pub struct ObjectStore {
+    pub dir: String,
+}
+
+impl ObjectStore {
+    pub fn new(dir: &str) -> Self {
+        Self { dir: dir.to_string() }
+    }
+
+    pub fn object_path(&self, hash: &str) -> String {
+        format!("{}/{}", self.dir, hash)
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        Path::new(&self.object_path(hash)).exists()
+    }
+
+    /// Store content, returns its hash and whether it was newly written
+    pub fn put(&self, content: &[u8]) -> Result<(String, bool), Box<dyn std::error::Error>> {
+        let hash = sha256_hex(content);
+        if self.contains(&hash) {
+            return Ok((hash, false));
+        }
+        fs::create_dir_all(&self.dir)?;
+        // Write to a temporary name first so a crash never leaves a truncated object
+        let temp_path = format!("{}.tmp", self.object_path(&hash));
+        fs::write(&temp_path, content)?;
+        fs::rename(&temp_path, self.object_path(&hash))?;
+        Ok((hash, true))
+    }
+
+    pub fn get(&self, hash: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        Ok(fs::read(self.object_path(hash))?)
+    }
+
+    /// Check an object exists and its content still matches its hash
+    pub fn verify(&self, hash: &str) -> Result<(), String> {
+        match fs::read(self.object_path(hash)) {
+            Ok(content) if sha256_hex(&content) == hash => Ok(()),
+            Ok(_) => Err(format!("Object {} is corrupted", hash)),
+            Err(e) => Err(format!("Object {} is missing: {}", hash, e)),
+        }
+    }
+
+    /// Remove objects not referenced by any snapshot, returns the number removed
+    pub fn garbage_collect(&self, snapshots: &[SnapshotManifest]) -> Result<usize, Box<dyn std::error::Error>> {
+        if !Path::new(&self.dir).exists() {
+            return Ok(0);
+        }
+        let referenced: std::collections::HashSet<&str> = snapshots
+            .iter()
+            .flat_map(|s| s.files.iter().map(|f| f.hash.as_str()))
+            .collect();
+        let mut removed = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !referenced.contains(name.as_str()) {
+                fs::remove_file(entry.path())?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
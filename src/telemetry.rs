@@ -1,4 +1,5 @@
 use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Default, Serialize, Deserialize, Clone, Debug)]
@@ -17,13 +18,47 @@ pub struct TelemetryData {
     pub longitude: Option<f64>,
     pub altitude: Option<f64>,
     pub acceleration: f64,
-    pub brake_temperature: f64,
+    /// Named `brake_temperature` before schema version 3
+    #[serde(alias = "brake_temperature")]
+    pub brake_temp: f64,
     pub tire_pressure_fl: f64,
     pub tire_pressure_fr: f64,
     pub tire_pressure_rl: f64,
     pub tire_pressure_rr: f64,
+    /// Channels published by plugin data sources, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, f64>,
 }
 
+/// Description of a numeric telemetry channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelInfo {
+    pub name: &'static str,
+    pub unit: &'static str,
+}
+
+/// All numeric channels carried by `TelemetryData`, in export order
+pub const CHANNELS: &[ChannelInfo] = &[
+    ChannelInfo { name: "speed", unit: "mph" },
+    ChannelInfo { name: "rpm", unit: "rpm" },
+    ChannelInfo { name: "engine_temp", unit: "F" },
+    ChannelInfo { name: "fuel_level", unit: "%" },
+    ChannelInfo { name: "battery_voltage", unit: "V" },
+    ChannelInfo { name: "oil_pressure", unit: "psi" },
+    ChannelInfo { name: "throttle_position", unit: "%" },
+    ChannelInfo { name: "brake_pressure", unit: "psi" },
+    ChannelInfo { name: "gear", unit: "" },
+    ChannelInfo { name: "acceleration", unit: "G" },
+    ChannelInfo { name: "brake_temp", unit: "F" },
+    ChannelInfo { name: "tire_pressure_fl", unit: "psi" },
+    ChannelInfo { name: "tire_pressure_fr", unit: "psi" },
+    ChannelInfo { name: "tire_pressure_rl", unit: "psi" },
+    ChannelInfo { name: "tire_pressure_rr", unit: "psi" },
+    ChannelInfo { name: "latitude", unit: "deg" },
+    ChannelInfo { name: "longitude", unit: "deg" },
+    ChannelInfo { name: "altitude", unit: "m" },
+];
+
 impl TelemetryData {
     /// Create a new TelemetryData instance with current timestamp
     pub fn new() -> Self {
@@ -148,6 +183,69 @@ impl TelemetryData {
         self.gear >= -1 && self.gear <= 6
     }
 
+    /// Get a channel value by name, `None` for unknown channels or missing GPS fields.
+    /// Plugin channels in `extra` are looked up too.
+    pub fn channel_value(&self, name: &str) -> Option<f64> {
+        match name {
+            "speed" => Some(self.speed),
+            "rpm" => Some(self.rpm),
+            "engine_temp" => Some(self.engine_temp),
+            "fuel_level" => Some(self.fuel_level),
+            "battery_voltage" => Some(self.battery_voltage),
+            "oil_pressure" => Some(self.oil_pressure),
+            "throttle_position" => Some(self.throttle_position),
+            "brake_pressure" => Some(self.brake_pressure),
+            "gear" => Some(self.gear as f64),
+            "acceleration" => Some(self.acceleration),
+            "brake_temp" => Some(self.brake_temp),
+            "tire_pressure_fl" => Some(self.tire_pressure_fl),
+            "tire_pressure_fr" => Some(self.tire_pressure_fr),
+            "tire_pressure_rl" => Some(self.tire_pressure_rl),
+            "tire_pressure_rr" => Some(self.tire_pressure_rr),
+            "latitude" => self.latitude,
+            "longitude" => self.longitude,
+            "altitude" => self.altitude,
+            _ => self.extra.get(name).copied(),
+        }
+    }
+    
+    /// Whether a name belongs to a built-in channel
+    pub fn is_builtin_channel(name: &str) -> bool {
+        CHANNELS.iter().any(|c| c.name == name)
+    }
+    
+    /// Names of the plugin channels present in any of the records, sorted
+    pub fn extra_channel_names(data_points: &[TelemetryData]) -> Vec<String> {
+        let names: std::collections::BTreeSet<&String> = data_points.iter().flat_map(|p| p.extra.keys()).collect();
+        names.into_iter().cloned().collect()
+    }
+
+    /// Set a channel value by name, returns false for unknown channels
+    pub fn set_channel_value(&mut self, name: &str, value: f64) -> bool {
+        match name {
+            "speed" => self.speed = value,
+            "rpm" => self.rpm = value,
+            "engine_temp" => self.engine_temp = value,
+            "fuel_level" => self.fuel_level = value,
+            "battery_voltage" => self.battery_voltage = value,
+            "oil_pressure" => self.oil_pressure = value,
+            "throttle_position" => self.throttle_position = value,
+            "brake_pressure" => self.brake_pressure = value,
+            "gear" => self.gear = value.round() as i32,
+            "acceleration" => self.acceleration = value,
+            "brake_temp" => self.brake_temp = value,
+            "tire_pressure_fl" => self.tire_pressure_fl = value,
+            "tire_pressure_fr" => self.tire_pressure_fr = value,
+            "tire_pressure_rl" => self.tire_pressure_rl = value,
+            "tire_pressure_rr" => self.tire_pressure_rr = value,
+            "latitude" => self.latitude = Some(value),
+            "longitude" => self.longitude = Some(value),
+            "altitude" => self.altitude = Some(value),
+            _ => return false,
+        }
+        true
+    }
+
     /// Create a summary of critical alerts
     pub fn get_alerts(&self) -> Vec<String> {
         let mut alerts = Vec::new();
@@ -213,11 +311,19 @@ pub mod utils {
             longitude: interpolate_option(start.longitude, end.longitude, factor),
             altitude: interpolate_option(start.altitude, end.altitude, factor),
             acceleration: start.acceleration + (end.acceleration - start.acceleration) * factor,
-            brake_temperature: start.brake_temperature + (end.brake_temperature - start.brake_temperature) * factor,
+            brake_temp: start.brake_temp + (end.brake_temp - start.brake_temp) * factor,
             tire_pressure_fl: start.tire_pressure_fl + (end.tire_pressure_fl - start.tire_pressure_fl) * factor,
             tire_pressure_fr: start.tire_pressure_fr + (end.tire_pressure_fr - start.tire_pressure_fr) * factor,
             tire_pressure_rl: start.tire_pressure_rl + (end.tire_pressure_rl - start.tire_pressure_rl) * factor,
             tire_pressure_rr: start.tire_pressure_rr + (end.tire_pressure_rr - start.tire_pressure_rr) * factor,
+            extra: start
+                .extra
+                .iter()
+                .map(|(name, s)| {
+                    let value = interpolate_option(Some(*s), end.extra.get(name).copied(), factor).unwrap_or(*s);
+                    (name.clone(), value)
+                })
+                .collect(),
         }
     }
 
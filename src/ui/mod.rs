@@ -66,4 +66,7 @@ impl Dashboard {
     }
 }
 
+pub mod plugin_profiler;
+pub mod plugin_settings;
+pub mod sessions;
 pub mod widgets; 
\ No newline at end of file
@@ -0,0 +1,67 @@
+use egui::{Color32, RichText, Ui};
+use std::time::Duration;
+use crate::plugin::PluginManager;
+use crate::plugin::profiler::{CallSummary, FRAME_BUDGET};
+
+fn millis(duration: Duration) -> String {
+    format!("{:.2}", duration.as_secs_f64() * 1000.0)
+}
+
+fn megabytes(bytes: usize) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Timings of one plugin call type, copied out of the manager
+pub struct ProfileRow {
+    pub plugin: String,
+    pub call: &'static str,
+    pub summary: CallSummary,
+    /// Linear memory of a WebAssembly plugin, `None` for other plugins
+    pub memory: Option<usize>,
+}
+
+/// Copy what the overlay shows, so the manager is only locked briefly
+pub fn snapshot(manager: &PluginManager) -> Vec<ProfileRow> {
+    let mut rows = Vec::new();
+    for metadata in manager.get_all_metadata() {
+        let Some(profile) = manager.plugin_profile(&metadata.name) else {
+            continue;
+        };
+        let memory = manager.plugin_memory(&metadata.name);
+        for call in profile.calls.keys() {
+            if let Some(summary) = profile.summary(call) {
+                rows.push(ProfileRow { plugin: metadata.name.clone(), call, summary, memory });
+            }
+        }
+    }
+    rows
+}
+
+/// Developer overlay with each plugin's call timings, and memory use of
+/// WebAssembly plugins: native allocations aren't tracked.
+/// Calls whose p99 alone exceeds the frame budget are shown in red.
+pub fn show(ui: &mut Ui, rows: &[ProfileRow]) {
+    ui.label(format!("Times in ms over the last calls; frame budget {} ms", millis(FRAME_BUDGET)));
+    ui.label("Memory is only measured for WebAssembly plugins");
+    egui::Grid::new("plugin_profiler").num_columns(7).striped(true).show(ui, |ui| {
+        for heading in ["plugin", "call", "min", "avg", "p99", "max", "WASM memory"] {
+            ui.label(RichText::new(heading).strong());
+        }
+        ui.end_row();
+
+        for (index, row) in rows.iter().enumerate() {
+            // Name and memory only on a plugin's first row
+            let first = index == 0 || rows[index - 1].plugin != row.plugin;
+            let summary = &row.summary;
+            ui.label(if first { row.plugin.as_str() } else { "" });
+            ui.label(row.call);
+            ui.label(millis(summary.min));
+            ui.label(millis(summary.avg));
+            let p99 = RichText::new(millis(summary.p99));
+            ui.label(if summary.p99 > FRAME_BUDGET { p99.color(Color32::RED) } else { p99 });
+            ui.label(millis(summary.max));
+            ui.label(if first { row.memory.map(megabytes).unwrap_or_else(|| "n/a".to_string()) } else { String::new() });
+            ui.end_row();
+        }
+    });
+}
@@ -0,0 +1,267 @@
+use egui::{Color32, RichText, Ui};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::plugin::{utils, PluginManager, PluginSetting, SettingType};
+use crate::plugin::schedule::lock_within;
+use crate::plugin::settings;
+
+/// Time without edits after which changed configs are written to disk
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// Folder browser opened by a `File` setting's "Browse" button
+struct FilePicker {
+    setting: String,
+    dir: PathBuf,
+}
+
+/// Dashboard panel with an editor for every setting a plugin declares.
+///
+/// Edits are applied to the plugin as soon as the whole config is valid,
+/// and saved shortly after the last change.
+pub struct PluginSettingsPanel {
+    selected: Option<String>,
+    /// Values being edited, by plugin then setting name
+    drafts: HashMap<String, HashMap<String, String>>,
+    picker: Option<FilePicker>,
+    unsaved_since: Option<Instant>,
+    status: Option<String>,
+}
+
+impl PluginSettingsPanel {
+    pub fn new() -> Self {
+        Self {
+            selected: None,
+            drafts: HashMap::new(),
+            picker: None,
+            unsaved_since: None,
+            status: None,
+        }
+    }
+
+    /// Show the editor, locking the manager only while reading from or
+    /// applying to it, and waiting at most `wait` for it each time
+    pub fn show(&mut self, ui: &mut Ui, manager: &Mutex<PluginManager>, wait: Duration) {
+        let Some(plugin_manager) = lock_within(manager, wait) else {
+            ui.colored_label(Color32::YELLOW, "Waiting for plugins");
+            return;
+        };
+        let configurable: Vec<_> = plugin_manager.get_all_metadata().into_iter().filter(|m| !m.settings.is_empty()).collect();
+        let config = self.selected.as_ref().and_then(|name| plugin_manager.get_plugin(name)).map(|p| p.get_config());
+        drop(plugin_manager);
+        if configurable.is_empty() {
+            ui.label("No plugin has settings");
+            return;
+        }
+
+        ui.horizontal_wrapped(|ui| {
+            for metadata in &configurable {
+                let selected = self.selected.as_deref() == Some(metadata.name.as_str());
+                if ui.selectable_label(selected, &metadata.name).clicked() {
+                    self.selected = Some(metadata.name.clone());
+                    self.picker = None;
+                }
+            }
+        });
+        ui.separator();
+
+        let Some(metadata) = configurable.iter().find(|m| Some(&m.name) == self.selected.as_ref()) else {
+            ui.label("Select a plugin to edit its settings");
+            self.save_if_due(manager, wait);
+            return;
+        };
+        let name = metadata.name.clone();
+        let draft = self
+            .drafts
+            .entry(name.clone())
+            .or_insert_with(|| settings::sanitize(&metadata.settings, &config.unwrap_or_default()).0);
+
+        let mut changed = false;
+        egui::Grid::new(("plugin_settings", &name)).num_columns(2).striped(true).show(ui, |ui| {
+            for setting in &metadata.settings {
+                let label = ui.label(if setting.required { format!("{} *", setting.name) } else { setting.name.clone() });
+                if !setting.description.is_empty() {
+                    label.on_hover_text(&setting.description);
+                }
+                ui.vertical(|ui| {
+                    let value = draft.entry(setting.name.clone()).or_insert_with(|| setting.default_value.clone());
+                    changed |= setting_editor(ui, setting, value, &mut self.picker);
+                    if let Err(error) = settings::parse_value(setting, value) {
+                        ui.label(RichText::new(error).color(Color32::RED).small());
+                    }
+                });
+                ui.end_row();
+            }
+        });
+
+        if let Some(picker) = &mut self.picker {
+            if let Some(path) = file_picker(ui, picker) {
+                draft.insert(picker.setting.clone(), path.to_string_lossy().to_string());
+                self.picker = None;
+                changed = true;
+            } else if ui.button("Cancel").clicked() {
+                self.picker = None;
+            }
+        }
+
+        let mut revert = false;
+        ui.horizontal(|ui| {
+            if ui.button("Reset to defaults").clicked() {
+                for setting in &metadata.settings {
+                    draft.insert(setting.name.clone(), setting.default_value.clone());
+                }
+                changed = true;
+            }
+            revert = ui.button("Revert").clicked();
+        });
+        if revert {
+            self.drafts.remove(&name);
+            self.status = None;
+        }
+
+        if changed {
+            let draft = self.drafts.get(&name).cloned().unwrap_or_default();
+            self.status = Some(match utils::validate_config(metadata, &draft) {
+                Ok(()) => match lock_within(manager, wait).map(|mut m| m.set_plugin_config(&name, draft)) {
+                    Some(Ok(())) => {
+                        self.unsaved_since = Some(Instant::now());
+                        "Applied".to_string()
+                    }
+                    Some(Err(errors)) => errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "),
+                    None => "Not applied: plugins are busy".to_string(),
+                },
+                Err(error) => format!("Not applied: {}", error),
+            });
+        }
+        self.save_if_due(manager, wait);
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+
+    /// Write configs once edits have settled, retrying while plugins are busy
+    fn save_if_due(&mut self, manager: &Mutex<PluginManager>, wait: Duration) {
+        if self.unsaved_since.is_some_and(|t| t.elapsed() >= SAVE_DELAY) {
+            let Some(plugin_manager) = lock_within(manager, wait) else {
+                return;
+            };
+            self.unsaved_since = None;
+            if let Err(e) = plugin_manager.save_configs() {
+                self.status = Some(format!("Failed to save settings: {}", e));
+            }
+        }
+    }
+}
+
+/// Editor widget for one setting, returns whether the value changed
+fn setting_editor(ui: &mut Ui, setting: &PluginSetting, value: &mut String, picker: &mut Option<FilePicker>) -> bool {
+    if !setting.choices.is_empty() {
+        let mut changed = false;
+        egui::ComboBox::from_id_source(("setting", &setting.name))
+            .selected_text(value.as_str())
+            .show_ui(ui, |ui| {
+                for choice in &setting.choices {
+                    changed |= ui.selectable_value(value, choice.clone(), choice).changed();
+                }
+            });
+        return changed;
+    }
+
+    match setting.value_type {
+        SettingType::String => ui.text_edit_singleline(value).changed(),
+        SettingType::Integer => match value.trim().parse::<i64>() {
+            Ok(mut number) => {
+                let response = match (setting.min, setting.max) {
+                    (Some(min), Some(max)) => ui.add(egui::Slider::new(&mut number, min as i64..=max as i64)),
+                    _ => ui.add(egui::DragValue::new(&mut number)),
+                };
+                if response.changed() {
+                    *value = number.to_string();
+                }
+                response.changed()
+            }
+            // Let the user fix a value the widget can't show
+            Err(_) => ui.text_edit_singleline(value).changed(),
+        },
+        SettingType::Float => match value.trim().parse::<f64>() {
+            Ok(mut number) => {
+                let response = match (setting.min, setting.max) {
+                    (Some(min), Some(max)) => ui.add(egui::Slider::new(&mut number, min..=max)),
+                    _ => ui.add(egui::DragValue::new(&mut number).speed(0.1)),
+                };
+                if response.changed() {
+                    *value = number.to_string();
+                }
+                response.changed()
+            }
+            Err(_) => ui.text_edit_singleline(value).changed(),
+        },
+        SettingType::Boolean => {
+            let mut checked = value.trim() == "true";
+            let changed = ui.checkbox(&mut checked, "").changed();
+            if changed || value.trim().parse::<bool>().is_err() {
+                *value = checked.to_string();
+            }
+            changed
+        }
+        SettingType::Color => {
+            let mut color = settings::parse_color(value.trim()).unwrap_or(Color32::WHITE);
+            let changed = ui.color_edit_button_srgba(&mut color).changed();
+            if changed {
+                *value = settings::format_color(color);
+            }
+            changed
+        }
+        SettingType::File => {
+            ui.horizontal(|ui| {
+                let changed = ui.text_edit_singleline(value).changed();
+                if ui.button("Browse…").clicked() {
+                    let current = PathBuf::from(value.as_str());
+                    let dir = current
+                        .parent()
+                        .filter(|d| d.is_dir())
+                        .map(|d| d.to_path_buf())
+                        .unwrap_or_else(|| PathBuf::from("."));
+                    *picker = Some(FilePicker { setting: setting.name.clone(), dir });
+                }
+                changed
+            })
+            .inner
+        }
+    }
+}
+
+/// Folder listing for picking a file, returns the chosen file
+fn file_picker(ui: &mut Ui, picker: &mut FilePicker) -> Option<PathBuf> {
+    ui.separator();
+    ui.label(format!("Choose a file for '{}' in {}", picker.setting, picker.dir.display()));
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(&picker.dir)
+        .map(|dir| dir.filter_map(|e| e.ok().map(|e| e.path())).collect())
+        .unwrap_or_default();
+    // Folders first, then files, each by name
+    entries.sort_by_key(|p| (!p.is_dir(), p.file_name().map(|n| n.to_os_string())));
+
+    let mut chosen = None;
+    let mut open_dir = None;
+    egui::ScrollArea::vertical().max_height(160.0).show(ui, |ui| {
+        if ui.selectable_label(false, "..").clicked() {
+            open_dir = Some(picker.dir.join(".."));
+        }
+        for path in &entries {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            if path.is_dir() {
+                if ui.selectable_label(false, format!("{}/", name)).clicked() {
+                    open_dir = Some(path.clone());
+                }
+            } else if ui.selectable_label(false, name).clicked() {
+                chosen = Some(path.clone());
+            }
+        }
+    });
+    if let Some(dir) = open_dir {
+        picker.dir = dir;
+    }
+    chosen
+}
@@ -0,0 +1,145 @@
+use egui::{Color32, RichText, Ui};
+use crate::storage::Storage;
+use crate::storage::catalog::{SessionFilter, SessionInfo, SessionMetadata};
+use crate::storage::export::format_iso8601;
+
+/// Dashboard panel listing recorded sessions with search and editing
+pub struct SessionCatalogPanel {
+    search: String,
+    tag: String,
+    sessions: Vec<SessionInfo>,
+    /// Sessions that couldn't be read, with the reason
+    unreadable: Vec<(String, String)>,
+    selected: Option<String>,
+    editing: SessionMetadata,
+    tags_text: String,
+    status: Option<String>,
+    needs_refresh: bool,
+}
+
+impl SessionCatalogPanel {
+    pub fn new() -> Self {
+        Self {
+            search: String::new(),
+            tag: String::new(),
+            sessions: Vec::new(),
+            unreadable: Vec::new(),
+            selected: None,
+            editing: SessionMetadata::default(),
+            tags_text: String::new(),
+            status: None,
+            needs_refresh: true,
+        }
+    }
+
+    /// Reload the session list from storage using the current search fields
+    pub fn refresh(&mut self, storage: &Storage) {
+        let filter = SessionFilter {
+            text: Some(self.search.trim().to_string()).filter(|s| !s.is_empty()),
+            tag: Some(self.tag.trim().to_string()).filter(|s| !s.is_empty()),
+            ..Default::default()
+        };
+        match storage.list_sessions(&filter) {
+            Ok(listing) => {
+                self.sessions = listing.sessions;
+                self.unreadable = listing.failed;
+            }
+            Err(e) => self.status = Some(format!("Failed to list sessions: {}", e)),
+        }
+        self.needs_refresh = false;
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, storage: &Storage) {
+        if self.needs_refresh {
+            self.refresh(storage);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Search:");
+            let search = ui.text_edit_singleline(&mut self.search);
+            ui.label("Tag:");
+            let tag = ui.text_edit_singleline(&mut self.tag);
+            if search.changed() || tag.changed() || ui.button("Refresh").clicked() {
+                self.needs_refresh = true;
+            }
+        });
+        ui.separator();
+
+        egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+            if self.sessions.is_empty() {
+                ui.label("No sessions found");
+            }
+            for session in &self.sessions {
+                let selected = self.selected.as_deref() == Some(session.id.as_str());
+                let summary = &session.summary;
+                let text = format!(
+                    "{}  |  {}  |  {}  |  {:.1} km  |  max {}",
+                    session.display_name(),
+                    format_iso8601(summary.start_time),
+                    console::format_time_hours(summary.duration_secs() as f64 / 3600.0),
+                    summary.distance_meters / 1000.0,
+                    console::format_speed(summary.max_speed, true),
+                );
+                if ui.selectable_label(selected, text).clicked() {
+                    self.selected = Some(session.id.clone());
+                    self.editing = session.metadata.clone();
+                    self.tags_text = session.metadata.tags.join(", ");
+                }
+            }
+            for (id, error) in &self.unreadable {
+                ui.colored_label(Color32::RED, format!("Session {} can't be read: {}", id, error));
+            }
+        });
+
+        if let Some(id) = self.selected.clone() {
+            ui.separator();
+            egui::Grid::new("session_metadata").num_columns(2).show(ui, |ui| {
+                ui.label("Name");
+                ui.text_edit_singleline(&mut self.editing.name);
+                ui.end_row();
+                ui.label("Driver");
+                ui.text_edit_singleline(&mut self.editing.driver);
+                ui.end_row();
+                ui.label("Vehicle");
+                ui.text_edit_singleline(&mut self.editing.vehicle_profile);
+                ui.end_row();
+                ui.label("Tags");
+                ui.text_edit_singleline(&mut self.tags_text);
+                ui.end_row();
+                ui.label("Notes");
+                ui.text_edit_multiline(&mut self.editing.notes);
+                ui.end_row();
+                ui.label("Pinned");
+                ui.checkbox(&mut self.editing.pinned, "Keep when cleaning up storage");
+                ui.end_row();
+            });
+
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    self.editing.tags = self.tags_text
+                        .split(',')
+                        .map(|t| t.trim().to_string())
+                        .filter(|t| !t.is_empty())
+                        .collect();
+                    self.status = Some(match storage.update_session_metadata(&id, self.editing.clone()) {
+                        Ok(()) => "Session saved".to_string(),
+                        Err(e) => format!("Failed to save session: {}", e),
+                    });
+                    self.needs_refresh = true;
+                }
+                if ui.button(RichText::new("Delete").color(Color32::RED)).clicked() {
+                    self.status = Some(match storage.delete_session(&id) {
+                        Ok(()) => "Session deleted".to_string(),
+                        Err(e) => format!("Failed to delete session: {}", e),
+                    });
+                    self.selected = None;
+                    self.needs_refresh = true;
+                }
+            });
+        }
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
@@ -39,8 +39,7 @@ impl Plugin for MockPlugin {
             author: "Test Author".to_string(),
             description: "A mock plugin for testing".to_string(),
             category: PluginCategory::Speedometer,
-            dependencies: vec![],
-            settings: vec![],
+            ..PluginMetadata::default()
         }
     }
 }
@@ -342,6 +341,7 @@ fn test_plugin_utils() {
         default_value: "default".to_string(),
         description: "Test setting".to_string(),
         required: true,
+        ..PluginSetting::default()
     });
     
     let mut config = std::collections::HashMap::new();
@@ -386,4 +386,2225 @@ fn test_plugin_loading() {
     
     assert!(plugin_manager.disable_plugin("Mock Plugin"), "Should disable plugin");
     assert!(!plugin_manager.get_enabled_plugins().contains(&"Mock Plugin".to_string()));
-} 
\ No newline at end of file
+} 
+#[test]
+fn test_gpx_kml_export() {
+    use console::storage::export;
+    
+    // Build a short track with one point lacking a GPS fix
+    let mut data_points = Vec::new();
+    for i in 0..5 {
+        let mut point = TelemetryData::new();
+        point.timestamp = 1_700_000_000 + i;
+        point.speed = 20.0 * i as f64;
+        point.rpm = 2000.0 + 500.0 * i as f64;
+        point.engine_temp = 190.0;
+        if i != 2 {
+            point.latitude = Some(40.0 + 0.001 * i as f64);
+            point.longitude = Some(-74.0);
+        }
+        data_points.push(point);
+    }
+    
+    // Test GPX export
+    let gpx = export::to_gpx("Test <drive>", &data_points).expect("GPX export should succeed");
+    assert_eq!(gpx.matches("<trkpt").count(), 4, "Only points with GPS fixes should be exported");
+    assert!(gpx.contains("<console:rpm>"), "GPX should carry RPM extensions");
+    assert!(gpx.contains("Test &lt;drive&gt;"), "Track name should be escaped");
+    assert!(gpx.contains("2023-11-14T22:13:20Z"), "Timestamps should be ISO 8601");
+    
+    // Test KML export
+    let kml = export::to_kml("Test drive", &data_points).expect("KML export should succeed");
+    assert!(kml.contains("<LineString>"), "KML should contain a line");
+    assert!(kml.matches("<Placemark>").count() > 1, "Line should be split by speed band");
+    
+    // Test distance and missing GPS handling
+    assert!(export::track_distance(&data_points) > 400.0, "Track distance should be about 444 meters");
+    assert!(export::to_gpx("Empty", &[TelemetryData::new()]).is_err(), "Sessions without GPS should fail");
+}
+
+#[test]
+fn test_motorsport_csv_export() {
+    use console::storage::export::{self, MotorsportExport};
+    
+    // Two samples per second around a small loop that returns to the start
+    let mut data_points = Vec::new();
+    for i in 0..40u64 {
+        let mut point = TelemetryData::new();
+        point.timestamp = 1_700_000_000 + i / 2;
+        point.speed = 60.0;
+        point.rpm = 5000.0;
+        let angle = (i % 20) as f64 / 20.0 * std::f64::consts::TAU;
+        point.latitude = Some(40.0 + 0.002 * angle.sin());
+        point.longitude = Some(-74.0 + 0.002 * (1.0 - angle.cos()));
+        data_points.push(point);
+    }
+    assert_eq!(export::estimate_sample_rate(&data_points), 2, "Sample rate should be 2 Hz");
+    
+    // Test beacon detection from the start/finish line
+    let beacons = export::detect_beacons(&data_points, 40.0, -74.0, 30.0, 5.0);
+    assert_eq!(beacons, vec![10.0], "One lap should be detected after 10 seconds");
+    
+    let options = MotorsportExport {
+        venue: "Test Track".to_string(),
+        vehicle: "Prelude".to_string(),
+        driver: "Driver".to_string(),
+        comment: String::new(),
+        beacons,
+    };
+    
+    // Test MoTeC export
+    let motec = export::to_motec_csv(&options, &data_points);
+    assert!(motec.starts_with("\"Format\",\"MoTeC CSV File\""), "MoTeC header should come first");
+    assert!(motec.contains("\"Sample Rate\",\"2\",\"Hz\""), "MoTeC header should carry the sample rate");
+    assert!(motec.contains("\"Beacon Markers\",\"10.000\""), "MoTeC header should carry beacons");
+    assert!(motec.contains("\"Time\",\"speed\",\"rpm\""), "MoTeC should list channel names");
+    assert!(motec.contains("\"s\",\"mph\",\"rpm\""), "MoTeC should list channel units");
+    
+    // Test AiM export
+    let aim = export::to_aim_csv(&options, &data_points);
+    assert!(aim.starts_with("\"Format\",\"AiM CSV File\""), "AiM header should come first");
+    assert!(aim.contains("\"Segment Times\",\"0:10.000\""), "AiM should list lap times");
+    assert_eq!(aim.lines().filter(|l| l.starts_with("\"19.500\"")).count(), 1, "Last sample should be at 19.5 s");
+    
+    // Test gaps keep their real time, missing values stay empty and plugin channels are exported
+    let mut sparse = vec![data_points[0].clone(), data_points[1].clone(), data_points[2].clone()];
+    sparse[1].latitude = None;
+    sparse[2].timestamp += 4;
+    sparse[2].extra.insert("afr".to_string(), 14.7);
+    assert_eq!(export::sample_times(&sparse), vec![0.0, 0.5, 5.0], "Times should follow the timestamps");
+    let motec = export::to_motec_csv(&MotorsportExport::default(), &sparse);
+    let lines: Vec<&str> = motec.lines().collect();
+    let header: Vec<&str> = lines.iter().find(|l| l.starts_with("\"Time\"")).unwrap().split(',').collect();
+    assert_eq!(header.last(), Some(&"\"afr\""), "Plugin channels should follow the built-in ones");
+    let latitude = header.iter().position(|c| *c == "\"latitude\"").unwrap();
+    let row = |time: &str| lines.iter().find(|l| l.starts_with(time)).unwrap_or_else(|| panic!("No row at {}", time)).split(',').collect::<Vec<_>>();
+    assert_eq!(row("\"0.500\"")[latitude], "", "Missing GPS should be left empty, not written as 0");
+    assert_eq!(row("\"0.500\"").last(), Some(&""), "Samples without a plugin channel should leave it empty");
+    assert_eq!(row("\"5.000\"").last(), Some(&"\"14.7\""));
+    assert!(motec.contains("\"End Time\",\"5.000\""), "End time should be the last sample's time");
+}
+
+#[test]
+fn test_session_catalog() {
+    use console::storage::catalog::{SessionFilter, SessionMetadata};
+    
+    let test_file = "test_catalog.json";
+    let storage = Storage::new(test_file);
+    
+    // Write two sessions with known ids
+    for (id, speed) in [("1000", 40.0), ("2000", 90.0)] {
+        let mut points = vec![TelemetryData::new(), TelemetryData::new()];
+        points[0].timestamp = id.parse().unwrap();
+        points[1].timestamp = points[0].timestamp + 600;
+        points[1].speed = speed;
+        fs::write(storage.session_path(id), serde_json::to_string(&points).unwrap()).unwrap();
+    }
+    
+    // Test listing, newest first with computed summaries, skipping a damaged session
+    fs::write(storage.session_path("3000"), "[{\"speed\": ").unwrap();
+    let listing = storage.list_sessions(&SessionFilter::default()).expect("A damaged session should not fail the list");
+    assert_eq!(listing.failed.len(), 1, "Damaged session should be reported");
+    assert_eq!(listing.failed[0].0, "3000");
+    let sessions = listing.sessions;
+    assert_eq!(sessions.len(), 2, "Should list both readable sessions");
+    assert_eq!(sessions[0].id, "2000", "Newest session should come first");
+    assert_eq!(sessions[0].summary.duration_secs(), 600, "Duration should be 600 s");
+    assert_eq!(sessions[0].summary.max_speed, 90.0, "Max speed should be 90");
+    
+    // Test metadata and search
+    storage.update_session_metadata("1000", SessionMetadata {
+        driver: "Alice".to_string(),
+        tags: vec!["track-day".to_string()],
+        notes: "Wet conditions".to_string(),
+        ..Default::default()
+    }).expect("Failed to update metadata");
+    storage.rename_session("1000", "Morning run").expect("Failed to rename session");
+    
+    let filter = SessionFilter { tag: Some("TRACK-DAY".to_string()), ..Default::default() };
+    let found = storage.list_sessions(&filter).unwrap().sessions;
+    assert_eq!(found.len(), 1, "Tag filter should match one session");
+    assert_eq!(found[0].display_name(), "Morning run", "Rename should update the display name");
+    
+    let filter = SessionFilter { text: Some("wet".to_string()), ..Default::default() };
+    assert_eq!(storage.list_sessions(&filter).unwrap().sessions.len(), 1, "Text search should match notes");
+    
+    // Test delete
+    storage.delete_session("3000").expect("Failed to delete damaged session");
+    storage.delete_session("2000").expect("Failed to delete session");
+    assert_eq!(storage.list_session_ids().unwrap(), vec!["1000".to_string()], "Deleted session should be gone");
+    
+    // Cleanup
+    let _ = storage.delete_session("1000");
+    let _ = fs::remove_file(storage.catalog_path());
+}
+
+#[test]
+fn test_storage_retention() {
+    use console::storage::catalog::SessionMetadata;
+    use console::storage::recorder::SessionRecorder;
+    use console::storage::retention::{self, RetentionPolicy};
+    
+    let test_file = "test_retention.json";
+    let storage = Storage::new(test_file);
+    let now = 100_000;
+    
+    // Three one-hour sessions sampled every second: old, pinned old, and recent
+    for start in [1_000u64, 2_000, 90_000] {
+        let points: Vec<TelemetryData> = (0..3600)
+            .map(|i| TelemetryData { timestamp: start + i, speed: 50.0, ..Default::default() })
+            .collect();
+        fs::write(storage.session_path(&start.to_string()), serde_json::to_string(&points).unwrap()).unwrap();
+    }
+    storage.update_session_metadata("2000", SessionMetadata { pinned: true, ..Default::default() }).unwrap();
+    
+    // Test downsampling keeps the session but shrinks it
+    let policy = RetentionPolicy {
+        downsample_after_secs: Some(50_000),
+        downsample_interval_secs: 60,
+        ..Default::default()
+    };
+    let report = storage.apply_retention_at(&policy, now).expect("Retention should succeed");
+    assert_eq!(report.downsampled_sessions, vec!["1000".to_string()], "Only the old unpinned session should be downsampled");
+    assert_eq!(storage.load_session("1000").unwrap().len(), 61, "Downsampled session should keep one sample per minute");
+    assert!(report.bytes_freed > 0, "Downsampling should free space");
+    
+    // Test max age never touches pinned sessions
+    let policy = RetentionPolicy { max_age_secs: Some(50_000), ..Default::default() };
+    let report = storage.apply_retention_at(&policy, now).unwrap();
+    assert_eq!(report.deleted_sessions, vec!["1000".to_string()], "Old unpinned session should be deleted");
+    
+    // Test the quota removes unpinned sessions until under the limit, past an unreadable one
+    fs::write(storage.session_path("3000"), "damaged").unwrap();
+    storage.seal_session("2000").unwrap();
+    for _ in 0..3 {
+        storage.create_snapshot().unwrap();
+    }
+    let policy = RetentionPolicy { keep_snapshots: Some(2), ..Default::default() };
+    let report = storage.apply_retention_at(&policy, now).unwrap();
+    assert_eq!(report.deleted_snapshots, 1, "Snapshots beyond the two newest should be pruned");
+    let policy = RetentionPolicy { max_total_bytes: Some(1), ..Default::default() };
+    let report = storage.apply_retention_at(&policy, now).expect("An unreadable session shouldn't stop retention");
+    assert_eq!(report.deleted_snapshots, 1, "The quota should prune snapshots down to the newest");
+    assert_eq!(storage.list_snapshots().unwrap().len(), 1);
+    assert_eq!(report.deleted_sessions, vec!["90000".to_string()], "Recent unpinned session should go to meet the quota");
+    assert_eq!(report.skipped_sessions.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(), vec!["3000"], "Unreadable session should be reported");
+    assert_eq!(storage.list_session_ids().unwrap(), vec!["2000".to_string(), "3000".to_string()], "Pinned and unreadable sessions should remain");
+    
+    // Test the quota counts integrity manifests, unreadable sessions and snapshots
+    let size = |path: String| fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let snapshot_bytes: u64 = ["snapshots", "objects"]
+        .iter()
+        .flat_map(|dir| fs::read_dir(format!("{}.backups/{}", test_file, dir)).unwrap())
+        .map(|entry| entry.unwrap().metadata().unwrap().len())
+        .sum();
+    let expected = size(storage.session_path("2000")) + size(storage.integrity_path("2000")) + size(storage.session_path("3000")) + snapshot_bytes;
+    assert_eq!(report.total_bytes, expected, "Everything retention manages should count towards the quota");
+    
+    // Test downsampling helper and the low-disk guard
+    assert_eq!(retention::downsample_session(&[], 10).len(), 0);
+    let mut recorder = SessionRecorder::new(&RetentionPolicy { min_free_bytes: u64::MAX, ..Default::default() });
+    assert!(!recorder.record(&storage, &TelemetryData::new()), "Recording should stop when disk is low");
+    assert!(recorder.stop_reason().unwrap().contains("low disk space"));
+    assert!(recorder.finish(&storage).unwrap().is_none(), "Nothing should be saved");
+    
+    // Cleanup
+    let _ = storage.delete_session("2000");
+    let _ = storage.delete_session("3000");
+    let _ = fs::remove_file(storage.catalog_path());
+    let _ = fs::remove_dir_all(format!("{}.backups", test_file));
+}
+
+#[test]
+fn test_session_integrity() {
+    use console::storage::integrity;
+    
+    let test_file = "test_integrity.json";
+    let storage = Storage::new(test_file);
+    
+    // Seal a session spanning several byte ranges
+    let mut points: Vec<TelemetryData> = (0..600)
+        .map(|i| TelemetryData { timestamp: 1_000 + i, speed: 30.0, ..Default::default() })
+        .collect();
+    let original = serde_json::to_string(&points).unwrap();
+    fs::write(storage.session_path("1000"), &original).unwrap();
+    let manifest = storage.seal_session("1000").expect("Failed to seal session");
+    assert_eq!(manifest.ranges.len(), original.len().div_ceil(manifest.range_bytes), "Ranges should cover the whole file");
+    assert_eq!(manifest.chain_head().len(), 64, "Chain head should be a SHA-256 hex digest");
+    assert!(storage.verify_session("1000").unwrap().is_valid(), "Untouched session should verify");
+    
+    // Tamper with one sample in place, the file keeps its length
+    points[300].speed = 90.0;
+    let tampered = serde_json::to_string(&points).unwrap();
+    assert_eq!(tampered.len(), original.len());
+    let changed = original.bytes().zip(tampered.bytes()).position(|(a, b)| a != b).unwrap();
+    fs::write(storage.session_path("1000"), &tampered).unwrap();
+    let report = storage.verify_session("1000").unwrap();
+    assert!(!report.is_valid(), "Tampered session should fail verification");
+    assert!(!report.content_ok, "Content digest should not match");
+    let expected = manifest.ranges[changed / manifest.range_bytes].bytes();
+    assert_eq!(report.corrupted_ranges, vec![expected.clone()], "Only the range holding the change should be reported");
+    assert!(report.describe().contains(&format!("Bytes {}..{}", expected.start, expected.end)));
+    
+    // Test bytes appended to the file are reported
+    fs::write(storage.session_path("1000"), format!("{}\n\n", original)).unwrap();
+    let report = storage.verify_session("1000").unwrap();
+    assert_eq!(report.corrupted_ranges, vec![original.len()..original.len() + 2], "Appended bytes should be reported");
+    
+    // Test a rewritten manifest breaks the chain
+    let mut forged = manifest.clone();
+    forged.ranges[1].hash = integrity::sha256_hex(b"forged");
+    assert!(!integrity::verify(&fs::read(storage.session_path("1000")).unwrap(), &forged).chain_ok, "Forged block hash should break the chain");
+    
+    // Test the stable per-sample hash
+    let sample = TelemetryData { timestamp: 42, ..Default::default() };
+    assert_eq!(console::storage::utils::calculate_data_hash(&sample), console::storage::utils::calculate_data_hash(&sample.clone()));
+    
+    // Cleanup
+    storage.delete_session("1000").expect("Failed to delete session");
+    assert!(!std::path::Path::new(&storage.integrity_path("1000")).exists(), "Manifest should be removed with the session");
+    let _ = fs::remove_file(storage.catalog_path());
+}
+
+#[test]
+fn test_schema_migration() {
+    use console::storage::schema::{self, FileKind, CURRENT_SCHEMA_VERSION};
+    
+    // Test fixtures from each historical version load as current data.
+    // The v1 fixtures were written by the original `Storage::save` and `save_session`.
+    for (fixture, version) in [("tests/fixtures/telemetry_v1.json", 1), ("tests/fixtures/telemetry_v2.json", 2)] {
+        let content = fs::read(fixture).expect("Failed to read fixture");
+        let (data, found): (TelemetryData, u32) = schema::decode_with_version(FileKind::Telemetry, &content)
+            .unwrap_or_else(|e| panic!("Failed to decode {}: {}", fixture, e));
+        assert_eq!(found, version, "Fixture {} should be detected as version {}", fixture, version);
+        assert_eq!(data.rpm, 3200.0, "Fixture {} should keep its values", fixture);
+        assert_eq!(data.brake_temp, 150.0, "Fixture {} should have brake_temperature renamed", fixture);
+    }
+    for (fixture, version) in [("tests/fixtures/session_v1.json", 1), ("tests/fixtures/session_v2.json", 2)] {
+        let content = fs::read(fixture).expect("Failed to read fixture");
+        let (data, found): (Vec<TelemetryData>, u32) = schema::decode_with_version(FileKind::Session, &content)
+            .unwrap_or_else(|e| panic!("Failed to decode {}: {}", fixture, e));
+        assert_eq!(found, version, "Fixture {} should be detected as version {}", fixture, version);
+        assert_eq!(data.len(), 3, "Fixture {} should have three samples", fixture);
+        assert_eq!(data[0].brake_temp, 150.0, "Fixture {} should have brake_temperature renamed in its samples", fixture);
+    }
+    let json = serde_json::to_string(&TelemetryData { brake_temp: 150.0, ..Default::default() }).unwrap();
+    let unversioned: TelemetryData = serde_json::from_str(&json.replace("\"brake_temp\"", "\"brake_temperature\"")).unwrap();
+    assert_eq!(unversioned.brake_temp, 150.0, "The old field name should still deserialize");
+    
+    // Test channels are renamed in files keyed by channel name
+    let compressed = r#"{"schema_version": 2, "kind": "compressed_session", "data": {"sample_count": 1, "tolerances": {"default": 0.1, "per_channel": {"brake_temperature": 2.0}}, "channels": [{"name": "brake_temperature", "indexes": [0], "values": [150.0]}]}}"#;
+    let compressed: console::storage::compression::CompressedSession = schema::decode(FileKind::CompressedSession, compressed.as_bytes()).unwrap();
+    assert_eq!(compressed.channels[0].name, "brake_temp");
+    assert_eq!(compressed.tolerances.tolerance("brake_temp"), 2.0);
+    
+    // Test files from a newer version are refused
+    let future = format!("{{\"schema_version\": {}, \"data\": {{}}}}", CURRENT_SCHEMA_VERSION + 1);
+    assert!(schema::decode::<TelemetryData>(FileKind::Telemetry, future.as_bytes()).is_err(), "Newer files should be refused");
+    
+    // Test bulk migration of a data directory holding old files
+    let test_file = "test_migration.json";
+    let storage = Storage::new(test_file);
+    fs::copy("tests/fixtures/telemetry_v1.json", test_file).unwrap();
+    fs::copy("tests/fixtures/session_v1.json", storage.session_path("1700000000")).unwrap();
+    storage.seal_session("1700000000").expect("Failed to seal session");
+    
+    let report = storage.migrate_all().expect("Migration should succeed");
+    assert!(report.failed.is_empty(), "No file should fail: {:?}", report.failed);
+    assert_eq!(report.migrated.len(), 2, "Main file and session should be migrated");
+    assert!(fs::read_to_string(test_file).unwrap().contains(&format!("\"schema_version\": {}", CURRENT_SCHEMA_VERSION)), "Main file should be rewritten");
+    assert!(storage.verify_session("1700000000").unwrap().is_valid(), "Migrated session should be resealed");
+    assert_eq!(storage.load().unwrap().rpm, 3200.0, "Migrated data should load");
+    let csv_path = "test_migration.csv";
+    storage.export_to_csv(csv_path).unwrap();
+    let header = fs::read_to_string(csv_path).unwrap().lines().next().unwrap().to_string();
+    assert!(header.contains(",brake_temperature,"), "The CSV header should keep its column names: {}", header);
+    fs::remove_file(csv_path).unwrap();
+    
+    let report = storage.migrate_all().unwrap();
+    assert!(report.migrated.is_empty(), "Second run should find everything up to date");
+    
+    // Cleanup
+    let _ = storage.delete_session("1700000000");
+    let _ = fs::remove_file(storage.catalog_path());
+    let _ = fs::remove_file(test_file);
+}
+
+#[test]
+fn test_snapshot_backups() {
+    use console::storage::snapshot;
+    
+    let test_dir = "test_snapshots";
+    let _ = fs::remove_dir_all(test_dir);
+    fs::create_dir_all(test_dir).unwrap();
+    let storage = Storage::new(&format!("{}/data.json", test_dir));
+    
+    // First snapshot: main file, one session and a plugin config
+    let mut data = TelemetryData::new();
+    data.speed = 10.0;
+    storage.save(&data).unwrap();
+    fs::write(storage.session_path("1000"), "[]").unwrap();
+    fs::write(format!("{}/plugin_configs.json", test_dir), "{}").unwrap();
+    fs::write(format!("{}/unrelated.txt", test_dir), "not ours").unwrap();
+    let first = storage.create_snapshot().expect("Failed to create snapshot");
+    assert_eq!(first.files.len(), 3, "Snapshot should cover the main file, session and plugin config");
+    assert_eq!(first.new_objects, 3, "All files should be stored the first time");
+    
+    // Second snapshot only stores what changed
+    data.speed = 20.0;
+    storage.save(&data).unwrap();
+    fs::write(storage.session_path("2000"), "[]").unwrap();
+    let second = storage.create_snapshot().unwrap();
+    assert_eq!(second.parent, Some(first.timestamp_ms), "Second snapshot should follow the first");
+    assert_eq!(second.new_objects, 1, "Only the changed main file should be stored (new session duplicates existing content)");
+    let changes = snapshot::diff(&first, &second);
+    assert_eq!(changes.added, vec!["data.json.session_2000".to_string()]);
+    assert_eq!(changes.modified, vec!["data.json".to_string()]);
+    
+    // Restore to the point in time of the first snapshot
+    storage.restore_to_point_in_time(first.timestamp_ms).expect("Failed to restore");
+    assert_eq!(storage.load().unwrap().speed, 10.0, "Main file should be restored");
+    assert!(!std::path::Path::new(&storage.session_path("2000")).exists(), "Later session should be removed");
+    assert!(std::path::Path::new(&format!("{}/unrelated.txt", test_dir)).exists(), "Unrelated files should be left alone");
+    assert_eq!(storage.list_snapshots().unwrap().len(), 3, "Restore should snapshot the state it replaced");
+    
+    // Corrupted objects are detected and pruning drops unreferenced ones
+    let hash = &first.file("data.json").unwrap().hash;
+    fs::write(format!("{}/objects/{}", storage.backup_dir, hash), "tampered").unwrap();
+    assert_eq!(storage.verify_snapshot(first.timestamp_ms).unwrap().len(), 1, "Corrupted object should be reported");
+    assert!(storage.restore_snapshot(first.timestamp_ms).is_err(), "Damaged snapshot should not be restored");
+    assert_eq!(storage.prune_snapshots(1).unwrap(), 2, "Two old snapshots should be pruned");
+    
+    // Test permission decisions, key bindings and the plugin registry are backed up
+    let mut manager = console::plugin::PluginManager::new();
+    manager.load_permissions(&storage.plugin_permissions_path()).unwrap();
+    manager.save_permissions().unwrap();
+    assert!(storage.plugin_permissions_path().exists(), "Decisions should be saved in the data directory");
+    fs::write(storage.key_bindings_path(), r#"{"bindings": {}}"#).unwrap();
+    let registry = format!("{}/plugins/registry.json", test_dir);
+    fs::create_dir_all(format!("{}/plugins", test_dir)).unwrap();
+    fs::write(&registry, "{}").unwrap();
+    let third = storage.create_snapshot().unwrap();
+    for path in ["plugin_permissions.json", "key_bindings.json", "plugins/registry.json"] {
+        assert!(third.file(path).is_some(), "{} should be in the snapshot", path);
+    }
+    fs::remove_dir_all(format!("{}/plugins", test_dir)).unwrap();
+    storage.restore_snapshot(third.timestamp_ms).expect("Failed to restore");
+    assert_eq!(fs::read_to_string(&registry).unwrap(), "{}", "Registry should be restored into its folder");
+    
+    // Cleanup
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_session_rollups() {
+    use console::storage::rollup;
+    
+    let test_dir = std::env::temp_dir().join(format!("console_test_rollups_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&test_dir);
+    fs::create_dir_all(&test_dir).unwrap();
+    let storage = Storage::new(test_dir.join("test_rollup.json").to_str().unwrap());
+    
+    // Two hours at 4 samples per second with a ramping speed
+    let points: Vec<TelemetryData> = (0..7200 * 4)
+        .map(|i| TelemetryData {
+            timestamp: 1_080_000 + i / 4,
+            speed: (i % 40) as f64,
+            ..Default::default()
+        })
+        .collect();
+    let rollups = rollup::build_rollups(&points);
+    assert_eq!(rollups.tiers.len(), 3, "Should build 1 s, 10 s and 1 min tiers");
+    let one_second = &rollups.tiers[0].channels["speed"];
+    assert_eq!(one_second.len(), 7200, "1 s tier should have one bucket per second");
+    assert_eq!(one_second[0].count, 4, "Each 1 s bucket should aggregate 4 samples");
+    assert_eq!((one_second[0].min, one_second[0].max, one_second[0].avg), (0.0, 3.0, 1.5));
+    assert!(!rollups.tiers[0].channels.contains_key("latitude"), "Channels without data should be skipped");
+    
+    // Test tier selection by span and pixel width
+    assert_eq!(rollups.select_tier(7200, 100).unwrap().resolution_secs, 60, "Whole session on 100 px should use 1 min");
+    assert_eq!(rollups.select_tier(600, 50).unwrap().resolution_secs, 10, "10 minutes on 50 px should use 10 s");
+    assert_eq!(rollups.select_tier(60, 800).unwrap().resolution_secs, 1, "Zoomed in should fall back to 1 s");
+    
+    // Test querying through storage, built lazily for older sessions
+    fs::write(storage.session_path("1080000"), serde_json::to_string(&points).unwrap()).unwrap();
+    let series = storage.query_session_range("1080000", "speed", 1_080_000, 1_080_000 + 3599, 50)
+        .expect("Query should succeed");
+    assert_eq!(series.resolution_secs, 60);
+    assert_eq!(series.buckets.len(), 60, "First hour at 1 min should be 60 buckets");
+    assert_eq!(series.buckets[0].max, 39.0);
+    let tier_paths: Vec<String> = rollup::ROLLUP_RESOLUTIONS.iter().map(|r| storage.rollup_path("1080000", *r)).collect();
+    assert!(tier_paths.iter().all(|p| std::path::Path::new(p).exists()), "Each tier should be stored after the first query");
+    assert!(storage.query_session_range("1080000", "unknown", 0, 1, 1).is_err());
+    
+    // Test tiers are stored compactly, well below the raw session
+    let session_bytes = fs::metadata(storage.session_path("1080000")).unwrap().len();
+    let rollup_bytes: u64 = tier_paths.iter().map(|p| fs::metadata(p).unwrap().len()).sum();
+    assert!(rollup_bytes * 4 < session_bytes, "Rollups ({} bytes) should be much smaller than the session ({} bytes)", rollup_bytes, session_bytes);
+    assert_eq!(storage.load_rollups("1080000").unwrap(), rollups, "Stored tiers should load back unchanged");
+    
+    // Test a query only reads the tier it picks
+    fs::write(storage.rollup_path("1080000", 1), "damaged").unwrap();
+    let series = storage.query_session_range("1080000", "speed", 1_080_000, 1_080_000 + 7199, 100).expect("1 min query shouldn't read the 1 s tier");
+    assert_eq!(series.resolution_secs, 60);
+    assert!(storage.query_session_range("1080000", "speed", 1_080_000, 1_080_060, 800).is_err(), "1 s query should read the damaged tier");
+    
+    // Test timestamps that go backwards still give ordered tiers that save
+    let resynced: Vec<TelemetryData> = [100u64, 101, 102, 95, 96, 103]
+        .iter()
+        .enumerate()
+        .map(|(i, t)| TelemetryData { timestamp: *t, speed: i as f64, ..Default::default() })
+        .collect();
+    let tier = rollup::build_tier(&resynced, 1);
+    let starts: Vec<u64> = tier.channels["speed"].iter().map(|b| b.start).collect();
+    assert_eq!(starts, vec![95, 96, 100, 101, 102, 103], "Buckets should be sorted by start");
+    let encoded = serde_json::to_string(&tier).expect("Tier with backwards timestamps should encode");
+    assert_eq!(serde_json::from_str::<rollup::RollupTier>(&encoded).unwrap(), tier);
+    let rollups = rollup::build_rollups(&resynced);
+    assert_eq!((rollups.start_time, rollups.end_time), (95, 103));
+    let resynced_id = storage.save_session(&resynced).expect("Saving a session with backwards timestamps should succeed");
+    storage.delete_session(&resynced_id).unwrap();
+    let mut unordered = tier.clone();
+    unordered.channels.get_mut("speed").unwrap().swap(0, 1);
+    assert!(serde_json::to_string(&unordered).is_err(), "Out-of-order buckets should fail to encode, not wrap");
+    
+    // Cleanup
+    storage.delete_session("1080000").unwrap();
+    assert!(tier_paths.iter().all(|p| !std::path::Path::new(p).exists()), "Rollups should be removed with the session");
+    fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_error_bounded_compression() {
+    use console::storage::compression::{self, CompressionTolerances};
+    use console::storage::retention::RetentionPolicy;
+    
+    // Ten minutes of smooth driving with gear changes and a GPS dropout
+    let points: Vec<TelemetryData> = (0..600u64)
+        .map(|i| {
+            let t = i as f64;
+            TelemetryData {
+                timestamp: 5_000 + i,
+                speed: 40.0 + 20.0 * (t / 60.0).sin(),
+                rpm: 2500.0 + 800.0 * (t / 15.0).sin(),
+                engine_temp: 180.0 + t / 60.0,
+                gear: 2 + (i / 150) as i32,
+                latitude: if (200..220).contains(&i) { None } else { Some(40.0 + t * 0.0001) },
+                longitude: if (200..220).contains(&i) { None } else { Some(-74.0) },
+                ..Default::default()
+            }
+        })
+        .collect();
+    
+    // Test reconstruction stays within every channel's tolerance
+    let tolerances = CompressionTolerances::default();
+    let (compressed, report) = compression::compress_session(&points, &tolerances);
+    assert!(report.point_ratio() > 5.0, "Smooth data should compress well, got {:.1}", report.point_ratio());
+    assert!(report.worst_error_ratio() <= 1.0 + 1e-9, "Error should stay within tolerance");
+    
+    let restored = compression::decompress_session(&compressed);
+    assert_eq!(restored.len(), points.len(), "All samples should be rebuilt");
+    for (original, rebuilt) in points.iter().zip(&restored) {
+        assert_eq!(original.timestamp, rebuilt.timestamp, "Timestamps should be exact");
+        assert_eq!(original.gear, rebuilt.gear, "Gear should be exact");
+        assert!((original.speed - rebuilt.speed).abs() <= tolerances.tolerance("speed") + 1e-9);
+        assert!((original.rpm - rebuilt.rpm).abs() <= tolerances.tolerance("rpm") + 1e-9);
+        assert_eq!(original.latitude.is_some(), rebuilt.latitude.is_some(), "GPS gaps should be kept");
+    }
+    
+    // Test archiving old sessions through retention
+    let test_file = "test_compression.json";
+    let storage = Storage::new(test_file);
+    fs::write(storage.session_path("5000"), serde_json::to_string(&points).unwrap()).unwrap();
+    // Saved sessions come with an integrity manifest and rollups, which count towards their size
+    storage.seal_session("5000").unwrap();
+    storage.finalize_session("5000").unwrap();
+    let policy = RetentionPolicy { archive_after_secs: Some(1_000), ..Default::default() };
+    let report = storage.apply_retention_at(&policy, 100_000).expect("Retention should succeed");
+    assert_eq!(report.archived_sessions, vec!["5000".to_string()], "Old session should be archived");
+    assert!(report.bytes_freed > 0, "Archiving should free space");
+    assert_eq!(storage.load_session("5000").unwrap().len(), 600, "Archived session should load transparently");
+    assert!(storage.verify_session("5000").unwrap().is_valid(), "Archived session should be resealed");
+    assert!(storage.archive_session("5000", &tolerances).is_err(), "Sessions should not be archived twice");
+    
+    // Cleanup
+    storage.delete_session("5000").unwrap();
+    let _ = fs::remove_file(storage.catalog_path());
+}
+
+#[test]
+fn test_console_bundle() {
+    use console::storage::bundle::{ConflictResolution, ConsoleBundle, ImportOptions, ItemStatus};
+    
+    let (source_dir, target_dir) = ("test_bundle_source", "test_bundle_target");
+    for dir in [source_dir, target_dir] {
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+    }
+    let source = Storage::new(&format!("{}/data.json", source_dir));
+    let target = Storage::new(&format!("{}/data.json", target_dir));
+    
+    // Source car: layout, theme, alert rules and one recorded session
+    fs::write(format!("{}/ui_layout.xml", source_dir), "<Layout/>").unwrap();
+    fs::write(format!("{}/theme.json", source_dir), r#"{"accent": "red", "font_size": 14}"#).unwrap();
+    fs::write(format!("{}/alert_rules.json", source_dir), r#"{"engine_temp_max": 230}"#).unwrap();
+    let points: Vec<TelemetryData> = (0..10).map(|i| TelemetryData { timestamp: 1_000 + i, speed: i as f64, ..Default::default() }).collect();
+    fs::write(source.session_path("1000"), serde_json::to_string(&points).unwrap()).unwrap();
+    source.rename_session("1000", "Track day").unwrap();
+    
+    // Test export round-trips through a bundle file
+    let bundle_path = format!("{}/car.bundle.json", source_dir);
+    source.export_bundle(&["1000".to_string()]).unwrap().save(&bundle_path).unwrap();
+    let bundle = ConsoleBundle::load(&bundle_path).expect("Bundle should load");
+    assert_eq!(bundle.files.len(), 3, "Only existing config files should be bundled");
+    assert_eq!(bundle.sessions.len(), 1, "Selected session should be bundled");
+    
+    // Target car already has its own theme with a different accent
+    fs::write(format!("{}/theme.json", target_dir), r#"{"accent": "blue", "font_size": 14, "night_mode": true}"#).unwrap();
+    
+    // Test preview reports per-setting conflicts
+    let preview = target.preview_bundle_import(&bundle).unwrap();
+    let status = |name: &str| preview.items.iter().find(|i| i.name == name).map(|i| i.status);
+    assert_eq!(status("theme.json: accent"), Some(ItemStatus::Conflict), "Differing accent should conflict");
+    assert_eq!(status("theme.json: font_size"), Some(ItemStatus::Identical), "Same font size should match");
+    assert_eq!(status("alert_rules.json: engine_temp_max"), Some(ItemStatus::New), "Alert rule should be new");
+    assert_eq!(status("session 1000"), Some(ItemStatus::New), "Session should be new");
+    assert_eq!(preview.conflicts().count(), 1, "Only the accent should conflict");
+    
+    // Test merge keeps local values on conflict and adds everything else
+    let options = ImportOptions { include_sessions: true, ..Default::default() };
+    let report = target.import_bundle(&bundle, &options).expect("Import should succeed");
+    assert_eq!(report.kept_existing, vec!["theme.json: accent".to_string()], "Local accent should be kept");
+    assert_eq!(report.imported_sessions, vec!["1000".to_string()], "Session should keep its id");
+    let theme: serde_json::Value = serde_json::from_str(&fs::read_to_string(format!("{}/theme.json", target_dir)).unwrap()).unwrap();
+    assert_eq!(theme["accent"], "blue", "Existing setting should win by default");
+    assert_eq!(theme["night_mode"], true, "Local-only settings should survive the merge");
+    assert_eq!(target.get_session_info("1000").unwrap().metadata.name, "Track day", "Session metadata should be imported");
+    assert!(target.verify_session("1000").unwrap().is_valid(), "Imported session should be sealed");
+    assert!(!target.list_snapshots().unwrap().is_empty(), "Import should snapshot the target first");
+    
+    // Test importing again is a no-op, and preferring the bundle overrides conflicts
+    let options = ImportOptions { resolution: ConflictResolution::UseBundle, include_sessions: true };
+    let report = target.import_bundle(&bundle, &options).unwrap();
+    assert_eq!(report.updated_files, vec!["theme.json".to_string()], "Only the theme should change");
+    assert!(report.imported_sessions.is_empty(), "Identical sessions should not be duplicated");
+    assert!(fs::read_to_string(format!("{}/theme.json", target_dir)).unwrap().contains("red"), "Bundle accent should win");
+    
+    // Test a changed session with a clashing id gets a new id
+    fs::write(target.session_path("1000"), "[]").unwrap();
+    let report = target.import_bundle(&bundle, &options).unwrap();
+    assert_eq!(report.imported_sessions, vec!["1001".to_string()], "Clashing session should be imported under a new id");
+    
+    // Test bundles can't write outside the managed files
+    let mut evil = bundle.clone();
+    evil.files.insert("../escape.json".to_string(), "{}".to_string());
+    assert!(target.import_bundle(&evil, &options).is_err(), "Unknown files should be rejected");
+    
+    // Cleanup
+    for dir in [source_dir, target_dir] {
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+// Plugin exported through the C ABI, loaded in-process for testing
+#[derive(Default)]
+struct AbiTestPlugin {
+    speed: f64,
+    label: String,
+}
+
+impl console::plugin::abi::ExportedPlugin for AbiTestPlugin {
+    fn metadata(&self) -> PluginMetadata {
+        PluginMetadata { name: "Abi Test".to_string(), ..Default::default() }
+    }
+    
+    fn update(&mut self, data: &TelemetryData) {
+        if data.speed < 0.0 {
+            panic!("negative speed");
+        }
+        self.speed = data.speed;
+    }
+    
+    fn draw(&self) -> console::plugin::draw::DrawList {
+        let mut list = console::plugin::draw::DrawList::new();
+        list.text(&self.label).gauge("Speed", self.speed, 0.0, 200.0, "mph");
+        list
+    }
+    
+    fn get_config(&self) -> std::collections::HashMap<String, String> {
+        [("label".to_string(), self.label.clone())].into_iter().collect()
+    }
+    
+    fn set_config(&mut self, config: std::collections::HashMap<String, String>) {
+        self.label = config.get("label").cloned().unwrap_or_default();
+    }
+}
+
+console::export_plugin!(AbiTestPlugin);
+
+#[test]
+fn test_native_plugin_abi() {
+    use console::plugin::PluginStatus;
+    use console::plugin::abi::{PluginVTable, PLUGIN_ABI_VERSION};
+    use console::plugin::draw::DrawCommand;
+    use console::plugin::loader::NativePlugin;
+    use console::plugin::PluginManager;
+    
+    // Test handshake and metadata export
+    assert_eq!(console_plugin_abi_version(), PLUGIN_ABI_VERSION, "Exported ABI version should match");
+    let mut plugin = unsafe { NativePlugin::from_vtable(console_plugin_entry(), None) }.expect("Plugin should load");
+    assert_eq!(plugin.get_metadata().name, "Abi Test", "Metadata should cross the ABI");
+    let older: PluginMetadata = serde_json::from_str(r#"{"name": "Older Plugin", "version": "0.9.0"}"#).unwrap();
+    assert_eq!(older.name, "Older Plugin");
+    assert!(older.permissions.is_empty() && older.settings.is_empty(), "Fields a plugin doesn't send should take their defaults");
+
+    // Test calls through the function table
+    plugin.init();
+    plugin.set_config([("label".to_string(), "Track".to_string())].into_iter().collect());
+    assert_eq!(plugin.get_config().get("label").map(|s| s.as_str()), Some("Track"), "Config should round-trip");
+    plugin.update(&TelemetryData { speed: 88.0, ..Default::default() });
+    let list = plugin.draw_list().expect("Plugin should return a draw list");
+    assert_eq!(list.commands[0], DrawCommand::Text { text: "Track".to_string(), color: None }, "Draw list should reflect config");
+    assert!(matches!(list.commands[1], DrawCommand::Gauge { value, .. } if value == 88.0), "Draw list should reflect telemetry");
+    
+    // Test a panic inside the plugin is reported instead of unwinding into the host
+    plugin.update(&TelemetryData { speed: -1.0, ..Default::default() });
+    assert!(matches!(plugin.get_status(), PluginStatus::Error(msg) if msg.contains("negative speed")), "Panic should become an error status");
+    
+    // Test mismatched ABI versions are refused with a clear error
+    static OLD_VTABLE: PluginVTable = PluginVTable { abi_version: 99, ..PluginVTable::for_plugin::<AbiTestPlugin>() };
+    let error = unsafe { NativePlugin::from_vtable(&OLD_VTABLE, None) }.err().expect("Mismatched ABI should be refused");
+    assert!(error.contains("ABI version 99"), "Error should name the plugin's ABI version: {}", error);
+    
+    // Test the loader refuses files that aren't plugin libraries
+    let test_dir = "test_plugin_dir";
+    let _ = fs::remove_dir_all(test_dir);
+    fs::create_dir_all(test_dir).unwrap();
+    fs::write(format!("{}/broken.{}", test_dir, std::env::consts::DLL_EXTENSION), "not a library").unwrap();
+    fs::write(format!("{}/readme.txt", test_dir), "ignored").unwrap();
+    let mut manager = PluginManager::new();
+    let report = manager.load_plugins_from_dir(test_dir);
+    assert!(report.loaded.is_empty(), "Nothing should load");
+    assert_eq!(report.rejected.len(), 1, "Only the library file should be tried");
+    assert!(report.rejected[0].1.contains("cannot open library"), "Rejection should explain why");
+    
+    // Cleanup
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_native_plugin_library() {
+    use console::plugin::draw::DrawCommand;
+    use console::plugin::loader::NativePlugin;
+    use console::plugin::PluginManager;
+    use std::env::consts::{DLL_EXTENSION, DLL_PREFIX, DLL_SUFFIX};
+    
+    // The speedometer example is built as a cdylib next to the test binaries
+    let examples = std::env::current_exe().unwrap().parent().unwrap().parent().unwrap().join("examples");
+    let library = examples.join(format!("{}speedometer{}", DLL_PREFIX, DLL_SUFFIX));
+    assert!(library.exists(), "Example plugin should be built at {}", library.display());
+    
+    // Test the library loads through libloading and works across the ABI
+    let mut plugin = NativePlugin::load(&library).expect("Example plugin should load");
+    assert_eq!(plugin.get_metadata().name, "Speedometer", "Metadata should come from the library");
+    plugin.init();
+    plugin.update(&TelemetryData { speed: 72.0, rpm: 3000.0, ..Default::default() });
+    let list = plugin.draw_list().expect("Plugin should return a draw list");
+    assert!(list.commands.iter().any(|c| matches!(c, DrawCommand::Gauge { value, .. } if *value == 72.0)), "Draw list should show the speed: {:?}", list.commands);
+    drop(plugin);
+    
+    // Test the manager picks the library up from a plugin folder
+    let test_dir = std::env::temp_dir().join(format!("console_test_native_{}", std::process::id()));
+    fs::create_dir_all(&test_dir).unwrap();
+    fs::copy(&library, test_dir.join(format!("speedometer.{}", DLL_EXTENSION))).unwrap();
+    let mut manager = PluginManager::new();
+    let report = manager.load_plugins_from_dir(test_dir.to_str().unwrap());
+    assert!(report.rejected.is_empty(), "Library should not be refused: {:?}", report.rejected);
+    assert!(manager.get_plugin("Speedometer").is_some(), "Manager should load the library");
+    
+    // Cleanup
+    drop(manager);
+    fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_wasm_plugin_sandbox() {
+    use console::plugin::PluginStatus;
+    use console::plugin::draw::DrawCommand;
+    use console::plugin::manifest::PluginManifest;
+    use console::plugin::wasm::WasmPlugin;
+    
+    let test_dir = "test_wasm_dir";
+    let _ = fs::remove_dir_all(test_dir);
+    fs::create_dir_all(test_dir).unwrap();
+    let secret_path = format!("{}/secret.json", test_dir);
+    fs::write(&secret_path, r#"{"commands":[{"type":"text","text":"granted"}]}"#).unwrap();
+    
+    // Module that tries to read a file in init and draws it if allowed
+    let heading = r#"{"commands":[{"type":"heading","text":"Hello"}]}"#;
+    let reader = |path: &str| r#"(module
+      (import "console" "log" (func $log (param i32 i32)))
+      (import "console" "read_file" (func $read_file (param i32 i32 i32 i32) (result i32)))
+      (memory (export "memory") 1)
+      (global $read (mut i32) (i32.const -1))
+      (data (i32.const 0) "PATH")
+      (data (i32.const 1024) "HEADING")
+      (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+      (func (export "init")
+        (global.set $read (call $read_file (i32.const 0) (i32.const PATH_LEN) (i32.const 8192) (i32.const 1024))))
+      (func (export "update") (param $ptr i32) (param $len i32)
+        (call $log (local.get $ptr) (i32.const 12)))
+      (func (export "render") (result i64)
+        (if (result i64) (i32.gt_s (global.get $read) (i32.const 0))
+          (then (i64.or (i64.const 0x200000000000) (i64.extend_i32_u (global.get $read))))
+          (else (i64.or (i64.const 0x40000000000) (i64.const HEADING_LEN))))))"#
+        .replace("PATH_LEN", &path.len().to_string())
+        .replace("HEADING_LEN", &heading.len().to_string())
+        .replace("PATH", path)
+        .replace("HEADING", &heading.replace('"', "\\\""));
+    let wat = reader(&secret_path);
+    
+    // Test no filesystem access without a grant
+    let mut plugin = WasmPlugin::new(wat.as_bytes(), PluginManifest { name: "reader".to_string(), ..Default::default() })
+        .expect("Module should instantiate");
+    plugin.init();
+    plugin.update(&TelemetryData::default());
+    assert!(matches!(plugin.get_status(), PluginStatus::Ready), "Plugin should run normally");
+    assert_eq!(plugin.draw_list().commands, vec![DrawCommand::Heading { text: "Hello".to_string() }], "Draw list should come from the module");
+    assert!(plugin.log().iter().any(|l| l.starts_with("denied: read")), "Ungranted read should be denied");
+    assert_eq!(plugin.log().last().map(|s| s.as_str()), Some("{\"speed\":0.0"), "Telemetry should reach the module as JSON");
+    
+    // Test a manifest grant opens exactly that folder
+    let manifest = PluginManifest { name: "reader".to_string(), read_paths: vec![test_dir.into()], ..Default::default() };
+    let mut plugin = WasmPlugin::new(wat.as_bytes(), manifest).unwrap();
+    plugin.init();
+    assert_eq!(plugin.draw_list().commands, vec![DrawCommand::Text { text: "granted".to_string(), color: None }], "Granted read should succeed");
+    
+    // Test relative grants in a manifest file are relative to the manifest
+    fs::write(format!("{}/reader.json", test_dir), r#"{"read_paths": ["."]}"#).unwrap();
+    let manifest = PluginManifest::for_plugin(std::path::Path::new(&format!("{}/reader.wasm", test_dir))).unwrap();
+    assert!(manifest.may_read(std::path::Path::new(&secret_path)), "Grant should cover the manifest's folder");
+    assert!(!manifest.may_read(std::path::Path::new("Cargo.toml")), "Grant should not cover the working directory");
+    let mut plugin = WasmPlugin::new(reader("secret.json").as_bytes(), manifest).unwrap();
+    plugin.init();
+    assert_eq!(plugin.draw_list().commands, vec![DrawCommand::Text { text: "granted".to_string(), color: None }], "Relative reads should be taken from the manifest's folder");
+    
+    // Test a server that sends one byte at a time can't hold a request past its deadline
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        use std::io::Write;
+        let Ok((mut stream, _)) = listener.accept() else {
+            return;
+        };
+        for _ in 0..50 {
+            if stream.write_all(b"x").is_err() {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+    });
+    let fetch = r#"(module
+      (import "console" "log" (func $log (param i32 i32)))
+      (import "console" "net_request" (func $net_request (param i32 i32 i32 i32 i32 i32) (result i32)))
+      (memory (export "memory") 1)
+      (data (i32.const 0) "ADDRESS")
+      (data (i32.const 256) "GET")
+      (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+      (func (export "init")
+        (call $log (i32.const 8192) (call $net_request (i32.const 0) (i32.const ADDRESS_LEN) (i32.const 256) (i32.const 3) (i32.const 8192) (i32.const 1024))))
+      (func (export "render") (result i64) (i64.const 0)))"#
+        .replace("ADDRESS_LEN", &address.len().to_string())
+        .replace("ADDRESS", &address);
+    let manifest = PluginManifest { name: "fetcher".to_string(), network_hosts: vec![address], ..Default::default() };
+    let mut plugin = WasmPlugin::new(fetch.as_bytes(), manifest).unwrap();
+    let started = std::time::Instant::now();
+    plugin.init();
+    assert!(started.elapsed() < std::time::Duration::from_secs(3), "Request should give up after its overall deadline, took {:?}", started.elapsed());
+    assert!(plugin.log().is_empty(), "Timed out request should fail, not return what trickled in: {:?}", plugin.log());
+    
+    // Test runaway plugins are stopped by the CPU budget
+    let spin = r#"(module
+      (memory (export "memory") 1)
+      (func (export "alloc") (param i32) (result i32) (i32.const 0))
+      (func (export "update") (param i32 i32) (loop $forever (br $forever)))
+      (func (export "render") (result i64) (i64.const 0)))"#;
+    let mut plugin = WasmPlugin::new(spin.as_bytes(), PluginManifest { fuel_per_call: 100_000, ..Default::default() }).unwrap();
+    plugin.update(&TelemetryData::default());
+    assert!(matches!(plugin.get_status(), PluginStatus::Error(msg) if msg.contains("CPU budget")), "Infinite loop should exhaust fuel");
+    
+    // Test memory limits apply from instantiation
+    let hungry = r#"(module
+      (memory (export "memory") 32)
+      (func (export "alloc") (param i32) (result i32) (i32.const 0))
+      (func (export "render") (result i64) (i64.const 0)))"#;
+    let manifest = PluginManifest { max_memory_bytes: 1024 * 1024, ..Default::default() };
+    assert!(WasmPlugin::new(hungry.as_bytes(), manifest).is_err(), "2 MiB of memory should exceed a 1 MiB limit");
+    
+    // Test modules can't import anything beyond the console host functions
+    let wasi = r#"(module (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32))))"#;
+    assert!(WasmPlugin::new(wasi.as_bytes(), PluginManifest::default()).is_err(), "WASI imports should be refused");
+    
+    // Cleanup
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+#[test]
+fn test_plugin_hot_reload() {
+    use console::plugin::PluginManager;
+    use console::plugin::watcher::{PluginChange, PluginWatcher};
+    
+    let test_dir = "test_reload_dir";
+    let _ = fs::remove_dir_all(test_dir);
+    fs::create_dir_all(test_dir).unwrap();
+    let module_path = std::path::PathBuf::from(format!("{}/gauge.wasm", test_dir));
+    let manifest_path = format!("{}/gauge.json", test_dir);
+    
+    // Module that keeps whatever config it was given and reports it back
+    let module = r#"(module
+      (memory (export "memory") 1)
+      (global $ptr (mut i32) (i32.const 0))
+      (global $len (mut i32) (i32.const 2))
+      (data (i32.const 0) "{}")
+      (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+      (func (export "set_config") (param i32 i32) (global.set $ptr (local.get 0)) (global.set $len (local.get 1)))
+      (func (export "get_config") (result i64)
+        (i64.or (i64.shl (i64.extend_i32_u (global.get $ptr)) (i64.const 32)) (i64.extend_i32_u (global.get $len))))
+      (func (export "render") (result i64) (i64.const 0)))"#;
+    fs::write(&module_path, module).unwrap();
+    fs::write(&manifest_path, r#"{"name": "Gauge", "version": "1.0"}"#).unwrap();
+    
+    let mut manager = PluginManager::new();
+    manager.add_plugin(Box::new(MockPlugin { initialized: false, update_count: 0, enabled: true }));
+    assert_eq!(manager.load_plugins_from_dir(test_dir).loaded, vec!["Gauge".to_string()], "Module should load");
+    manager.init_plugins();
+    let config: std::collections::HashMap<String, String> = [("color".to_string(), "red".to_string())].into_iter().collect();
+    manager.get_plugin_mut("Gauge").unwrap().set_config(config.clone());
+    let mut watcher = PluginWatcher::new(test_dir);
+    assert!(watcher.poll().is_empty(), "Nothing should change before files are touched");
+    
+    // Test a changed manifest is reported once the files settle, then reloaded in place
+    fs::write(&manifest_path, r#"{"name": "Gauge", "version": "2.0.0"}"#).unwrap();
+    assert!(watcher.poll().is_empty(), "Changes should wait for the file to settle");
+    let changes = watcher.poll();
+    assert_eq!(changes, vec![PluginChange::Modified(module_path.clone())], "Settled change should be reported");
+    let report = manager.apply_plugin_changes(&changes);
+    assert_eq!(report.reloaded, vec!["Gauge".to_string()], "Plugin should be reloaded");
+    let gauge = manager.get_plugin("Gauge").unwrap();
+    assert_eq!(gauge.get_metadata().version, "2.0.0", "New version should be running");
+    assert_eq!(gauge.get_config(), config, "Config should be re-applied after reload");
+    let names: Vec<String> = manager.get_all_metadata().into_iter().map(|m| m.name).collect();
+    assert_eq!(names, vec!["Mock Plugin".to_string(), "Gauge".to_string()], "Other plugins and order should be kept");
+    
+    // Test a broken build leaves the running version in place
+    fs::write(&module_path, "not wasm").unwrap();
+    watcher.poll();
+    let report = manager.apply_plugin_changes(&watcher.poll());
+    assert_eq!(report.rejected.len(), 1, "Broken module should be refused");
+    assert_eq!(manager.get_plugin("Gauge").unwrap().get_metadata().version, "2.0.0", "Old version should keep running");
+    
+    // Test deleting the file unloads the plugin
+    fs::remove_file(&module_path).unwrap();
+    fs::remove_file(&manifest_path).unwrap();
+    let report = manager.apply_plugin_changes(&watcher.poll());
+    assert_eq!(report.unloaded, vec!["Gauge".to_string()], "Removed module should be unloaded");
+    assert!(manager.get_plugin("Gauge").is_none(), "Plugin should be gone");
+    assert!(manager.get_plugin("Mock Plugin").is_some(), "Other plugins should be untouched");
+    
+    // Cleanup
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+/// Plugin that panics in `update` until told otherwise
+struct PanickingPlugin {
+    panics: bool,
+    enabled: bool,
+}
+
+impl Plugin for PanickingPlugin {
+    fn init(&mut self) {}
+
+    fn update(&mut self, _data: &TelemetryData) {
+        if self.panics {
+            panic!("sensor index out of range");
+        }
+    }
+
+    fn render(&self, _ui: &mut egui::Ui) {}
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: "Crashy".to_string(),
+            version: "1.0.0".to_string(),
+            author: "Test Author".to_string(),
+            description: "Panics on update".to_string(),
+            category: PluginCategory::Diagnostics,
+            ..PluginMetadata::default()
+        }
+    }
+}
+
+#[test]
+fn test_plugin_panic_isolation() {
+    use console::plugin::{PluginManager, PluginStatus};
+    use console::plugin::health::MAX_CONSECUTIVE_FAILURES;
+
+    let mut manager = PluginManager::new();
+    manager.add_plugin(Box::new(PanickingPlugin { panics: true, enabled: true }));
+    manager.add_plugin(Box::new(MockPlugin { initialized: false, update_count: 0, enabled: true }));
+    manager.init_plugins();
+    let telemetry = TelemetryData::new();
+
+    // Test a panic is caught and reported in the plugin status
+    manager.update_plugins(&telemetry);
+    match manager.plugin_status("Crashy") {
+        Some(PluginStatus::Error(message)) => assert!(message.contains("sensor index out of range"), "Status should carry the panic message: {}", message),
+        other => panic!("Crashing plugin should be in error, got {:?}", other),
+    }
+    assert!(manager.get_plugin("Crashy").unwrap().is_enabled(), "A single failure should not disable the plugin");
+
+    // Test repeated failures disable the plugin while others keep running
+    for _ in 1..MAX_CONSECUTIVE_FAILURES + 2 {
+        manager.update_plugins(&telemetry);
+    }
+    assert!(!manager.get_plugin("Crashy").unwrap().is_enabled(), "Plugin should be disabled after repeated failures");
+    let health = manager.plugin_health("Crashy").unwrap();
+    assert_eq!(health.total_failures, MAX_CONSECUTIVE_FAILURES, "Disabled plugin should not be called again");
+    assert!(health.disabled_reason.is_some(), "Disable reason should be recorded");
+    assert!(!manager.get_enabled_plugins().contains(&"Crashy".to_string()));
+    assert!(matches!(manager.plugin_status("Mock Plugin"), Some(PluginStatus::Ready)), "Other plugins should be unaffected");
+
+    // Test re-enabling gives the plugin a clean record
+    assert!(manager.enable_plugin("Crashy"), "Should re-enable plugin");
+    assert!(manager.plugin_health("Crashy").is_none(), "Health should be reset");
+    manager.update_plugins(&telemetry);
+    assert_eq!(manager.plugin_health("Crashy").unwrap().consecutive_failures, 1, "Failures should count from zero again");
+}
+
+/// Plugin that reports an error status, or sleeps through its updates
+struct StallingPlugin {
+    name: &'static str,
+    stall: std::time::Duration,
+    updates: std::sync::Arc<std::sync::atomic::AtomicU32>,
+}
+
+impl Plugin for StallingPlugin {
+    fn init(&mut self) {}
+
+    fn update(&mut self, _data: &TelemetryData) {
+        self.updates.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        std::thread::sleep(self.stall);
+    }
+
+    fn render(&self, _ui: &mut egui::Ui) {}
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata { name: self.name.to_string(), ..PluginMetadata::default() }
+    }
+
+    fn get_status(&self) -> console::plugin::PluginStatus {
+        console::plugin::PluginStatus::Error("no GPS fix".to_string())
+    }
+}
+
+#[test]
+fn test_plugin_watchdog() {
+    use console::plugin::PluginManager;
+    use console::plugin::health::{HANG_TIMEOUT, MAX_CONSECUTIVE_FAILURES};
+    use std::sync::atomic::Ordering;
+
+    let mut manager = PluginManager::new();
+    let reporting = std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0));
+    let hanging = std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0));
+    manager.add_plugin(Box::new(StallingPlugin { name: "No Fix", stall: std::time::Duration::ZERO, updates: reporting.clone() }));
+    manager.add_plugin(Box::new(StallingPlugin { name: "Stuck", stall: HANG_TIMEOUT + std::time::Duration::from_millis(300), updates: hanging.clone() }));
+    manager.init_plugins();
+    let telemetry = TelemetryData::new();
+
+    // Test a call overrunning the hang timeout marks the plugin hung and disables it
+    manager.update_plugins(&telemetry);
+    let health = manager.plugin_health("Stuck").unwrap();
+    assert!(health.disabled_reason.as_deref().is_some_and(|r| r.contains("update hung")), "Hung plugin should be disabled: {:?}", health.disabled_reason);
+
+    // Test a hung plugin is skipped and an error status the plugin reports is no crash
+    for _ in 0..MAX_CONSECUTIVE_FAILURES + 1 {
+        manager.update_plugins(&telemetry);
+    }
+    assert_eq!(hanging.load(Ordering::Relaxed), 1, "Hung plugin should not be called again");
+    assert_eq!(reporting.load(Ordering::Relaxed), MAX_CONSECUTIVE_FAILURES + 2, "Plugin reporting an error should keep being updated");
+    let health = manager.plugin_health("No Fix").unwrap();
+    assert_eq!(health.total_failures, 0, "Reported error status should not count as a failure");
+    assert!(health.disabled_reason.is_none(), "Reported error status should not disable the plugin");
+    assert!(manager.get_plugin("No Fix").unwrap().is_enabled());
+
+    // Test a plugin hung on the update thread doesn't block the UI or exit
+    use console::plugin::schedule::{lock_within, UpdateThread, JOIN_TIMEOUT, UI_LOCK_WAIT};
+    let mut manager = PluginManager::new();
+    let stuck = std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0));
+    manager.add_plugin(Box::new(StallingPlugin { name: "Stuck", stall: HANG_TIMEOUT * 2, updates: stuck.clone() }));
+    manager.init_plugins();
+    let hung_plugins = manager.hung_plugins();
+    let manager = std::sync::Arc::new(std::sync::Mutex::new(manager));
+    let thread = UpdateThread::start(manager.clone(), std::sync::Arc::new(std::sync::Mutex::new(telemetry)), std::time::Duration::from_millis(10), |_| {});
+    std::thread::sleep(HANG_TIMEOUT + std::time::Duration::from_millis(300));
+    assert_eq!(stuck.load(Ordering::Relaxed), 1, "Plugin should be stuck in its first update");
+    assert!(hung_plugins.contains("Stuck"), "Watchdog should mark the plugin hung while the call runs");
+    let started = std::time::Instant::now();
+    assert!(lock_within(&manager, UI_LOCK_WAIT).is_none(), "UI should give up on the locked manager");
+    assert!(started.elapsed() < UI_LOCK_WAIT * 10, "UI should only wait briefly for the manager");
+    let started = std::time::Instant::now();
+    drop(thread);
+    assert!(started.elapsed() < JOIN_TIMEOUT * 2, "Stopping the update thread shouldn't wait for the hung call");
+}
+
+/// Service exposed by the fuel plugin in the dependency test
+struct FuelService {
+    litres_per_hour: f64,
+}
+
+/// Plugin with configurable dependencies that records its init order
+struct DependentPlugin {
+    metadata: PluginMetadata,
+    init_log: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    fuel: std::sync::Arc<std::sync::Mutex<Option<f64>>>,
+}
+
+impl DependentPlugin {
+    fn new(name: &str, version: &str, dependencies: &[&str], init_log: &std::sync::Arc<std::sync::Mutex<Vec<String>>>) -> Self {
+        Self {
+            metadata: PluginMetadata {
+                name: name.to_string(),
+                version: version.to_string(),
+                dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+                ..PluginMetadata::default()
+            },
+            init_log: init_log.clone(),
+            fuel: Default::default(),
+        }
+    }
+}
+
+impl Plugin for DependentPlugin {
+    fn init(&mut self) {
+        self.init_log.lock().unwrap().push(self.metadata.name.clone());
+    }
+
+    fn update(&mut self, _data: &TelemetryData) {}
+
+    fn render(&self, _ui: &mut egui::Ui) {}
+
+    fn get_metadata(&self) -> PluginMetadata {
+        self.metadata.clone()
+    }
+
+    fn connect_services(&mut self, services: &console::plugin::deps::Services) {
+        *self.fuel.lock().unwrap() = services.get::<FuelService>("Fuel").map(|s| s.litres_per_hour);
+    }
+
+    fn provide_services(&mut self, services: &mut console::plugin::deps::ServiceProvider) {
+        if self.metadata.name == "Fuel" {
+            services.provide(FuelService { litres_per_hour: 7.5 });
+        }
+    }
+}
+
+#[test]
+fn test_plugin_dependencies() {
+    use console::plugin::PluginManager;
+    use console::plugin::deps::Dependency;
+
+    // Test dependency strings with and without version requirements
+    let dependency = Dependency::parse("Fuel Monitor >=1.2, <2").unwrap();
+    assert_eq!(dependency.name, "Fuel Monitor");
+    assert!(dependency.requirement.matches(&semver::Version::new(1, 4, 0)));
+    assert!(!dependency.requirement.matches(&semver::Version::new(2, 0, 0)));
+    assert_eq!(Dependency::parse("Fuel").unwrap().requirement, semver::VersionReq::STAR);
+    assert!(Dependency::parse(">=1.0").is_err(), "A requirement without a name should be refused");
+
+    let init_log = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let trip = DependentPlugin::new("Trip", "1.0.0", &["Fuel ^1.0"], &init_log);
+    let snoop = DependentPlugin::new("Snoop", "1.0.0", &[], &init_log);
+    let (trip_fuel, snoop_fuel) = (trip.fuel.clone(), snoop.fuel.clone());
+
+    let mut manager = PluginManager::new();
+    manager.add_plugin(Box::new(trip));
+    manager.add_plugin(Box::new(DependentPlugin::new("Fuel", "1.2.0", &[], &init_log)));
+    manager.add_plugin(Box::new(snoop));
+    manager.add_plugin(Box::new(DependentPlugin::new("Gauge", "1.0.0", &["Fuel >=2"], &init_log)));
+    manager.add_plugin(Box::new(DependentPlugin::new("Orphan", "1.0.0", &["Missing"], &init_log)));
+    manager.add_plugin(Box::new(DependentPlugin::new("A", "1.0.0", &["B"], &init_log)));
+    manager.add_plugin(Box::new(DependentPlugin::new("B", "1.0.0", &["A"], &init_log)));
+    manager.add_plugin(Box::new(DependentPlugin::new("C", "1.0.0", &["A"], &init_log)));
+
+    // Test broken dependencies are refused with a reason
+    let refused: std::collections::HashMap<String, String> = manager.init_plugins().into_iter().collect();
+    let mut names: Vec<&str> = refused.keys().map(|n| n.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["A", "B", "C", "Gauge", "Orphan"], "Plugins with broken dependencies should be refused");
+    assert!(refused["Gauge"].contains("requires 'Fuel' >=2"), "Incompatible version should be reported: {}", refused["Gauge"]);
+    assert!(refused["Orphan"].contains("missing dependency 'Missing'"), "Missing dependency should be reported");
+    assert!(refused["A"].contains("dependency cycle: A -> B -> A"), "Cycle should be reported: {}", refused["A"]);
+    assert!(manager.get_plugin("Gauge").is_none(), "Refused plugins should not be loaded");
+
+    // Test dependencies are initialized first, otherwise registration order is kept
+    assert_eq!(*init_log.lock().unwrap(), vec!["Fuel", "Trip", "Snoop"], "Init should follow the dependency order");
+
+    // Test services are only visible to declared dependents
+    assert_eq!(*trip_fuel.lock().unwrap(), Some(7.5), "Dependent should receive the service");
+    assert_eq!(*snoop_fuel.lock().unwrap(), None, "Unrelated plugins should not see the service");
+}
+
+/// Plugin declaring typed settings, keeping the config it was given
+#[derive(Default)]
+struct SettingsPlugin {
+    config: std::collections::HashMap<String, String>,
+}
+
+impl Plugin for SettingsPlugin {
+    fn init(&mut self) {}
+
+    fn update(&mut self, _data: &TelemetryData) {}
+
+    fn render(&self, _ui: &mut egui::Ui) {}
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: "Shift Light".to_string(),
+            settings: vec![
+                PluginSetting {
+                    name: "units".to_string(),
+                    default_value: "metric".to_string(),
+                    choices: vec!["metric".to_string(), "imperial".to_string()],
+                    ..PluginSetting::default()
+                },
+                PluginSetting {
+                    name: "max_rpm".to_string(),
+                    value_type: SettingType::Integer,
+                    default_value: "8000".to_string(),
+                    required: true,
+                    min: Some(1000.0),
+                    max: Some(12000.0),
+                    ..PluginSetting::default()
+                },
+                PluginSetting {
+                    name: "warning_color".to_string(),
+                    value_type: SettingType::Color,
+                    default_value: "#FF0000".to_string(),
+                    ..PluginSetting::default()
+                },
+            ],
+            ..PluginMetadata::default()
+        }
+    }
+
+    fn get_config(&self) -> std::collections::HashMap<String, String> {
+        self.config.clone()
+    }
+
+    fn set_config(&mut self, config: std::collections::HashMap<String, String>) {
+        self.config = config;
+    }
+}
+
+#[test]
+fn test_plugin_settings() {
+    use console::plugin::PluginManager;
+    use console::plugin::settings::{self, Settings};
+    use std::collections::HashMap;
+
+    let metadata = SettingsPlugin::default().get_metadata();
+    let config = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    };
+
+    // Test typed access, with defaults for missing optional settings
+    let typed = Settings::parse(&metadata.settings, &config(&[("max_rpm", "7200")])).unwrap();
+    assert_eq!(typed.integer("max_rpm"), Some(7200));
+    assert_eq!(typed.float("max_rpm"), Some(7200.0), "Integers should convert to floats");
+    assert_eq!(typed.string("units"), Some("metric"), "Missing optional setting should take its default");
+    assert_eq!(typed.color("warning_color"), Some(egui::Color32::from_rgb(255, 0, 0)));
+    assert_eq!(typed.boolean("max_rpm"), None, "Accessor of the wrong type should return None");
+
+    // Test each invalid field is reported
+    let errors = Settings::parse(&metadata.settings, &config(&[("units", "furlongs"), ("warning_color", "red")])).unwrap_err();
+    let fields: Vec<&str> = errors.iter().map(|e| e.setting.as_str()).collect();
+    assert_eq!(fields, vec!["units", "max_rpm", "warning_color"], "Every bad field should be reported");
+    assert!(errors[0].message.contains("not one of metric, imperial"), "Choice error: {}", errors[0]);
+    assert!(errors[1].message.contains("required"), "Required error: {}", errors[1]);
+    let errors = Settings::parse(&metadata.settings, &config(&[("max_rpm", "20000")])).unwrap_err();
+    assert!(errors[0].message.contains("above the maximum of 12000"), "Range error: {}", errors[0]);
+    assert!(console::plugin::utils::validate_config(&metadata, &config(&[("max_rpm", "abc")])).is_err());
+
+    // Test invalid saved values fall back to their default
+    let (sanitized, errors) = settings::sanitize(&metadata.settings, &config(&[("max_rpm", "500"), ("extra", "kept")]));
+    assert_eq!(errors.len(), 1, "Only the out of range value should be reported");
+    assert_eq!(sanitized["max_rpm"], "8000", "Invalid value should be replaced by the default");
+    assert_eq!(sanitized["extra"], "kept", "Undeclared keys should be passed through");
+
+    // Test the manager refuses invalid configs and applies saved ones on registration
+    let mut manager = PluginManager::new();
+    manager.add_plugin(Box::new(SettingsPlugin::default()));
+    assert!(manager.set_plugin_config("Shift Light", config(&[("max_rpm", "0")])).is_err(), "Invalid config should be refused");
+    assert!(manager.get_plugin("Shift Light").unwrap().get_config().is_empty(), "Refused config should not be applied");
+    manager.set_plugin_config("Shift Light", config(&[("max_rpm", "9000"), ("units", "imperial")])).unwrap();
+    manager.remove_plugin("Shift Light");
+    manager.add_plugin(Box::new(SettingsPlugin::default()));
+    let applied = manager.get_plugin("Shift Light").unwrap().get_config();
+    assert_eq!(applied["max_rpm"], "9000", "Saved config should be applied when the plugin is added");
+    assert_eq!(applied["warning_color"], "#FF0000", "Defaults should be filled in");
+    assert!(manager.config_errors("Shift Light").is_empty());
+
+    // Test configs are saved in the data directory, where bundles look for them
+    let test_dir = std::env::temp_dir().join(format!("console_test_plugin_configs_{}", std::process::id()));
+    fs::create_dir_all(&test_dir).unwrap();
+    let storage = Storage::new(test_dir.join("data.json").to_str().unwrap());
+    manager.load_configs(&storage.plugin_configs_path()).expect("Missing configs should load as empty");
+    manager.save_configs().expect("Failed to save configs");
+    let bundle = storage.export_bundle(&[]).unwrap();
+    assert!(bundle.files["plugin_configs.json"].contains("9000"), "Bundle should carry the saved configs");
+    let mut reloaded = PluginManager::new();
+    reloaded.load_configs(&storage.plugin_configs_path()).unwrap();
+    reloaded.add_plugin(Box::new(SettingsPlugin::default()));
+    assert_eq!(reloaded.get_plugin("Shift Light").unwrap().get_config()["max_rpm"], "9000", "Saved config should load back");
+
+    // Cleanup
+    fs::remove_dir_all(&test_dir).unwrap();
+}
+
+#[test]
+fn test_setting_color_format() {
+    use console::plugin::settings::{format_color, parse_color};
+
+    // Test colors written by the settings editor read back the same
+    let opaque = egui::Color32::from_rgb(18, 171, 255);
+    assert_eq!(format_color(opaque), "#12ABFF");
+    assert_eq!(parse_color(&format_color(opaque)), Some(opaque));
+    let translucent = egui::Color32::from_rgba_unmultiplied(255, 0, 0, 128);
+    assert_eq!(format_color(translucent), "#FF000080", "Alpha should only be written when not opaque");
+    assert_eq!(parse_color(&format_color(translucent)), Some(translucent));
+    assert_eq!(parse_color("12ABFF"), None, "Colors need a leading #");
+}
+
+/// Plugin that publishes a lap on every update and records the events it receives
+struct EventPlugin {
+    name: String,
+    subscriptions: Vec<console::plugin::events::EventKind>,
+    sender: Option<console::plugin::events::EventSender>,
+    enabled: bool,
+    laps: u32,
+    received: std::sync::Arc<std::sync::Mutex<Vec<console::plugin::events::Envelope>>>,
+}
+
+impl EventPlugin {
+    fn new(name: &str, subscriptions: Vec<console::plugin::events::EventKind>) -> Self {
+        Self { name: name.to_string(), subscriptions, sender: None, enabled: true, laps: 0, received: Default::default() }
+    }
+}
+
+impl Plugin for EventPlugin {
+    fn init(&mut self) {}
+
+    fn update(&mut self, _data: &TelemetryData) {
+        use console::plugin::events::Event;
+        if self.name == "Lap Timer" {
+            self.laps += 1;
+            if let Some(sender) = &self.sender {
+                sender.publish(Event::LapCompleted { lap: self.laps, time_secs: 92.5 });
+            }
+        }
+    }
+
+    fn render(&self, _ui: &mut egui::Ui) {}
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata { name: self.name.clone(), ..PluginMetadata::default() }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn connect_events(&mut self, events: console::plugin::events::EventSender) {
+        self.sender = Some(events);
+    }
+
+    fn subscriptions(&self) -> Vec<console::plugin::events::EventKind> {
+        self.subscriptions.clone()
+    }
+
+    fn on_event(&mut self, event: &console::plugin::events::Envelope) {
+        use console::plugin::events::Event;
+        self.received.lock().unwrap().push(event.clone());
+        // Reply to each lap, to check replies wait for the next dispatch
+        if let (Event::LapCompleted { lap, .. }, Some(sender)) = (&event.event, &self.sender) {
+            sender.publish(Event::Custom { topic: "shift_light.flash".to_string(), payload: serde_json::json!({ "lap": lap }) });
+        }
+    }
+}
+
+#[test]
+fn test_plugin_event_bus() {
+    use console::plugin::PluginManager;
+    use console::plugin::events::{Envelope, Event, EventKind, HOST_SOURCE};
+
+    let timer = EventPlugin::new("Lap Timer", vec![EventKind::LapCompleted, EventKind::Custom("shift_light.flash".to_string())]);
+    let shift_light = EventPlugin::new("Shift Light", vec![EventKind::LapCompleted, EventKind::ButtonPressed]);
+    let logger = EventPlugin::new("Logger", vec![EventKind::PluginLoaded]);
+    let (timer_events, shift_events, logger_events) = (timer.received.clone(), shift_light.received.clone(), logger.received.clone());
+
+    let mut manager = PluginManager::new();
+    manager.add_plugin(Box::new(timer));
+    manager.add_plugin(Box::new(shift_light));
+    manager.add_plugin(Box::new(logger));
+    manager.init_plugins();
+
+    // Test host lifecycle events reach subscribers
+    manager.dispatch_events();
+    let loaded: Vec<Event> = logger_events.lock().unwrap().iter().map(|e| e.event.clone()).collect();
+    assert_eq!(loaded.len(), 3, "Logger should hear about every plugin loaded");
+    assert_eq!(loaded[0], Event::PluginLoaded { name: "Lap Timer".to_string() });
+    assert!(shift_events.lock().unwrap().is_empty(), "Unsubscribed events should not be delivered");
+
+    // Test a plugin event reaches subscribers but not its publisher
+    manager.update_plugins(&TelemetryData::new());
+    let expected = Envelope { source: "Lap Timer".to_string(), event: Event::LapCompleted { lap: 1, time_secs: 92.5 } };
+    assert_eq!(*shift_events.lock().unwrap(), vec![expected], "Shift light should receive the lap");
+    assert!(timer_events.lock().unwrap().is_empty(), "Publisher should not receive its own event");
+
+    // Test events published while handling are delivered on the next dispatch
+    assert_eq!(manager.dispatch_events(), 1, "Reply should be delivered on the next dispatch");
+    let reply = timer_events.lock().unwrap()[0].clone();
+    assert_eq!(reply.source, "Shift Light");
+    assert_eq!(reply.event, Event::Custom { topic: "shift_light.flash".to_string(), payload: serde_json::json!({ "lap": 1 }) });
+
+    // Test events from the host and the JSON form
+    shift_events.lock().unwrap().clear();
+    manager.publish(Event::ButtonPressed { button: "mode".to_string() });
+    manager.dispatch_events();
+    assert_eq!(shift_events.lock().unwrap()[0].source, HOST_SOURCE);
+    let json = serde_json::to_value(Event::ButtonPressed { button: "mode".to_string() }).unwrap();
+    assert_eq!(json, serde_json::json!({ "type": "button_pressed", "button": "mode" }));
+
+    // Test disabled plugins receive nothing
+    manager.disable_plugin("Shift Light");
+    shift_events.lock().unwrap().clear();
+    manager.publish(Event::ButtonPressed { button: "mode".to_string() });
+    assert_eq!(manager.dispatch_events(), 0, "Disabled plugins should not receive events");
+}
+
+/// Data source plugin: a fake wideband controller publishing AFR, a lambda
+/// channel derived from it, or a plugin trying to overwrite other channels
+struct SourcePlugin {
+    name: String,
+    dependencies: Vec<String>,
+    channels: Vec<console::plugin::sources::PluginChannel>,
+    afr: f64,
+    enabled: bool,
+}
+
+impl SourcePlugin {
+    fn new(name: &str, dependencies: &[&str], channels: Vec<console::plugin::sources::PluginChannel>) -> Self {
+        Self {
+            name: name.to_string(),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            channels,
+            afr: 14.7,
+            enabled: true,
+        }
+    }
+}
+
+impl Plugin for SourcePlugin {
+    fn init(&mut self) {}
+
+    fn update(&mut self, _data: &TelemetryData) {}
+
+    fn render(&self, _ui: &mut egui::Ui) {}
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata { name: self.name.clone(), dependencies: self.dependencies.clone(), ..PluginMetadata::default() }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn channels(&self) -> Vec<console::plugin::sources::PluginChannel> {
+        self.channels.clone()
+    }
+
+    fn produce(&mut self, data: &mut TelemetryData) {
+        match self.name.as_str() {
+            "Wideband" => {
+                self.afr += 1.0;
+                data.extra.insert("afr".to_string(), self.afr);
+            }
+            "Lambda" => {
+                if let Some(afr) = data.channel_value("afr") {
+                    data.extra.insert("lambda".to_string(), afr / 14.7);
+                }
+            }
+            _ => {
+                data.speed = 999.0;
+                data.extra.insert("afr".to_string(), 0.0);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_plugin_data_sources() {
+    use console::plugin::PluginManager;
+    use console::plugin::sources::PluginChannel;
+    use console::storage::{compression, rollup};
+
+    let afr = PluginChannel { alert_above: Some(16.0), ..PluginChannel::new("afr", "AFR") };
+    let mut manager = PluginManager::new();
+    manager.add_plugin(Box::new(SourcePlugin::new("Lambda", &["Wideband"], vec![PluginChannel::new("lambda", "")])));
+    manager.add_plugin(Box::new(SourcePlugin::new("Wideband", &[], vec![afr])));
+    manager.add_plugin(Box::new(SourcePlugin::new("Rogue", &[], vec![PluginChannel::new("speed", "mph"), PluginChannel::new("afr", "AFR")])));
+
+    // Test clashing channel names are refused
+    let refused = manager.init_plugins();
+    assert_eq!(refused.len(), 2, "Both of Rogue's channels should be refused: {:?}", refused);
+    assert!(refused.iter().all(|(plugin, _)| plugin == "Rogue"));
+    assert!(refused[0].1.contains("built-in"), "Built-in name should be refused: {}", refused[0].1);
+    assert!(refused[1].1.contains("already published by 'Wideband'"), "Taken name should be refused: {}", refused[1].1);
+    assert_eq!(manager.plugin_channels().len(), 2);
+
+    // Test sources and derived channels fill the frame, in dependency order
+    let mut data = TelemetryData { speed: 40.0, ..TelemetryData::new() };
+    manager.produce_telemetry(&mut data);
+    assert_eq!(data.channel_value("afr"), Some(15.7), "Source channel should be published");
+    assert!((data.channel_value("lambda").unwrap() - 15.7 / 14.7).abs() < 1e-9, "Derived channel should use this frame's source value");
+    assert_eq!(data.speed, 40.0, "Plugins should not overwrite built-in channels");
+    assert!(manager.channel_alerts(&data).is_empty(), "No alert within limits");
+
+    // Test plugin channels raise alerts
+    manager.produce_telemetry(&mut data);
+    assert_eq!(manager.channel_alerts(&data), vec!["afr high: 16.70 AFR".to_string()]);
+
+    // Test plugin channels are recorded, compressed and rolled up like built-in ones
+    let session: Vec<TelemetryData> = (0..5)
+        .map(|i| {
+            let mut point = TelemetryData { timestamp: 1_000 + i, ..Default::default() };
+            manager.produce_telemetry(&mut point);
+            point
+        })
+        .collect();
+    let json = serde_json::to_string(&session).unwrap();
+    let restored: Vec<TelemetryData> = serde_json::from_str(&json).unwrap();
+    for (name, value) in &session[4].extra {
+        assert!((restored[4].extra[name] - value).abs() < 1e-9, "Plugin channel {} should survive storage", name);
+    }
+    let old: TelemetryData = serde_json::from_str(r#"{"speed":1.0,"rpm":0.0,"engine_temp":0.0,"fuel_level":0.0,"battery_voltage":0.0,"oil_pressure":0.0,"throttle_position":0.0,"brake_pressure":0.0,"gear":0,"timestamp":0,"latitude":null,"longitude":null,"altitude":null,"acceleration":0.0,"brake_temp":0.0,"tire_pressure_fl":0.0,"tire_pressure_fr":0.0,"tire_pressure_rl":0.0,"tire_pressure_rr":0.0}"#).unwrap();
+    assert!(old.extra.is_empty(), "Records without plugin channels should still load");
+    let (compressed, _) = compression::compress_session(&session, &compression::CompressionTolerances::default());
+    let decompressed = compression::decompress_session(&compressed);
+    assert_eq!(decompressed[2].extra.get("afr"), session[2].extra.get("afr"), "Compression should keep plugin channels");
+    let tier = rollup::build_tier(&session, 60);
+    assert!(tier.channels.contains_key("lambda"), "Rollups should include plugin channels");
+
+    // Test a disabled source leaves its channels and the derived ones empty
+    manager.disable_plugin("Wideband");
+    manager.produce_telemetry(&mut data);
+    assert_eq!(data.channel_value("afr"), None, "Disabled source should publish nothing");
+    assert_eq!(data.channel_value("lambda"), None, "Derived channel should follow its source");
+}
+
+/// Media player plugin controlled by buttons, swipes and its volume slider
+#[derive(Default)]
+struct MediaPlugin {
+    track: i32,
+    volume: f64,
+}
+
+impl Plugin for MediaPlugin {
+    fn init(&mut self) {}
+
+    fn update(&mut self, _data: &TelemetryData) {}
+
+    fn render(&self, _ui: &mut egui::Ui) {}
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata { name: "Media".to_string(), category: PluginCategory::Entertainment, ..PluginMetadata::default() }
+    }
+
+    fn get_config(&self) -> std::collections::HashMap<String, String> {
+        [("track".to_string(), self.track.to_string()), ("volume".to_string(), self.volume.to_string())].into()
+    }
+
+    fn handle_input(&mut self, input: &console::plugin::input::Interaction) -> bool {
+        use console::plugin::input::{Interaction, SwipeDirection};
+        match input {
+            Interaction::Action { name } if name == "next" => self.track += 1,
+            Interaction::Action { name } if name == "previous" => self.track -= 1,
+            Interaction::Swipe { direction: SwipeDirection::Left } => self.track += 1,
+            Interaction::Control { id, value } if id == "volume" => self.volume = *value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[test]
+fn test_plugin_input() {
+    use console::plugin::PluginManager;
+    use console::plugin::input::{classify_gesture, Interaction, KeyBindings, SwipeDirection};
+    use console::plugin::manifest::PluginManifest;
+    use console::plugin::wasm::WasmPlugin;
+    use egui::{pos2, Rect};
+
+    // Test presses are classified by distance and duration
+    let panel = Rect::from_min_max(pos2(100.0, 100.0), pos2(400.0, 300.0));
+    assert_eq!(classify_gesture(pos2(150.0, 120.0), pos2(152.0, 121.0), 0.1, panel), Some(Interaction::Tap { x: 50.0, y: 20.0 }));
+    assert_eq!(classify_gesture(pos2(150.0, 120.0), pos2(150.0, 120.0), 1.0, panel), Some(Interaction::LongPress { x: 50.0, y: 20.0 }));
+    assert_eq!(classify_gesture(pos2(300.0, 200.0), pos2(180.0, 210.0), 0.2, panel), Some(Interaction::Swipe { direction: SwipeDirection::Left }));
+    assert_eq!(classify_gesture(pos2(300.0, 250.0), pos2(290.0, 150.0), 0.2, panel), Some(Interaction::Swipe { direction: SwipeDirection::Up }));
+    assert_eq!(classify_gesture(pos2(300.0, 200.0), pos2(330.0, 200.0), 0.2, panel), None, "Short drags are neither taps nor swipes");
+
+    // Test key bindings, default and from a file
+    let bindings = KeyBindings::default();
+    assert_eq!(bindings.action(egui::Key::ArrowRight), Some("next"));
+    let bindings_path = "test_key_bindings.json";
+    assert_eq!(KeyBindings::load(std::path::Path::new(bindings_path)).unwrap(), bindings, "Missing file should give the defaults");
+    fs::write(bindings_path, r#"{"bindings": {"F1": "mute"}}"#).unwrap();
+    let custom = KeyBindings::load(std::path::Path::new(bindings_path)).unwrap();
+    assert_eq!(custom.action(egui::Key::F1), Some("mute"));
+    assert_eq!(custom.action(egui::Key::ArrowRight), None, "Only configured keys should be bound");
+    fs::remove_file(bindings_path).unwrap();
+
+    // Test interactions change plugin state
+    let mut manager = PluginManager::new();
+    manager.add_plugin(Box::new(MockPlugin { initialized: false, update_count: 0, enabled: true }));
+    manager.add_plugin(Box::new(MediaPlugin::default()));
+    manager.init_plugins();
+    assert!(manager.send_input("Media", &Interaction::Control { id: "volume".to_string(), value: 0.4 }));
+    assert!(manager.send_input("Media", &Interaction::Swipe { direction: SwipeDirection::Left }));
+    assert!(!manager.send_input("Media", &Interaction::Tap { x: 1.0, y: 1.0 }), "Unused input should be reported as such");
+    let config = manager.get_plugin("Media").unwrap().get_config();
+    assert_eq!((config["track"].as_str(), config["volume"].as_str()), ("1", "0.4"), "Plugin state should follow its input");
+    assert_eq!(manager.focused_plugin(), Some("Media"), "Interacted plugin should get the focus");
+
+    // Test hardware actions go to the plugin that uses them
+    assert_eq!(manager.dispatch_action("next").as_deref(), Some("Media"));
+    assert_eq!(manager.get_plugin("Media").unwrap().get_config()["track"], "2");
+    assert_eq!(manager.dispatch_action("eject"), None, "Unused action should not be handled");
+
+    // Test sandboxed plugins receive input as JSON
+    let wat = r#"(module
+      (import "console" "log" (func $log (param i32 i32)))
+      (memory (export "memory") 1)
+      (data (i32.const 0) "{\"commands\":[]}")
+      (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+      (func (export "render") (result i64) (i64.const 15))
+      (func (export "on_input") (param $ptr i32) (param $len i32) (result i32)
+        (call $log (local.get $ptr) (local.get $len))
+        (i32.const 1)))"#;
+    let mut plugin = WasmPlugin::new(wat.as_bytes(), PluginManifest { name: "remote".to_string(), ..Default::default() }).unwrap();
+    assert!(plugin.handle_input(&Interaction::Action { name: "next".to_string() }), "Module should report the input as used");
+    assert_eq!(plugin.log().last().map(|s| s.as_str()), Some(r#"{"type":"action","name":"next"}"#));
+}
+
+#[test]
+fn test_plugin_registry() {
+    use console::plugin::PluginManager;
+    use console::plugin::manifest::PluginManifest;
+    use console::plugin::registry::{InstallOutcome, PluginArchive, PluginRegistry};
+    use std::path::Path;
+    
+    let test_dir = "test_registry_dir";
+    let plugin_dir = Path::new(test_dir).join("plugins");
+    let _ = fs::remove_dir_all(test_dir);
+    fs::create_dir_all(test_dir).unwrap();
+    let module = r#"(module
+      (memory (export "memory") 1)
+      (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+      (func (export "render") (result i64) (i64.const 0)))"#;
+    let manifest = |version: &str| PluginManifest {
+        id: "com.example.gauge".to_string(),
+        name: "Gauge".to_string(),
+        version: version.to_string(),
+        abi_version: Some(1),
+        entry: "gauge.wasm".to_string(),
+        ..Default::default()
+    };
+    let pack = |version: &str| {
+        let path = Path::new(test_dir).join(format!("gauge-{}.plugin", version));
+        PluginArchive::pack(manifest(version), module.as_bytes().to_vec()).save(&path).unwrap();
+        path
+    };
+    
+    // Test names are unique in the manager
+    let mut manager = PluginManager::new();
+    assert!(manager.add_plugin(Box::new(MockPlugin { initialized: false, update_count: 0, enabled: true })));
+    assert!(!manager.add_plugin(Box::new(MockPlugin { initialized: false, update_count: 0, enabled: true })), "Duplicate name should be refused");
+    assert_eq!(manager.get_all_metadata().len(), 1, "Only one plugin should be registered");
+    
+    // Test installing writes the plugin and its manifest, and the loader picks them up
+    let mut registry = PluginRegistry::open(&plugin_dir).unwrap();
+    assert_eq!(registry.install(&pack("1.0.0")).unwrap(), InstallOutcome::Installed { id: "com.example.gauge".to_string(), version: "1.0.0".to_string() });
+    assert!(plugin_dir.join("gauge.json").exists(), "Manifest should be installed next to the plugin");
+    assert_eq!(manager.load_plugins_from_dir(plugin_dir.to_str().unwrap()).loaded, vec!["Gauge".to_string()], "Installed plugin should load");
+    assert_eq!(manager.plugin_manifest("Gauge").map(|m| m.id.as_str()), Some("com.example.gauge"));
+    assert!(manager.plugin_by_id("com.example.gauge").is_some(), "Plugin should be found by id");
+    assert!(manager.plugin_by_id("Mock Plugin").is_some(), "Plugins without a manifest should be found by name");
+    
+    // Test upgrades, downgrades and pinning
+    assert!(matches!(registry.install(&pack("1.1.0")).unwrap(), InstallOutcome::Upgraded { ref from, ref to, .. } if from == "1.0.0" && to == "1.1.0"));
+    let error = registry.install(&pack("1.0.0")).unwrap_err().to_string();
+    assert!(error.contains("not newer"), "Downgrade should be refused: {}", error);
+    registry.set_pinned("com.example.gauge", true).unwrap();
+    let error = registry.install(&pack("2.0.0")).unwrap_err().to_string();
+    assert!(error.contains("pinned"), "Pinned plugin should not be upgraded: {}", error);
+    let reopened = PluginRegistry::open(&plugin_dir).unwrap();
+    assert_eq!(reopened.get("com.example.gauge").map(|p| (p.version.as_str(), p.pinned)), Some(("1.1.0", true)), "Registry should be saved");
+    registry.set_pinned("com.example.gauge", false).unwrap();
+    assert!(registry.install(&pack("2.0.0")).is_ok(), "Unpinned plugin should upgrade");
+    
+    // Test tampered and incompatible archives are refused
+    let tampered_path = Path::new(test_dir).join("tampered.plugin");
+    let mut tampered = PluginArchive::pack(manifest("3.0.0"), module.as_bytes().to_vec());
+    tampered.content.extend_from_slice(b" ");
+    tampered.save(&tampered_path).unwrap();
+    let error = registry.install(&tampered_path).unwrap_err().to_string();
+    assert!(error.contains("checksum"), "Tampered archive should be refused: {}", error);
+    let mut newer_abi = manifest("3.0.0");
+    newer_abi.abi_version = Some(99);
+    PluginArchive::pack(newer_abi, module.as_bytes().to_vec()).save(&tampered_path).unwrap();
+    let error = registry.install(&tampered_path).unwrap_err().to_string();
+    assert!(error.contains("ABI version 99"), "Incompatible archive should be refused: {}", error);
+    assert_eq!(registry.get("com.example.gauge").unwrap().version, "2.0.0", "Refused archives should leave the plugin alone");
+    
+    // Test an upgrade whose plugin file can't be written leaves the installed files alone
+    let blocker = plugin_dir.join(".gauge.wasm.partial");
+    fs::create_dir_all(blocker.join("busy")).unwrap();
+    assert!(registry.install(&pack("3.0.0")).is_err(), "Upgrade should fail when the plugin file can't be staged");
+    let installed: PluginManifest = serde_json::from_str(&fs::read_to_string(plugin_dir.join("gauge.json")).unwrap()).unwrap();
+    assert_eq!(installed.version, "2.0.0", "The manifest should still describe the installed plugin file");
+    assert!(!plugin_dir.join(".gauge.json.partial").exists(), "Staged files should be cleaned up");
+    fs::remove_dir_all(&blocker).unwrap();
+    assert!(!plugin_dir.join(".registry.json.partial").exists() && plugin_dir.join("registry.json").exists(), "Registry should be saved in place");
+    
+    // Test the loader refuses a plugin file modified after installation
+    assert!(registry.verify("com.example.gauge").is_ok());
+    fs::write(plugin_dir.join("gauge.wasm"), format!("{}\n", module)).unwrap();
+    assert!(registry.verify("com.example.gauge").is_err(), "Modified file should fail verification");
+    let report = PluginManager::new().load_plugins_from_dir(plugin_dir.to_str().unwrap());
+    assert!(report.rejected.iter().any(|(_, e)| e.contains("checksum")), "Modified plugin should not load: {:?}", report.rejected);
+    
+    // Test two plugins can't share an id
+    let clash_dir = Path::new(test_dir).join("clash");
+    fs::create_dir_all(&clash_dir).unwrap();
+    for name in ["a", "b"] {
+        fs::write(clash_dir.join(format!("{}.wasm", name)), module).unwrap();
+        fs::write(clash_dir.join(format!("{}.json", name)), format!(r#"{{"id": "com.example.same", "name": "{}"}}"#, name)).unwrap();
+    }
+    let report = PluginManager::new().load_plugins_from_dir(clash_dir.to_str().unwrap());
+    assert_eq!(report.loaded, vec!["a".to_string()], "First plugin with the id should load");
+    assert!(report.rejected[0].1.contains("already used by 'a'"), "Clashing id should be refused: {}", report.rejected[0].1);
+    
+    // Test removing deletes the files
+    registry.remove("com.example.gauge").unwrap();
+    assert!(!plugin_dir.join("gauge.wasm").exists() && !plugin_dir.join("gauge.json").exists(), "Files should be removed");
+    assert!(registry.installed().next().is_none(), "Registry should be empty");
+    
+    // Cleanup
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+/// Restricted plugin that records what it sees and publishes what it can
+struct TripPlugin {
+    sender: Option<console::plugin::events::EventSender>,
+    seen: std::sync::Arc<std::sync::Mutex<Vec<(f64, f64)>>>,
+    alerts: std::sync::Arc<std::sync::Mutex<u32>>,
+}
+
+impl Plugin for TripPlugin {
+    fn init(&mut self) {}
+
+    fn update(&mut self, data: &TelemetryData) {
+        use console::plugin::events::Event;
+        self.seen.lock().unwrap().push((data.speed, data.rpm));
+        if let Some(sender) = &self.sender {
+            sender.publish(Event::LapCompleted { lap: 1, time_secs: 90.0 });
+            sender.publish(Event::TrackChanged { track: "Spa".to_string() });
+        }
+    }
+
+    fn render(&self, _ui: &mut egui::Ui) {}
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: "Trip Computer".to_string(),
+            permissions: vec![
+                "read_channel:speed".to_string(),
+                "write_channel:trip_km".to_string(),
+                "publish:lap_completed".to_string(),
+                "subscribe:alert_raised".to_string(),
+            ],
+            ..PluginMetadata::default()
+        }
+    }
+
+    fn connect_events(&mut self, events: console::plugin::events::EventSender) {
+        self.sender = Some(events);
+    }
+
+    fn subscriptions(&self) -> Vec<console::plugin::events::EventKind> {
+        vec![console::plugin::events::EventKind::AlertRaised]
+    }
+
+    fn on_event(&mut self, _event: &console::plugin::events::Envelope) {
+        *self.alerts.lock().unwrap() += 1;
+    }
+
+    fn channels(&self) -> Vec<console::plugin::sources::PluginChannel> {
+        use console::plugin::sources::PluginChannel;
+        vec![PluginChannel::new("trip_km", "km"), PluginChannel::new("odometer_km", "km")]
+    }
+
+    fn produce(&mut self, data: &mut TelemetryData) {
+        data.extra.insert("trip_km".to_string(), 12.5);
+        data.extra.insert("odometer_km".to_string(), 40000.0);
+    }
+}
+
+#[test]
+fn test_plugin_permissions() {
+    use console::plugin::PluginManager;
+    use console::plugin::events::{Event, EventKind};
+    use console::plugin::permissions::{Permission, PermissionDecisions, PermissionSet};
+    use console::plugin::manifest::PluginManifest;
+    use console::plugin::wasm::WasmPlugin;
+    
+    // Test permissions parse from and print to their declared form
+    let permission: Permission = "read_channel:rpm".parse().unwrap();
+    assert_eq!(permission, Permission::ReadChannel("rpm".to_string()));
+    assert_eq!("storage".parse::<Permission>().unwrap().to_string(), "storage");
+    assert!("read_channel".parse::<Permission>().is_err(), "Scope should be required");
+    assert!("teleport:moon".parse::<Permission>().is_err(), "Unknown kinds should be refused");
+    assert_eq!(Permission::Subscribe("*".to_string()).describe(), "receive all events");
+    assert!(Permission::ReadChannel("*".to_string()).covers(&permission), "Wildcard should cover every channel");
+    
+    // Test plugins that declare permissions get nothing until the user decides
+    let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let alerts = std::sync::Arc::new(std::sync::Mutex::new(0));
+    let listener = EventPlugin::new("Listener", vec![EventKind::LapCompleted, EventKind::TrackChanged]);
+    let received = listener.received.clone();
+    let mut manager = PluginManager::new();
+    manager.add_plugin(Box::new(TripPlugin { sender: None, seen: seen.clone(), alerts: alerts.clone() }));
+    manager.add_plugin(Box::new(listener));
+    manager.init_plugins();
+    assert!(manager.granted_permissions("Listener").is_none(), "Built-in plugins without declarations should be trusted");
+    let pending: Vec<String> = manager.pending_permissions().into_iter().map(|(n, p)| format!("{} {}", n, p)).collect();
+    assert_eq!(pending.len(), 4, "Every declared permission should be asked for: {:?}", pending);
+    let data = TelemetryData { speed: 50.0, rpm: 3000.0, ..Default::default() };
+    manager.update_plugins(&data);
+    assert_eq!(seen.lock().unwrap().last(), Some(&(0.0, 0.0)), "Undecided channels should be hidden");
+    
+    // Test allowed permissions take effect immediately, denied ones stay closed
+    manager.decide_permission("Trip Computer", "read_channel:speed".parse().unwrap(), true);
+    manager.decide_permission("Trip Computer", "write_channel:trip_km".parse().unwrap(), true);
+    manager.decide_permission("Trip Computer", "publish:lap_completed".parse().unwrap(), true);
+    manager.decide_permission("Trip Computer", "subscribe:alert_raised".parse().unwrap(), false);
+    assert!(manager.pending_permissions().is_empty(), "Decided permissions shouldn't be asked again");
+    received.lock().unwrap().clear();
+    manager.update_plugins(&data);
+    assert_eq!(seen.lock().unwrap().last(), Some(&(50.0, 0.0)), "Only the allowed channel should be visible");
+    manager.dispatch_events();
+    let kinds: Vec<EventKind> = received.lock().unwrap().iter().map(|e| e.event.kind()).collect();
+    assert_eq!(kinds, vec![EventKind::LapCompleted], "Only permitted topics should be published");
+    manager.publish(Event::AlertRaised { message: "low fuel".to_string() });
+    manager.dispatch_events();
+    assert_eq!(*alerts.lock().unwrap(), 0, "Denied subscription should not be delivered");
+    
+    // Test only permitted channels are published
+    let channels: Vec<&str> = manager.plugin_channels().iter().map(|(_, c)| c.name.as_str()).collect();
+    assert_eq!(channels, vec!["trip_km"], "Unpermitted channel should be refused");
+    let mut frame = TelemetryData::default();
+    manager.produce_telemetry(&mut frame);
+    assert_eq!(frame.extra.get("trip_km"), Some(&12.5));
+    assert!(!frame.extra.contains_key("odometer_km"), "Unpermitted value should be dropped");
+    
+    // Test decisions are saved and read back
+    let decisions_path = std::path::Path::new("test_permissions.json");
+    let mut decisions = PermissionDecisions::default();
+    decisions.decide("Trip Computer", permission.clone(), true);
+    decisions.save(decisions_path).unwrap();
+    let loaded = PermissionDecisions::load(decisions_path).unwrap();
+    assert_eq!(loaded, decisions, "Decisions should round-trip");
+    assert_eq!(loaded.granted("Trip Computer", &[permission.clone(), Permission::Storage]).granted, vec![permission]);
+    fs::remove_file(decisions_path).unwrap();
+    
+    // Test WASM host functions only use what was granted, even if the manifest asks for more
+    let test_dir = "test_permission_dir";
+    let _ = fs::remove_dir_all(test_dir);
+    fs::create_dir_all(test_dir).unwrap();
+    let secret_path = format!("{}/secret.txt", test_dir);
+    fs::write(&secret_path, "secret").unwrap();
+    let wat = r#"(module
+      (import "console" "log" (func $log (param i32 i32)))
+      (import "console" "read_file" (func $read_file (param i32 i32 i32 i32) (result i32)))
+      (memory (export "memory") 1)
+      (data (i32.const 0) "PATH")
+      (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+      (func (export "init")
+        (call $log (i32.const 8192) (call $read_file (i32.const 0) (i32.const PATH_LEN) (i32.const 8192) (i32.const 64))))
+      (func (export "render") (result i64) (i64.const 0)))"#
+        .replace("PATH_LEN", &secret_path.len().to_string())
+        .replace("PATH", &secret_path);
+    let manifest = PluginManifest { name: "reader".to_string(), read_paths: vec![test_dir.into()], ..Default::default() };
+    let mut plugin = WasmPlugin::new(wat.as_bytes(), manifest.clone()).unwrap();
+    plugin.set_permissions(&PermissionSet::default());
+    plugin.init();
+    assert!(plugin.log().iter().any(|l| l.starts_with("denied: read")), "Read should be denied until the user allows it");
+    let mut plugin = WasmPlugin::new(wat.as_bytes(), manifest.clone()).unwrap();
+    plugin.set_permissions(&PermissionSet { granted: vec![Permission::ReadFile(test_dir.into())] });
+    plugin.init();
+    assert_eq!(plugin.log().last().map(|s| s.as_str()), Some("secret"), "Allowed read should succeed");
+    let mut manager = PluginManager::new();
+    manager.add_plugin(Box::new(WasmPlugin::new(wat.as_bytes(), manifest).unwrap()));
+    assert_eq!(
+        manager.pending_permissions(),
+        vec![("reader".to_string(), Permission::ReadFile(test_dir.into()))],
+        "Manifest grants should become permission requests"
+    );
+    
+    // Test decisions are kept by manifest id, so they survive a plugin being renamed
+    let plugin_dir = format!("{}/plugins", test_dir);
+    fs::create_dir_all(&plugin_dir).unwrap();
+    fs::write(format!("{}/reader.wasm", plugin_dir), &wat).unwrap();
+    let write_manifest = |name: &str| {
+        let manifest = format!(r#"{{"id": "com.example.reader", "name": "{}", "read_paths": ["logs"]}}"#, name);
+        fs::write(format!("{}/reader.json", plugin_dir), manifest).unwrap();
+    };
+    write_manifest("Reader");
+    let decisions_path = std::path::Path::new(test_dir).join("plugin_permissions.json");
+    let mut manager = PluginManager::new();
+    manager.load_plugins_from_dir(&plugin_dir);
+    manager.load_permissions(&decisions_path).unwrap();
+    let (name, requested) = manager.pending_permissions().remove(0);
+    manager.decide_permission(&name, requested.clone(), true);
+    manager.save_permissions().unwrap();
+    let saved = PermissionDecisions::load(&decisions_path).unwrap();
+    assert_eq!(saved.plugins.keys().collect::<Vec<_>>(), vec!["com.example.reader"], "Decisions should be stored by id");
+    write_manifest("Log Reader");
+    let mut manager = PluginManager::new();
+    manager.load_plugins_from_dir(&plugin_dir);
+    manager.load_permissions(&decisions_path).unwrap();
+    assert!(manager.pending_permissions().is_empty(), "A renamed plugin shouldn't be asked again");
+    assert_eq!(manager.granted_permissions("Log Reader").unwrap().granted, vec![requested], "A renamed plugin should keep its grants");
+    
+    // Cleanup
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+/// Plugin whose update takes longer than a frame
+struct SlowPlugin;
+
+impl Plugin for SlowPlugin {
+    fn init(&mut self) {}
+
+    fn update(&mut self, _data: &TelemetryData) {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+
+    fn render(&self, _ui: &mut egui::Ui) {}
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata { name: "Trip Statistics".to_string(), ..PluginMetadata::default() }
+    }
+}
+
+#[test]
+fn test_plugin_profiler() {
+    use console::plugin::PluginManager;
+    use console::plugin::input::{KeyBindings, TOGGLE_PROFILER_ACTION};
+    use console::plugin::profiler::{CallStats, PROFILE_WINDOW};
+    use console::plugin::manifest::PluginManifest;
+    use console::plugin::wasm::WasmPlugin;
+    use std::time::Duration;
+    
+    // Test statistics over the rolling window
+    let mut stats = CallStats::default();
+    assert!(stats.summary().is_none(), "No calls should give no statistics");
+    for ms in 1..=100 {
+        stats.record(Duration::from_millis(ms));
+    }
+    let summary = stats.summary().unwrap();
+    assert_eq!((summary.min, summary.max), (Duration::from_millis(1), Duration::from_millis(100)));
+    assert_eq!(summary.avg, Duration::from_micros(50_500));
+    assert_eq!(summary.p99, Duration::from_millis(99), "p99 should be the nearest rank");
+    for _ in 0..PROFILE_WINDOW {
+        stats.record(Duration::from_millis(2));
+    }
+    let summary = stats.summary().unwrap();
+    assert_eq!((summary.samples, summary.max), (PROFILE_WINDOW, Duration::from_millis(2)), "Old calls should leave the window");
+    
+    // Test the manager times each plugin's calls and flags the one over budget
+    let module = r#"(module
+      (memory (export "memory") 2)
+      (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+      (func (export "render") (result i64) (i64.const 0)))"#;
+    let mut manager = PluginManager::new();
+    manager.add_plugin(Box::new(MockPlugin { initialized: false, update_count: 0, enabled: true }));
+    manager.add_plugin(Box::new(SlowPlugin));
+    manager.add_plugin(Box::new(WasmPlugin::new(module.as_bytes(), PluginManifest { name: "Gauge".to_string(), ..Default::default() }).unwrap()));
+    manager.init_plugins();
+    manager.end_frame();
+    manager.update_plugins(&TelemetryData::default());
+    let over_budget: Vec<String> = manager.end_frame().into_iter().map(|(name, _)| name).collect();
+    assert_eq!(over_budget, vec!["Trip Statistics".to_string()], "Only the slow plugin should exceed the budget");
+    let update = manager.plugin_profile("Trip Statistics").and_then(|p| p.summary("update")).expect("Update should be timed");
+    assert!(update.min >= Duration::from_millis(20), "Timing should cover the call: {:?}", update.min);
+    assert_eq!(manager.plugin_profile("Mock Plugin").and_then(|p| p.summary("update")).map(|s| s.samples), Some(1));
+    assert!(manager.plugin_profile("Mock Plugin").unwrap().summary("init").is_some(), "Init should be timed too");
+    assert!(manager.end_frame().is_empty(), "Budget should apply per frame");
+    
+    // Test memory use is reported where the plugin knows it
+    assert_eq!(manager.plugin_memory("Gauge"), Some(2 * 65536), "WASM plugins should report their linear memory");
+    assert_eq!(manager.plugin_memory("Mock Plugin"), None);
+    
+    // Test the overlay has a default hotkey
+    assert_eq!(KeyBindings::default().action(egui::Key::F12), Some(TOGGLE_PROFILER_ACTION));
+}
+
+/// Plugin recording the rpm of each update it gets
+struct ScheduledPlugin {
+    name: String,
+    schedule: console::plugin::schedule::UpdateSchedule,
+    seen: std::sync::Arc<std::sync::Mutex<Vec<f64>>>,
+}
+
+impl ScheduledPlugin {
+    fn new(name: &str, schedule: console::plugin::schedule::UpdateSchedule) -> Self {
+        Self { name: name.to_string(), schedule, seen: Default::default() }
+    }
+}
+
+impl Plugin for ScheduledPlugin {
+    fn init(&mut self) {}
+
+    fn update(&mut self, data: &TelemetryData) {
+        self.seen.lock().unwrap().push(data.rpm);
+    }
+
+    fn render(&self, _ui: &mut egui::Ui) {}
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata { name: self.name.clone(), ..PluginMetadata::default() }
+    }
+
+    fn update_schedule(&self) -> console::plugin::schedule::UpdateSchedule {
+        self.schedule.clone()
+    }
+}
+
+#[test]
+fn test_plugin_update_schedule() {
+    use console::plugin::PluginManager;
+    use console::plugin::manifest::PluginManifest;
+    use console::plugin::schedule::{UpdateSchedule, UpdateThread};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    
+    // Test rates and manifest declarations
+    assert_eq!(UpdateSchedule::hz(10.0), UpdateSchedule::Interval(Duration::from_millis(100)));
+    assert_eq!(UpdateSchedule::hz(0.0), UpdateSchedule::EveryTick, "A rate that isn't positive should mean every tick");
+    let manifest = PluginManifest { update_rate: Some(2.0), ..Default::default() };
+    assert_eq!(manifest.update_schedule(), Some(UpdateSchedule::Interval(Duration::from_millis(500))));
+    let manifest = PluginManifest { update_rate: Some(2.0), update_on: vec!["speed".to_string()], ..Default::default() };
+    assert_eq!(manifest.update_schedule(), Some(UpdateSchedule::OnChange(vec!["speed".to_string()])), "Watched channels should take precedence");
+    assert_eq!(PluginManifest::default().update_schedule(), None);
+    
+    // Test each plugin is only updated when its schedule says so
+    let every = ScheduledPlugin::new("Gauge", UpdateSchedule::EveryTick);
+    let slow = ScheduledPlugin::new("Trip Statistics", UpdateSchedule::hz(10.0));
+    let watcher = ScheduledPlugin::new("Shift Light", UpdateSchedule::OnChange(vec!["rpm".to_string()]));
+    let (every_seen, slow_seen, watcher_seen) = (every.seen.clone(), slow.seen.clone(), watcher.seen.clone());
+    let mut manager = PluginManager::new();
+    manager.add_plugin(Box::new(every));
+    manager.add_plugin(Box::new(slow));
+    manager.add_plugin(Box::new(watcher));
+    manager.init_plugins();
+    
+    let start = Instant::now();
+    let mut data = TelemetryData { rpm: 1000.0, ..Default::default() };
+    assert_eq!(manager.update_plugins_at(&data, start), 3, "Every plugin should get a first update");
+    assert_eq!(manager.update_plugins_at(&data, start + Duration::from_millis(16)), 1, "Only the every-tick plugin should run again");
+    data.rpm = 1500.0;
+    assert_eq!(manager.update_plugins_at(&data, start + Duration::from_millis(33)), 2);
+    data.speed = 40.0;
+    assert_eq!(manager.update_plugins_at(&data, start + Duration::from_millis(100)), 2, "Unwatched channels shouldn't wake a watcher");
+    assert_eq!(*slow_seen.lock().unwrap(), vec![1000.0, 1500.0]);
+    assert_eq!(*watcher_seen.lock().unwrap(), vec![1000.0, 1500.0]);
+    assert_eq!(every_seen.lock().unwrap().len(), 4);
+    assert!(manager.plugin_profile("Trip Statistics").and_then(|p| p.summary("update")).is_some_and(|s| s.samples == 2), "Skipped ticks shouldn't be timed");
+    
+    // Test the update thread acquires telemetry and updates plugins until dropped
+    let mut manager = PluginManager::new();
+    let plugin = ScheduledPlugin::new("Gauge", UpdateSchedule::EveryTick);
+    let seen = plugin.seen.clone();
+    manager.add_plugin(Box::new(plugin));
+    manager.init_plugins();
+    let manager = Arc::new(Mutex::new(manager));
+    let telemetry = Arc::new(Mutex::new(TelemetryData::default()));
+    let thread = UpdateThread::start(manager.clone(), telemetry.clone(), Duration::from_millis(5), |data| data.rpm += 1.0);
+    std::thread::sleep(Duration::from_millis(100));
+    drop(thread);
+    let updates = seen.lock().unwrap().clone();
+    assert!(updates.len() > 2, "Plugin should be updated on each tick: {:?}", updates);
+    assert!(updates.windows(2).all(|w| w[1] > w[0]), "Each update should see newly acquired data");
+    assert_eq!(telemetry.lock().unwrap().rpm, *updates.last().unwrap());
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(seen.lock().unwrap().len(), updates.len(), "Updates should stop with the thread");
+    assert!(manager.lock().unwrap().get_plugin("Gauge").is_some());
+}
+
+/// Plugin remembering its best lap across restarts
+struct LapRecordPlugin {
+    name: String,
+    permissions: Vec<String>,
+    state: Option<console::plugin::state::PluginState>,
+    best: Option<f64>,
+}
+
+impl LapRecordPlugin {
+    fn new(name: &str, permissions: &[&str]) -> Self {
+        Self { name: name.to_string(), permissions: permissions.iter().map(|p| p.to_string()).collect(), state: None, best: None }
+    }
+}
+
+impl Plugin for LapRecordPlugin {
+    fn init(&mut self) {
+        self.best = self.state.as_ref().and_then(|s| s.get::<(String, f64)>("best_lap").ok().flatten()).map(|(_, time)| time);
+    }
+
+    fn update(&mut self, _data: &TelemetryData) {}
+
+    fn render(&self, _ui: &mut egui::Ui) {}
+
+    fn get_metadata(&self) -> PluginMetadata {
+        PluginMetadata { name: self.name.clone(), permissions: self.permissions.clone(), ..PluginMetadata::default() }
+    }
+
+    fn connect_state(&mut self, state: console::plugin::state::PluginState) {
+        self.state = Some(state);
+    }
+}
+
+#[test]
+fn test_plugin_state() {
+    use console::plugin::PluginManager;
+    use console::plugin::permissions::Permission;
+    use console::plugin::state::{PluginState, StateStore};
+    use console::plugin::manifest::PluginManifest;
+    use console::plugin::wasm::WasmPlugin;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    
+    let test_dir = "test_plugin_state";
+    let _ = fs::remove_dir_all(test_dir);
+    fs::create_dir_all(test_dir).unwrap();
+    let storage = Storage::new(&format!("{}/data.json", test_dir));
+    let state_path = storage.plugin_state_path();
+    
+    // Test typed values and transactions
+    let mut manager = PluginManager::new();
+    manager.open_state(Path::new(&state_path)).unwrap();
+    manager.add_plugin(Box::new(LapRecordPlugin::new("Lap Records", &[])));
+    manager.add_plugin(Box::new(LapRecordPlugin::new("Odometer", &[])));
+    manager.init_plugins();
+    let laps = manager.plugin_state("Lap Records").unwrap();
+    assert_eq!(laps.get::<(String, f64)>("best_lap").unwrap(), None);
+    laps.put("best_lap", &("Laguna Seca".to_string(), 92.4)).unwrap();
+    laps.put("favourites", &vec!["Spa", "Suzuka"]).unwrap();
+    assert_eq!(laps.get::<(String, f64)>("best_lap").unwrap(), Some(("Laguna Seca".to_string(), 92.4)));
+    assert!(laps.get::<u32>("favourites").is_err(), "Reading a value as the wrong type should fail");
+    let failed = laps.transaction(|tx| {
+        tx.put("best_lap", &("Laguna Seca".to_string(), 91.0))?;
+        tx.remove("favourites");
+        Err("lap was invalid".into())
+    });
+    assert!(failed.is_err());
+    assert_eq!(laps.keys().unwrap(), vec!["best_lap".to_string(), "favourites".to_string()], "A failed transaction should change nothing");
+    laps.transaction(|tx| {
+        let (track, _) = tx.get::<(String, f64)>("best_lap")?.unwrap();
+        tx.put("best_lap", &(track, 91.0))?;
+        tx.remove("favourites");
+        Ok(())
+    }).unwrap();
+    assert_eq!(laps.keys().unwrap(), vec!["best_lap".to_string()]);
+    assert_eq!(manager.plugin_state("Odometer").unwrap().keys().unwrap(), Vec::<String>::new(), "Plugins should not see each other's state");
+    
+    // Test the quota refuses writes that would grow a plugin's state too far
+    let store = Arc::new(Mutex::new(StateStore::default()));
+    store.lock().unwrap().set_quota(64);
+    let small = PluginState::new("small", store.clone());
+    small.put("odometer", &1234.5).unwrap();
+    let error = small.put("notes", &"x".repeat(100)).unwrap_err();
+    assert!(error.to_string().contains("quota"), "Unexpected error: {}", error);
+    assert_eq!(small.get::<f64>("odometer").unwrap(), Some(1234.5), "A refused write should keep the previous state");
+    assert!(small.usage().unwrap() <= 64);
+    
+    // Test state survives a restart
+    drop(manager);
+    let mut manager = PluginManager::new();
+    manager.open_state(Path::new(&state_path)).unwrap();
+    manager.add_plugin(Box::new(LapRecordPlugin::new("Lap Records", &[])));
+    manager.init_plugins();
+    assert_eq!(manager.plugin_state("Lap Records").unwrap().get::<(String, f64)>("best_lap").unwrap(), Some(("Laguna Seca".to_string(), 91.0)));
+    
+    // Test restricted plugins need the storage permission
+    manager.add_plugin(Box::new(LapRecordPlugin::new("Fuel Log", &["storage"])));
+    manager.init_plugins();
+    let fuel = manager.plugin_state("Fuel Log").unwrap();
+    let error = fuel.put("litres", &40.0).unwrap_err();
+    assert!(error.to_string().contains("not permitted"), "Unexpected error: {}", error);
+    manager.decide_permission("Fuel Log", Permission::Storage, true);
+    fuel.put("litres", &40.0).expect("Allowed plugin should be able to store data");
+    manager.decide_permission("Fuel Log", Permission::Storage, false);
+    assert!(fuel.get::<f64>("litres").is_err(), "Denying storage should take effect immediately");
+    
+    // Test WASM plugins store JSON values through host functions
+    let module = r#"(module
+      (import "console" "state_put" (func $put (param i32 i32 i32 i32) (result i32)))
+      (import "console" "state_get" (func $get (param i32 i32 i32 i32) (result i32)))
+      (memory (export "memory") 1)
+      (data (i32.const 0) "odometer12.5")
+      (data (i32.const 1024) "{\"commands\":[]}")
+      (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+      (func (export "init")
+        (if (i32.ne (call $put (i32.const 0) (i32.const 8) (i32.const 8) (i32.const 4)) (i32.const 0)) (then unreachable))
+        (if (i32.ne (call $get (i32.const 0) (i32.const 8) (i32.const 100) (i32.const 16)) (i32.const 4)) (then unreachable)))
+      (func (export "render") (result i64) (i64.const 0x4000000000f)))"#;
+    manager.add_plugin(Box::new(WasmPlugin::new(module.as_bytes(), PluginManifest { name: "Trip Odometer".to_string(), ..Default::default() }).unwrap()));
+    manager.init_plugins();
+    assert!(!matches!(manager.plugin_status("Trip Odometer"), Some(console::plugin::PluginStatus::Error(_))), "Module should store and read back its state: {:?}", manager.plugin_status("Trip Odometer"));
+    assert_eq!(manager.plugin_state("Trip Odometer").unwrap().get::<f64>("odometer").unwrap(), Some(12.5));
+    
+    // Test plugin state is included in snapshots and migrations
+    let snapshot = storage.create_snapshot().unwrap();
+    assert!(snapshot.file("data.json.plugin_state.json").is_some(), "Snapshots should back up plugin state");
+    let report = storage.migrate_all().unwrap();
+    assert!(report.up_to_date.contains(&state_path), "Plugin state should be checked by migrations");
+    
+    fs::remove_dir_all(test_dir).unwrap();
+}